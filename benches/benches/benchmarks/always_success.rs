@@ -1,5 +1,7 @@
-use crate::benchmarks::util::{gen_always_success_block, new_always_success_chain};
-use ckb_store::{self, ChainStore};
+use crate::benchmarks::util::{
+    gen_always_success_block, new_always_success_chain, new_always_success_chain_with_config,
+};
+use ckb_store::{self, CellSetMode, ChainStore, StoreConfig};
 use criterion::{criterion_group, Criterion};
 use std::sync::Arc;
 
@@ -171,8 +173,62 @@ fn bench(c: &mut Criterion) {
     );
 }
 
+// benchmark processing 20 blocks on main branch with the cell set kept in memory and read
+// from the store, the in-memory cell set grows with the txs of the genesis block
+fn bench_cell_set_mode(c: &mut Criterion) {
+    for (name, cell_set_mode) in &[
+        (
+            "always_success main_branch hamt cell set",
+            CellSetMode::Hamt,
+        ),
+        (
+            "always_success main_branch store cell set",
+            CellSetMode::Store,
+        ),
+    ] {
+        c.bench_function_over_inputs(
+            name,
+            move |b, txs_size| {
+                b.iter_with_setup(
+                    || {
+                        let store_config = StoreConfig {
+                            cell_set_mode: *cell_set_mode,
+                            ..Default::default()
+                        };
+                        let chains =
+                            new_always_success_chain_with_config(**txs_size, 2, store_config);
+                        let (ref chain1, ref shared1) = chains.0[0];
+                        let (ref chain2, ref shared2) = chains.0[1];
+                        let mut blocks = vec![shared1
+                            .snapshot()
+                            .get_block(&shared1.genesis_hash())
+                            .unwrap()];
+                        let mut parent = blocks[0].clone();
+                        (0..20).for_each(|_| {
+                            let block = gen_always_success_block(&mut blocks, &parent, shared2);
+                            chain2
+                                .process_block(Arc::new(block.clone()), false)
+                                .expect("process block OK");
+                            parent = block;
+                        });
+                        (chain1.clone(), blocks)
+                    },
+                    |(chain, blocks)| {
+                        blocks.into_iter().skip(1).for_each(|block| {
+                            chain
+                                .process_block(Arc::new(block), true)
+                                .expect("process block OK");
+                        });
+                    },
+                )
+            },
+            SIZES,
+        );
+    }
+}
+
 criterion_group!(
     name = process_block;
     config = Criterion::default().sample_size(10);
-    targets = bench
+    targets = bench, bench_cell_set_mode
 );
@@ -2,4 +2,5 @@ pub mod always_success;
 pub mod next_epoch_ext;
 pub mod overall;
 pub mod secp_2in2out;
+pub mod store;
 pub mod util;
@@ -0,0 +1,138 @@
+use ckb_db::RocksDB;
+use ckb_store::{ChainDB, ChainStore, StoreConfig, COLUMNS};
+use ckb_types::{
+    bytes::Bytes,
+    core::{
+        capacity_bytes, BlockBuilder, BlockView, Capacity, TransactionBuilder, TransactionMeta,
+    },
+    packed::{Byte32, CellInput, CellOutput, OutPoint},
+    prelude::*,
+};
+use criterion::{criterion_group, Criterion};
+
+#[cfg(not(feature = "ci"))]
+const SIZES: &[usize] = &[500usize, 2000];
+
+#[cfg(feature = "ci")]
+const SIZES: &[usize] = &[5usize];
+
+fn gen_block(txs_size: usize) -> BlockView {
+    let transactions = (0..txs_size).map(|i| {
+        TransactionBuilder::default()
+            .input(CellInput::new(
+                OutPoint::new(Default::default(), i as u32),
+                0,
+            ))
+            .output(
+                CellOutput::new_builder()
+                    .capacity(capacity_bytes!(100).pack())
+                    .build(),
+            )
+            .output_data(Bytes::new().pack())
+            .build()
+    });
+    BlockBuilder::default()
+        .number(1u64.pack())
+        .transactions(transactions)
+        .build()
+}
+
+fn bench(c: &mut Criterion) {
+    // benchmark committing a single block to the store
+    c.bench_function_over_inputs(
+        "store attach_block commit",
+        |b, txs_size| {
+            b.iter_with_setup(
+                || {
+                    let store = ChainDB::new(RocksDB::open_tmp(COLUMNS), Default::default());
+                    (store, gen_block(**txs_size))
+                },
+                |(store, block)| {
+                    let txn = store.begin_transaction();
+                    txn.insert_block(&block).expect("insert block");
+                    txn.attach_block(&block).expect("attach block");
+                    txn.commit().expect("commit");
+                },
+            )
+        },
+        SIZES,
+    );
+}
+
+// A block whose inputs spend the outputs of a few parents, in turn
+fn gen_fan_in_block(parents: &[Byte32], inputs: usize) -> BlockView {
+    let transaction = TransactionBuilder::default()
+        .inputs((0..inputs).map(|i| {
+            let out_point = OutPoint::new(parents[i % parents.len()].clone(), 0);
+            CellInput::new(out_point, 0)
+        }))
+        .build();
+    BlockBuilder::default()
+        .number(2u64.pack())
+        .transaction(transaction)
+        .build()
+}
+
+// Look up the meta of the parent of each input, as the cell provider does
+fn resolve_fan_in_block(store: &ChainDB, block: &BlockView) {
+    for input in block.transactions()[0].inputs() {
+        let tx_hash = input.previous_output().tx_hash();
+        store.get_tx_meta(&tx_hash).expect("meta");
+    }
+}
+
+fn bench_tx_meta_cache(c: &mut Criterion) {
+    // a block spending many outputs of a few parents looks up the same metas repeatedly
+    const PARENTS: usize = 10;
+    let setup = |tx_meta_cache_size: Option<usize>| {
+        let config = StoreConfig {
+            tx_meta_cache_size,
+            ..Default::default()
+        };
+        let store = ChainDB::new(RocksDB::open_tmp(COLUMNS), config);
+        let tx_hashes: Vec<_> = gen_block(PARENTS).tx_hashes().to_vec();
+        let txn = store.begin_transaction();
+        for tx_hash in &tx_hashes {
+            let meta = TransactionMeta::new(1, 0, Default::default(), 1, false);
+            txn.update_cell_set(tx_hash, &meta.pack())
+                .expect("update cell set");
+        }
+        txn.commit().expect("commit");
+        (store, tx_hashes)
+    };
+
+    // the hit rate of resolving each block once, starting with an empty cache
+    for inputs in SIZES {
+        let (store, parents) = setup(Some(PARENTS));
+        resolve_fan_in_block(&store, &gen_fan_in_block(&parents, *inputs));
+        let cache = store
+            .cache()
+            .and_then(|cache| cache.tx_meta.as_ref())
+            .expect("tx meta cache");
+        let lookups = cache.hits() + cache.misses();
+        println!(
+            "store fan-in tx_meta hit rate/{}: {} hits of {} lookups ({:.1}%)",
+            inputs,
+            cache.hits(),
+            lookups,
+            cache.hits() as f64 * 100.0 / lookups as f64
+        );
+    }
+
+    for (name, tx_meta_cache_size) in &[
+        ("store fan-in get_tx_meta", None),
+        ("store fan-in get_tx_meta cached", Some(PARENTS)),
+    ] {
+        c.bench_function_over_inputs(
+            name,
+            move |b, inputs| {
+                let (store, parents) = setup(*tx_meta_cache_size);
+                let block = gen_fan_in_block(&parents, **inputs);
+                b.iter(|| resolve_fan_in_block(&store, &block))
+            },
+            SIZES,
+        );
+    }
+}
+
+criterion_group!(attach_block, bench, bench_tx_meta_cache);
@@ -7,7 +7,7 @@ use ckb_shared::{
     shared::{Shared, SharedBuilder},
     Snapshot,
 };
-use ckb_store::ChainStore;
+use ckb_store::{ChainStore, StoreConfig};
 use ckb_system_scripts::BUNDLED_CELL;
 use ckb_test_chain_utils::always_success_cell;
 use ckb_types::{
@@ -37,6 +37,14 @@ impl Chains {
 }
 
 pub fn new_always_success_chain(txs_size: usize, chains_num: usize) -> Chains {
+    new_always_success_chain_with_config(txs_size, chains_num, StoreConfig::default())
+}
+
+pub fn new_always_success_chain_with_config(
+    txs_size: usize,
+    chains_num: usize,
+    store_config: StoreConfig,
+) -> Chains {
     let (_, _, always_success_script) = always_success_cell();
     let tx = create_always_success_tx();
     let dao = genesis_dao_data(vec![&tx]).unwrap();
@@ -76,6 +84,7 @@ pub fn new_always_success_chain(txs_size: usize, chains_num: usize) -> Chains {
     for _ in 0..chains_num {
         let (shared, table) = SharedBuilder::default()
             .consensus(consensus.clone())
+            .store_config(store_config)
             .build()
             .unwrap();
         let chain_service = ChainService::new(shared.clone(), table);
@@ -1,33 +1,305 @@
-use ckb_error::Error;
-use ckb_store::{ChainStore, StoreTransaction};
+use ckb_error::{Error, InternalErrorKind};
+use ckb_store::{ChainDB, ChainStore, StoreTransaction};
 use ckb_types::{
-    core::{BlockView, TransactionMeta},
+    core::{BlockNumber, BlockView, TransactionMeta},
     packed::Byte32,
     prelude::*,
 };
 use im::hashmap as hamt;
 use im::hashmap::HashMap as HamtMap;
+use std::collections::HashSet;
 
+/// Trace a cell set mutation under the target `ckb-chain::cell_set`, so the mutation logs
+/// of two nodes can be diffed. Compiled out of the release builds.
+macro_rules! trace_cell_set {
+    ($( $args:tt )*) => {
+        if cfg!(debug_assertions) {
+            ckb_logger::trace_target!("ckb-chain::cell_set", $( $args )*);
+        }
+    }
+}
+
+/// How attaching a block mutates the cell set, every meta is the state after the block
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CellSetChange {
+    /// Transactions of the block which still have live outputs
+    pub new_metas: Vec<(Byte32, TransactionMeta)>,
+    /// Transactions before the block whose outputs are partly spent by the block
+    pub updated_metas: Vec<(Byte32, TransactionMeta)>,
+    /// Transactions before the block whose outputs are all dead after the block
+    pub removed: Vec<Byte32>,
+}
+
+/// Attach the block to the cell set in the store and to the in-memory one if it's given
+///
+/// Without the in-memory cell set, e.g. in `CellSetMode::Store`, the entries touched by the
+/// block are read from the transaction instead.
 pub fn attach_block_cell(
     txn: &StoreTransaction,
     block: &BlockView,
-    cell_set: &mut HamtMap<Byte32, TransactionMeta>,
+    cell_set: Option<&mut HamtMap<Byte32, TransactionMeta>>,
 ) -> Result<(), Error> {
+    match cell_set {
+        Some(cell_set) => {
+            let change = attach_cell_set_change(block, cell_set)?;
+            if cfg!(debug_assertions) {
+                trace_attach_block_cell(block, &change, cell_set);
+            }
+            apply_cell_set_change(txn, &change, Some(cell_set))?;
+        }
+        None => {
+            let touched = load_touched_cell_set(txn, block);
+            let change = attach_cell_set_change(block, &touched)?;
+            if cfg!(debug_assertions) {
+                trace_attach_block_cell(block, &change, &touched);
+            }
+            apply_cell_set_change(txn, &change, None)?;
+        }
+    }
+    txn.attach_cell_spenders(block)
+}
+
+// The stored entries of the transactions the block spends, and in debug builds of the
+// block's own transactions for the duplication check of `attach_cell_set_change`
+fn load_touched_cell_set(
+    txn: &StoreTransaction,
+    block: &BlockView,
+) -> HamtMap<Byte32, TransactionMeta> {
+    let mut touched = HamtMap::new();
+    for tx in block.transactions() {
+        let mut tx_hashes: Vec<Byte32> = tx.input_pts_iter().map(|cell| cell.tx_hash()).collect();
+        if cfg!(debug_assertions) {
+            tx_hashes.push(tx.hash());
+        }
+        for tx_hash in tx_hashes {
+            if touched.contains_key(&tx_hash) {
+                continue;
+            }
+            if let Some(meta) = txn.get_tx_meta(&tx_hash) {
+                touched.insert(tx_hash, meta);
+            }
+        }
+    }
+    touched
+}
+
+fn trace_attach_block_cell(
+    block: &BlockView,
+    change: &CellSetChange,
+    cell_set: &HamtMap<Byte32, TransactionMeta>,
+) {
     for tx in block.transactions() {
         for cell in tx.input_pts_iter() {
-            if let hamt::Entry::Occupied(mut o) = cell_set.entry(cell.tx_hash().clone()) {
+            if cell_set.contains_key(&cell.tx_hash()) {
+                let index: u32 = cell.index().unpack();
+                trace_cell_set!(
+                    "set_dead {} {} in block {}",
+                    cell.tx_hash(),
+                    index,
+                    block.hash()
+                );
+            }
+        }
+    }
+    for (tx_hash, meta) in &change.new_metas {
+        trace_cell_set!(
+            "insert {} {} outputs in block {}",
+            tx_hash,
+            meta.len(),
+            block.hash()
+        );
+    }
+    for tx_hash in &change.removed {
+        trace_cell_set!("remove {} in block {}", tx_hash, block.hash());
+    }
+}
+
+/// Rebuild the stored cell set from the main chain blocks, e.g. when it's lost or suspected
+/// to be corrupted
+///
+/// Every block from genesis to the tip is attached to a fresh cell set in the same way as
+/// `attach_block_cell`, the stale entries are dropped. This is an offline tool, the in-memory
+/// cell set of a running chain is not updated.
+pub fn rebuild_cell_set(store: &ChainDB) -> Result<(), Error> {
+    let tip = match store.get_tip_header() {
+        Some(tip) => tip,
+        None => Err(InternalErrorKind::Database.reason("the store is not initialized"))?,
+    };
+    let mut stale = Vec::new();
+    store.traverse_cell_set(|tx_hash, _| {
+        stale.push(tx_hash);
+        Ok(())
+    })?;
+
+    let txn = store.begin_transaction();
+    txn.delete_cell_sets(&stale)?;
+    let mut cell_set = HamtMap::new();
+    for number in 0..=tip.number() {
+        match store
+            .get_block_hash(number)
+            .and_then(|hash| store.get_block(&hash))
+        {
+            Some(block) => attach_block_cell(&txn, &block, Some(&mut cell_set))?,
+            None => Err(InternalErrorKind::DataCorrupted
+                .reason(format!("main chain block {} is missing", number)))?,
+        }
+    }
+    txn.commit()
+}
+
+/// Materialize the cell set as of the main chain block `number`
+///
+/// The blocks are replayed forward from the nearest snapshot stored by `snapshot_cell_set`
+/// at or below `number`, or from genesis if there's none, so the cost grows linearly with
+/// the distance from that snapshot. The maps produced by consecutive blocks share most of
+/// their nodes, so keeping several of them around is cheap.
+pub fn cell_set_at<'a, S: ChainStore<'a>>(
+    store: &'a S,
+    number: BlockNumber,
+) -> Result<HamtMap<Byte32, TransactionMeta>, Error> {
+    let (start, mut cell_set) = match store.get_cell_set_snapshot(number) {
+        Some((anchor, entries)) => (anchor + 1, entries.into_iter().collect()),
+        None => (0, HamtMap::new()),
+    };
+    for n in start..=number {
+        let block =
+            match store
+                .get_block_hash(n)
+                .and_then(|hash| store.get_block(&hash))
+            {
+                Some(block) => block,
+                None => Err(InternalErrorKind::Database
+                    .reason(format!("main chain block {} is not stored", n)))?,
+            };
+        let change = attach_cell_set_change(&block, &cell_set)?;
+        for (tx_hash, meta) in change.new_metas.into_iter().chain(change.updated_metas) {
+            cell_set.insert(tx_hash, meta);
+        }
+        for tx_hash in &change.removed {
+            cell_set.remove(tx_hash);
+        }
+    }
+    Ok(cell_set)
+}
+
+/// Store the cell set as of the main chain block `number` as an anchor of `cell_set_at`
+///
+/// Every snapshot is a full copy of the cell set, so they should be taken sparsely, e.g.
+/// once per epoch.
+pub fn snapshot_cell_set(store: &ChainDB, number: BlockNumber) -> Result<(), Error> {
+    let cell_set = cell_set_at(store, number)?;
+    let header = match store
+        .get_block_hash(number)
+        .and_then(|hash| store.get_block_header(&hash))
+    {
+        Some(header) => header,
+        None => Err(InternalErrorKind::Database
+            .reason(format!("main chain block {} is not stored", number)))?,
+    };
+    let txn = store.begin_transaction();
+    txn.insert_cell_set_snapshot(&header, cell_set.iter())?;
+    txn.commit()
+}
+
+// im splits the hash into 5 bit chunks, so every node of the HAMT has up to 32 children
+const HAMT_BRANCHES: usize = 32;
+
+/// An estimate of the nodes of an in-memory cell set, see `cell_set_memory_report`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub entries: usize,
+    pub estimated_nodes: usize,
+    /// Nodes on the paths to the entries which differ from the baseline, they were copied
+    /// when the entries changed
+    pub estimated_unique_nodes: usize,
+    /// Nodes still shared with the baseline
+    pub estimated_shared_nodes: usize,
+}
+
+/// Estimate how many nodes the cell set has and how many of them are shared with the
+/// baseline, e.g. a cell set kept from an earlier block
+///
+/// im doesn't expose its nodes, so the HAMT is assumed to be balanced, which holds since
+/// the keys are hashes. A high unique ratio after a long history means re-materializing
+/// the map won't reclaim much across versions, a low one means the versions are cheap.
+pub fn cell_set_memory_report(
+    cell_set: &HamtMap<Byte32, TransactionMeta>,
+    baseline: &HamtMap<Byte32, TransactionMeta>,
+) -> MemoryReport {
+    let entries = cell_set.len();
+    let estimated_nodes = estimate_hamt_nodes(entries, entries);
+    let changed = if cell_set.ptr_eq(baseline) {
+        0
+    } else {
+        let updated = cell_set
+            .iter()
+            .filter(|(tx_hash, meta)| baseline.get(tx_hash) != Some(meta))
+            .count();
+        let removed = baseline
+            .keys()
+            .filter(|tx_hash| !cell_set.contains_key(tx_hash))
+            .count();
+        updated + removed
+    };
+    let estimated_unique_nodes = estimate_hamt_nodes(entries, changed).min(estimated_nodes);
+    MemoryReport {
+        entries,
+        estimated_nodes,
+        estimated_unique_nodes,
+        estimated_shared_nodes: estimated_nodes - estimated_unique_nodes,
+    }
+}
+
+// The nodes on the paths to `paths` entries of a balanced HAMT with `entries` entries, at
+// most one node per path in each level, the leaf level is the first one with a node for
+// every entry
+fn estimate_hamt_nodes(entries: usize, paths: usize) -> usize {
+    if paths == 0 {
+        return 0;
+    }
+    let mut nodes = 0;
+    let mut level_nodes: usize = 1;
+    loop {
+        nodes += level_nodes.min(paths);
+        if level_nodes >= entries {
+            return nodes;
+        }
+        level_nodes = level_nodes.saturating_mul(HAMT_BRANCHES);
+    }
+}
+
+/// Calculate what attaching the block does to the cell set without touching the store
+pub fn attach_cell_set_change(
+    block: &BlockView,
+    cell_set: &HamtMap<Byte32, TransactionMeta>,
+) -> Result<CellSetChange, Error> {
+    // cloning the hamt is cheap since the nodes are shared
+    let mut next = cell_set.clone();
+    let mut touched = Vec::new();
+    for (index, tx) in block.transactions().iter().enumerate() {
+        for cell in tx.input_pts_iter() {
+            let cell_tx_hash = cell.tx_hash();
+            if let hamt::Entry::Occupied(mut o) = next.entry(cell_tx_hash.clone()) {
                 o.get_mut().set_dead(cell.index().unpack());
                 if o.get().all_dead() {
-                    txn.delete_cell_set(&cell.tx_hash())?;
                     o.remove_entry();
-                } else {
-                    txn.update_cell_set(&cell.tx_hash(), &o.get().pack())?;
                 }
+                touched.push(cell_tx_hash);
             }
         }
         let tx_hash = tx.hash();
+        // A live entry for a new tx means the block is attached twice or the tx hash is
+        // duplicated, only checked in debug builds
+        if cfg!(debug_assertions) && next.contains_key(&tx_hash) {
+            Err(InternalErrorKind::DataCorrupted.reason(format!(
+                "transaction {} of block {} is already in cell set",
+                tx_hash,
+                block.hash()
+            )))?;
+        }
         let outputs_len = tx.outputs().len();
-        let meta = if tx.is_cellbase() {
+        // BlockVerifier ensures the cellbase is the first and only one in a block
+        let meta = if index == 0 && tx.is_cellbase() {
             TransactionMeta::new_cellbase(
                 block.number(),
                 block.epoch().number(),
@@ -44,26 +316,85 @@ pub fn attach_block_cell(
                 false,
             )
         };
-        txn.update_cell_set(&tx_hash, &meta.pack())?;
-        cell_set.insert(tx_hash.to_owned(), meta);
+        next.insert(tx_hash.clone(), meta);
+        touched.push(tx_hash);
     }
-    Ok(())
+
+    let mut change = CellSetChange::default();
+    let mut seen = HashSet::with_capacity(touched.len());
+    for tx_hash in touched {
+        if !seen.insert(tx_hash.clone()) {
+            continue;
+        }
+        match (cell_set.contains_key(&tx_hash), next.get(&tx_hash)) {
+            (false, Some(meta)) => change.new_metas.push((tx_hash, meta.clone())),
+            (true, Some(meta)) => change.updated_metas.push((tx_hash, meta.clone())),
+            (true, None) => change.removed.push(tx_hash),
+            // created and fully spent inside the block
+            (false, None) => {}
+        }
+    }
+    Ok(change)
 }
 
+/// Write the change into the store and the in-memory cell set if it's given
+pub fn apply_cell_set_change(
+    txn: &StoreTransaction,
+    change: &CellSetChange,
+    mut cell_set: Option<&mut HamtMap<Byte32, TransactionMeta>>,
+) -> Result<(), Error> {
+    for (tx_hash, meta) in change.new_metas.iter().chain(change.updated_metas.iter()) {
+        txn.update_cell_set(tx_hash, &meta.pack())?;
+        if let Some(ref mut cell_set) = cell_set {
+            cell_set.insert(tx_hash.clone(), meta.clone());
+        }
+    }
+    if let Some(cell_set) = cell_set {
+        for tx_hash in &change.removed {
+            cell_set.remove(tx_hash);
+        }
+    }
+    txn.delete_cell_sets(&change.removed)
+}
+
+/// Detach the block from the cell set in the store and from the in-memory one if it's
+/// given, the entries are read from the transaction otherwise
 pub fn detach_block_cell(
     txn: &StoreTransaction,
     block: &BlockView,
-    cell_set: &mut HamtMap<Byte32, TransactionMeta>,
+    mut cell_set: Option<&mut HamtMap<Byte32, TransactionMeta>>,
 ) -> Result<(), Error> {
-    for tx in block.transactions().iter().rev() {
-        txn.delete_cell_set(&tx.hash())?;
-        cell_set.remove(&tx.hash());
+    let transactions = block.transactions();
+    // the created txs are removed from the store together after the loop, it's fine
+    // since the inputs below never refer to a tx of this block once it's removed
+    let mut removed = Vec::with_capacity(transactions.len());
+    for tx in transactions.iter().rev() {
+        trace_cell_set!("remove {} in block {}", tx.hash(), block.hash());
+        if let Some(ref mut cell_set) = cell_set {
+            cell_set.remove(&tx.hash());
+        }
+        removed.push(tx.hash());
 
         for cell in tx.input_pts_iter() {
             let cell_tx_hash = cell.tx_hash();
             let index: usize = cell.index().unpack();
-            if let Some(tx_meta) = cell_set.get_mut(&cell_tx_hash) {
-                tx_meta.unset_dead(index);
+            trace_cell_set!(
+                "unset_dead {} {} in block {}",
+                cell_tx_hash,
+                index,
+                block.hash()
+            );
+            let tx_meta = match cell_set {
+                Some(ref mut cell_set) => cell_set.get_mut(&cell_tx_hash).map(|tx_meta| {
+                    tx_meta.unset_dead(index);
+                    tx_meta.clone()
+                }),
+                None => txn.get_tx_meta(&cell_tx_hash).map(|mut tx_meta| {
+                    tx_meta.unset_dead(index);
+                    tx_meta
+                }),
+            };
+            if let Some(tx_meta) = tx_meta {
                 txn.update_cell_set(&cell_tx_hash, &tx_meta.pack())?;
             } else {
                 // the tx is full dead, deleted from cellset, we need recover it when fork
@@ -91,11 +422,20 @@ pub fn detach_block_cell(
                         )
                     };
                     meta.unset_dead(index); // recover
+                    trace_cell_set!(
+                        "insert {} {} outputs in block {}",
+                        cell_tx_hash,
+                        meta.len(),
+                        block.hash()
+                    );
                     txn.update_cell_set(&cell_tx_hash, &meta.pack())?;
-                    cell_set.insert(cell_tx_hash, meta);
+                    if let Some(ref mut cell_set) = cell_set {
+                        cell_set.insert(cell_tx_hash, meta);
+                    }
                 }
             }
         }
     }
-    Ok(())
+    txn.delete_cell_sets(&removed)?;
+    txn.detach_cell_spenders(block)
 }
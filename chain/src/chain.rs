@@ -90,12 +90,16 @@ impl ForkChanges {
 }
 
 struct CellSetWrapper<'a> {
-    pub cell_set: &'a HamtMap<Byte32, TransactionMeta>,
+    // None if the cell set is read from the store
+    pub cell_set: Option<&'a HamtMap<Byte32, TransactionMeta>>,
     pub txn: &'a StoreTransaction,
 }
 
 impl<'a> CellSetWrapper<'a> {
-    pub fn new(cell_set: &'a HamtMap<Byte32, TransactionMeta>, txn: &'a StoreTransaction) -> Self {
+    pub fn new(
+        cell_set: Option<&'a HamtMap<Byte32, TransactionMeta>>,
+        txn: &'a StoreTransaction,
+    ) -> Self {
         CellSetWrapper { cell_set, txn }
     }
 }
@@ -104,7 +108,11 @@ impl<'a> CellProvider for CellSetWrapper<'a> {
     fn cell(&self, out_point: &OutPoint, with_data: bool) -> CellStatus {
         let tx_hash = out_point.tx_hash();
         let index = out_point.index().unpack();
-        match self.cell_set.get(&tx_hash) {
+        let tx_meta = match self.cell_set {
+            Some(cell_set) => cell_set.get(&tx_hash).cloned(),
+            None => self.txn.get_tx_meta(&tx_hash),
+        };
+        match tx_meta {
             Some(tx_meta) => match tx_meta.is_dead(index as usize) {
                 Some(false) => {
                     let mut cell_meta = self
@@ -294,7 +302,7 @@ impl ChainService {
         db_txn.insert_epoch_ext(&epoch.last_block_hash_in_previous_epoch(), &epoch)?;
 
         let shared_snapshot = Arc::clone(&self.shared.snapshot());
-        let mut cell_set = shared_snapshot.cell_set().clone();
+        let mut cell_set = shared_snapshot.cell_set().cloned();
         let origin_proposals = shared_snapshot.proposals();
         let current_tip_header = shared_snapshot.tip_header();
 
@@ -321,10 +329,12 @@ impl ChainService {
             // MUST update index before reconcile_main_chain
             self.reconcile_main_chain(&db_txn, &mut fork, need_verify, &mut cell_set)?;
 
-            db_txn.insert_tip_header(&block.header())?;
-            if new_epoch || fork.has_detached() {
-                db_txn.insert_current_epoch_ext(&epoch)?;
-            }
+            let current_epoch = if new_epoch || fork.has_detached() {
+                Some(&epoch)
+            } else {
+                None
+            };
+            db_txn.advance_tip(&block.header(), current_epoch)?;
             total_difficulty = cannon_total_difficulty.clone();
         } else {
             db_txn.insert_block_ext(&block.header().hash(), &ext)?;
@@ -412,11 +422,11 @@ impl ChainService {
         &self,
         fork: &ForkChanges,
         txn: &StoreTransaction,
-        cell_set: &mut HamtMap<Byte32, TransactionMeta>,
+        cell_set: &mut Option<HamtMap<Byte32, TransactionMeta>>,
     ) -> Result<(), Error> {
         for block in fork.detached_blocks().iter().rev() {
             txn.detach_block(block)?;
-            detach_block_cell(txn, block, cell_set)?;
+            detach_block_cell(txn, block, cell_set.as_mut())?;
         }
         Ok(())
     }
@@ -546,14 +556,14 @@ impl ChainService {
         txn: &StoreTransaction,
         fork: &mut ForkChanges,
         need_verify: bool,
-        cell_set: &mut HamtMap<Byte32, TransactionMeta>,
+        cell_set: &mut Option<HamtMap<Byte32, TransactionMeta>>,
     ) -> Result<(), Error> {
         let txs_verify_cache = self.shared.txs_verify_cache();
 
         let verified_len = fork.verified_len();
         for b in fork.attached_blocks().iter().take(verified_len) {
             txn.attach_block(b)?;
-            attach_block_cell(txn, b, cell_set)?;
+            attach_block_cell(txn, b, cell_set.as_mut())?;
         }
 
         let verify_context = VerifyContext::new(txn, self.shared.consensus());
@@ -579,7 +589,7 @@ impl ChainService {
 
                     let transactions = b.transactions();
                     let resolved = {
-                        let wrapper = CellSetWrapper::new(cell_set, txn);
+                        let wrapper = CellSetWrapper::new(cell_set.as_ref(), txn);
                         let cell_provider = OverlayCellProvider::new(&block_cp, &wrapper);
                         transactions
                             .iter()
@@ -605,7 +615,7 @@ impl ChainService {
                             ) {
                                 Ok((cycles, txs_fees)) => {
                                     txn.attach_block(b)?;
-                                    attach_block_cell(txn, b, cell_set)?;
+                                    attach_block_cell(txn, b, cell_set.as_mut())?;
                                     let mut mut_ext = ext.clone();
                                     mut_ext.verified = Some(true);
                                     mut_ext.txs_fees = txs_fees;
@@ -649,7 +659,7 @@ impl ChainService {
                 }
             } else {
                 txn.attach_block(b)?;
-                attach_block_cell(txn, b, cell_set)?;
+                attach_block_cell(txn, b, cell_set.as_mut())?;
                 let mut mut_ext = ext.clone();
                 mut_ext.verified = Some(true);
                 txn.insert_block_ext(&b.header().hash(), &mut_ext)?;
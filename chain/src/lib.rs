@@ -6,6 +6,6 @@
 
 pub mod chain;
 
-mod cell;
+pub mod cell;
 #[cfg(test)]
 mod tests;
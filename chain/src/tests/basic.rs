@@ -1,3 +1,7 @@
+use crate::cell::{
+    attach_block_cell, attach_cell_set_change, cell_set_at, cell_set_memory_report,
+    detach_block_cell, rebuild_cell_set, snapshot_cell_set,
+};
 use crate::chain::ChainController;
 use crate::tests::util::{
     create_always_success_tx, create_cellbase, create_multi_outputs_transaction,
@@ -6,9 +10,9 @@ use crate::tests::util::{
 };
 use ckb_chain_spec::consensus::{Consensus, ConsensusBuilder};
 use ckb_dao_utils::genesis_dao_data;
-use ckb_error::assert_error_eq;
+use ckb_error::{assert_error_eq, InternalError, InternalErrorKind};
 use ckb_shared::shared::Shared;
-use ckb_store::ChainStore;
+use ckb_store::{ChainStore, StoreTransaction};
 use ckb_types::core::error::OutPointError;
 use ckb_types::prelude::*;
 use ckb_types::{
@@ -17,11 +21,12 @@ use ckb_types::{
         capacity_bytes,
         cell::{CellMeta, CellProvider, CellStatus},
         BlockBuilder, BlockView, Capacity, EpochExt, EpochNumberWithFraction, HeaderView,
-        TransactionBuilder, TransactionInfo,
+        TransactionBuilder, TransactionInfo, TransactionMeta,
     },
-    packed::{CellInput, CellOutputBuilder, OutPoint, Script},
+    packed::{Byte32, CellInput, CellOutput, CellOutputBuilder, OutPoint, Script},
     U256,
 };
+use im::hashmap::HashMap as HamtMap;
 use std::sync::Arc;
 
 #[test]
@@ -856,3 +861,245 @@ fn test_next_epoch_ext() {
         );
     }
 }
+
+#[test]
+#[cfg(debug_assertions)]
+fn attach_block_cell_twice() {
+    let (_chain_controller, shared, parent) = start_chain(None);
+    let block = shared.store().get_block(&parent.hash()).unwrap();
+    let txn = shared.store().begin_transaction();
+    let mut cell_set = HamtMap::new();
+
+    assert!(attach_block_cell(&txn, &block, Some(&mut cell_set)).is_ok());
+    let err = attach_block_cell(&txn, &block, Some(&mut cell_set)).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<InternalError>().map(InternalError::kind),
+        Some(&InternalErrorKind::DataCorrupted)
+    );
+}
+
+#[test]
+fn attach_detach_block_cell_without_hamt() {
+    let (_chain_controller, shared, parent) = start_chain(None);
+    let mock_store = MockStore::new(&parent, shared.store());
+    let mut chain = MockChain::new(parent.clone(), shared.consensus());
+    let genesis_tx_hash = create_always_success_tx().hash();
+    chain.gen_block_with_commit_txs(
+        vec![create_transaction(&genesis_tx_hash, 1)],
+        &mock_store,
+        false,
+    );
+    let block = chain.blocks().last().unwrap().clone();
+    let mut tx_hashes: Vec<_> = block.tx_hashes().to_vec();
+    tx_hashes.push(genesis_tx_hash);
+    let metas = |txn: &StoreTransaction| -> Vec<_> {
+        tx_hashes
+            .iter()
+            .map(|tx_hash| txn.get_tx_meta(tx_hash))
+            .collect()
+    };
+
+    let mut cell_set = shared.snapshot().cell_set().cloned().unwrap();
+    let hamt_txn = shared.store().begin_transaction();
+    let store_txn = shared.store().begin_transaction();
+    let before = metas(&store_txn);
+
+    attach_block_cell(&hamt_txn, &block, Some(&mut cell_set)).unwrap();
+    attach_block_cell(&store_txn, &block, None).unwrap();
+    let attached = metas(&store_txn);
+    assert_eq!(metas(&hamt_txn), attached);
+    assert_eq!(attached[2], None);
+    assert_eq!(
+        tx_hashes
+            .iter()
+            .map(|tx_hash| cell_set.get(tx_hash).cloned())
+            .collect::<Vec<_>>(),
+        attached
+    );
+
+    detach_block_cell(&hamt_txn, &block, Some(&mut cell_set)).unwrap();
+    detach_block_cell(&store_txn, &block, None).unwrap();
+    assert_eq!(metas(&hamt_txn), before);
+    assert_eq!(metas(&store_txn), before);
+}
+
+#[test]
+fn test_attach_cell_set_change() {
+    let gen_outputs = |len: usize| {
+        (
+            vec![CellOutput::default(); len],
+            vec![Bytes::new(); len].pack(),
+        )
+    };
+    let new_tx = |inputs: Vec<OutPoint>, outputs_len: usize| {
+        let (outputs, outputs_data) = gen_outputs(outputs_len);
+        TransactionBuilder::default()
+            .inputs(
+                inputs
+                    .into_iter()
+                    .map(|out_point| CellInput::new(out_point, 0)),
+            )
+            .outputs(outputs)
+            .outputs_data(outputs_data)
+            .build()
+    };
+    let tx_a = new_tx(vec![OutPoint::new(Byte32::zero(), 0)], 2);
+    let tx_b = new_tx(vec![OutPoint::new(tx_a.hash(), 0)], 2);
+    let block = BlockBuilder::default()
+        .transaction(tx_a.clone())
+        .transaction(tx_b.clone())
+        .build();
+
+    let change = attach_cell_set_change(&block, &HamtMap::new()).unwrap();
+    let new_hashes: Vec<_> = change
+        .new_metas
+        .iter()
+        .map(|(hash, _)| hash.clone())
+        .collect();
+    assert_eq!(new_hashes, vec![tx_a.hash(), tx_b.hash()]);
+    assert_eq!(change.new_metas[0].1.is_dead(0), Some(true));
+    assert_eq!(change.new_metas[0].1.is_dead(1), Some(false));
+    assert!(change.updated_metas.is_empty());
+    assert!(change.removed.is_empty());
+
+    let cell_set: HamtMap<_, _> = change.new_metas.into_iter().collect();
+    let tx_c = new_tx(
+        vec![OutPoint::new(tx_a.hash(), 1), OutPoint::new(tx_b.hash(), 0)],
+        1,
+    );
+    let block = BlockBuilder::default().transaction(tx_c.clone()).build();
+
+    let change = attach_cell_set_change(&block, &cell_set).unwrap();
+    assert_eq!(change.new_metas.len(), 1);
+    assert_eq!(change.new_metas[0].0, tx_c.hash());
+    assert_eq!(change.updated_metas.len(), 1);
+    assert_eq!(change.updated_metas[0].0, tx_b.hash());
+    assert_eq!(change.updated_metas[0].1.is_dead(0), Some(true));
+    assert_eq!(change.removed, vec![tx_a.hash()]);
+    // the input cell set is untouched
+    assert_eq!(cell_set.get(&tx_a.hash()).unwrap().is_dead(1), Some(false));
+}
+
+#[test]
+fn test_rebuild_cell_set() {
+    let tx = TransactionBuilder::default()
+        .witness(Script::default().into_witness())
+        .input(CellInput::new(OutPoint::null(), 0))
+        .outputs(vec![
+            CellOutputBuilder::default()
+                .capacity(capacity_bytes!(100_000_000).pack())
+                .build();
+            100
+        ])
+        .outputs_data(vec![Bytes::new(); 100].pack())
+        .build();
+    let always_success_tx = create_always_success_tx();
+    let mut root_hash = tx.hash();
+    let dao = genesis_dao_data(vec![&tx, &always_success_tx]).unwrap();
+    let genesis_block = BlockBuilder::default()
+        .transaction(tx)
+        .transaction(always_success_tx)
+        .difficulty(U256::from(1000u64).pack())
+        .dao(dao)
+        .build();
+    let consensus = ConsensusBuilder::default()
+        .genesis_block(genesis_block)
+        .build();
+    let (chain_controller, shared, parent) = start_chain(Some(consensus));
+    let mock_store = MockStore::new(&parent, shared.store());
+    let mut chain = MockChain::new(parent.clone(), shared.consensus());
+
+    for i in 1..6 {
+        let tx = create_transaction(&root_hash, i as u8);
+        root_hash = tx.hash();
+        chain.gen_block_with_commit_txs(vec![tx], &mock_store, false);
+    }
+    for block in chain.blocks() {
+        chain_controller
+            .process_block(Arc::new(block.clone()), false)
+            .expect("process block ok");
+    }
+
+    let live_cell_set = |shared: &Shared| {
+        let mut cell_set = Vec::new();
+        shared
+            .store()
+            .traverse_cell_set(|tx_hash, tx_meta| {
+                cell_set.push((tx_hash, tx_meta.as_bytes()));
+                Ok(())
+            })
+            .unwrap();
+        cell_set
+    };
+    let expected = live_cell_set(&shared);
+
+    let txn = shared.store().begin_transaction();
+    txn.delete_cell_set(&expected[0].0).unwrap();
+    txn.update_cell_set(&Byte32::zero(), &Default::default())
+        .unwrap();
+    txn.commit().unwrap();
+    assert_ne!(live_cell_set(&shared), expected);
+
+    rebuild_cell_set(shared.store()).unwrap();
+    assert_eq!(live_cell_set(&shared), expected);
+
+    // the cell set at the tip is the live one
+    let tip_number = shared.store().get_tip_header().unwrap().number();
+    let mut cell_set: Vec<_> = cell_set_at(shared.store(), tip_number)
+        .unwrap()
+        .into_iter()
+        .map(|(tx_hash, meta)| (tx_hash, meta.pack().as_bytes()))
+        .collect();
+    cell_set.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(cell_set, expected);
+
+    // the first spent transaction is still live at genesis
+    let genesis = shared.consensus().genesis_block();
+    let cell_set = cell_set_at(shared.store(), 0).unwrap();
+    assert_eq!(cell_set.len(), genesis.transactions().len());
+    assert_eq!(
+        cell_set[&genesis.transactions()[0].hash()].is_dead(0),
+        Some(false)
+    );
+
+    // replayed from the nearest snapshot below
+    let anchor = tip_number / 2;
+    let replayed = cell_set_at(shared.store(), tip_number).unwrap();
+    snapshot_cell_set(shared.store(), anchor).unwrap();
+    assert_eq!(
+        shared
+            .store()
+            .get_cell_set_snapshot(tip_number)
+            .map(|(number, entries)| (number, entries.into_iter().collect())),
+        Some((anchor, cell_set_at(shared.store(), anchor).unwrap()))
+    );
+    assert_eq!(cell_set_at(shared.store(), tip_number).unwrap(), replayed);
+    assert_eq!(shared.store().get_cell_set_snapshot(anchor - 1), None);
+}
+
+#[test]
+fn test_cell_set_memory_report() {
+    let meta = TransactionMeta::new(0, 0, Byte32::zero(), 1, false);
+    let tx_hash = |i: u32| {
+        let mut hash = [0u8; 32];
+        hash[..4].copy_from_slice(&i.to_le_bytes());
+        Byte32::new_unchecked(Bytes::from(hash.to_vec()))
+    };
+    let baseline: HamtMap<_, _> = (1..=1000u32).map(|i| (tx_hash(i), meta.clone())).collect();
+    let report = cell_set_memory_report(&baseline, &baseline);
+    assert_eq!(report.entries, 1000);
+    // a root, 32 nodes in the second level and 1000 in the third one
+    assert_eq!(report.estimated_nodes, 1033);
+    assert_eq!(report.estimated_unique_nodes, 0);
+    assert_eq!(report.estimated_shared_nodes, 1033);
+
+    let mut cell_set = baseline.clone();
+    cell_set.remove(&tx_hash(1));
+    cell_set.insert(Byte32::zero(), meta);
+    let report = cell_set_memory_report(&cell_set, &baseline);
+    assert_eq!(report.entries, 1000);
+    // the removed and the inserted entries touch the root, 2 nodes in the second level
+    // and 2 leaves
+    assert_eq!(report.estimated_unique_nodes, 5);
+    assert_eq!(report.estimated_shared_nodes, 1028);
+}
@@ -7,4 +7,38 @@ pub struct DBConfig {
     #[serde(default)]
     pub path: PathBuf,
     pub options: Option<HashMap<String, String>>,
+    /// Overrides of the column family options, keyed by the column name, the columns not
+    /// listed use the rocksdb defaults
+    #[serde(default)]
+    pub columns: HashMap<String, ColumnOptions>,
+}
+
+/// The options of a column family which can only be set when the database is opened
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnOptions {
+    /// The size in bytes of the LRU block cache of the column
+    pub block_cache_size: Option<usize>,
+    /// Enable the bloom filter with the bits per key
+    pub bloom_filter_bits_per_key: Option<i32>,
+    /// Extract the fixed length prefix of the keys for the bloom filters, the iterators
+    /// still seek in total order
+    pub prefix_len: Option<usize>,
+}
+
+/// How the reads which must not fail retry on the transient rocksdb errors
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// The number of retries after the first attempt
+    pub retries: usize,
+    /// The wait in milliseconds before the first retry, doubled before each retry after it
+    pub backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 3,
+            backoff_ms: 10,
+        }
+    }
 }
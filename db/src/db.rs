@@ -1,11 +1,14 @@
 use crate::snapshot::RocksDBSnapshot;
 use crate::transaction::RocksDBTransaction;
-use crate::{internal_error, Col, DBConfig, Result};
+use crate::{internal_error, Col, ColumnOptions, DBConfig, Result};
 use ckb_logger::{info, warn};
-use rocksdb::ops::{Get, GetColumnFamilys, GetPinnedCF, IterateCF, OpenCF, Put, SetOptions};
+use rocksdb::ops::{
+    FlushCF, Get, GetColumnFamilys, GetPinnedCF, GetPropertyCF, IterateCF, OpenCF, Put, SetOptions,
+};
 use rocksdb::{
-    ffi, ColumnFamily, DBPinnableSlice, IteratorMode, OptimisticTransactionDB,
-    OptimisticTransactionOptions, Options, WriteOptions,
+    ffi, BlockBasedOptions, ColumnFamily, ColumnFamilyDescriptor, DBPinnableSlice, FlushOptions,
+    IteratorMode, OptimisticTransactionDB, OptimisticTransactionOptions, Options, SliceTransform,
+    WriteOptions,
 };
 use std::sync::Arc;
 
@@ -18,6 +21,7 @@ pub(crate) const VERSION_VALUE: &str = "0.2100.0";
 
 pub struct RocksDB {
     pub(crate) inner: Arc<OptimisticTransactionDB>,
+    columns: u32,
 }
 
 impl RocksDB {
@@ -32,48 +36,74 @@ impl RocksDB {
         opts.create_missing_column_families(true);
 
         let cfnames: Vec<_> = (0..columns).map(|c| c.to_string()).collect();
-        let cf_options: Vec<&str> = cfnames.iter().map(|n| n as &str).collect();
+        if let Some(name) = config.columns.keys().find(|name| !cfnames.contains(name)) {
+            Err(internal_error(format!(
+                "failed to set the options of column {}: not found",
+                name
+            )))?;
+        }
+        let cf_descriptors = || {
+            cfnames
+                .iter()
+                .map(|name| {
+                    let cf_opts = config
+                        .columns
+                        .get(name)
+                        .map(column_options)
+                        .unwrap_or_default();
+                    ColumnFamilyDescriptor::new(name.as_str(), cf_opts)
+                })
+                .collect::<Vec<_>>()
+        };
 
         let db =
-            OptimisticTransactionDB::open_cf(&opts, &config.path, &cf_options).or_else(|err| {
-                let err_str = err.as_ref();
-                if err_str.starts_with("Invalid argument:")
-                    && err_str.ends_with("does not exist (create_if_missing is false)")
-                {
-                    info!("Initialize a new database");
-                    opts.create_if_missing(true);
-                    let db = OptimisticTransactionDB::open_cf(&opts, &config.path, &cf_options)
+            OptimisticTransactionDB::open_cf_descriptors(&opts, &config.path, cf_descriptors())
+                .or_else(|err| {
+                    let err_str = err.as_ref();
+                    if err_str.starts_with("Invalid argument:")
+                        && err_str.ends_with("does not exist (create_if_missing is false)")
+                    {
+                        info!("Initialize a new database");
+                        opts.create_if_missing(true);
+                        let db = OptimisticTransactionDB::open_cf_descriptors(
+                            &opts,
+                            &config.path,
+                            cf_descriptors(),
+                        )
                         .map_err(|err| {
                             internal_error(format!(
                                 "failed to open a new created database: {}",
                                 err
                             ))
                         })?;
-                    db.put(ver_key, ver_val).map_err(|err| {
-                        internal_error(format!("failed to initiate the database: {}", err))
-                    })?;
-                    Ok(db)
-                } else if err.as_ref().starts_with("Corruption:") {
-                    warn!("Repairing the rocksdb since {} ...", err);
-                    let mut repair_opts = Options::default();
-                    repair_opts.create_if_missing(false);
-                    repair_opts.create_missing_column_families(false);
-                    OptimisticTransactionDB::repair(repair_opts, &config.path).map_err(|err| {
-                        internal_error(format!("failed to repair the database: {}", err))
-                    })?;
-                    warn!("Opening the repaired rocksdb ...");
-                    OptimisticTransactionDB::open_cf(&opts, &config.path, &cf_options).map_err(
-                        |err| {
+                        db.put(ver_key, ver_val).map_err(|err| {
+                            internal_error(format!("failed to initiate the database: {}", err))
+                        })?;
+                        Ok(db)
+                    } else if err.as_ref().starts_with("Corruption:") {
+                        warn!("Repairing the rocksdb since {} ...", err);
+                        let mut repair_opts = Options::default();
+                        repair_opts.create_if_missing(false);
+                        repair_opts.create_missing_column_families(false);
+                        OptimisticTransactionDB::repair(repair_opts, &config.path).map_err(
+                            |err| internal_error(format!("failed to repair the database: {}", err)),
+                        )?;
+                        warn!("Opening the repaired rocksdb ...");
+                        OptimisticTransactionDB::open_cf_descriptors(
+                            &opts,
+                            &config.path,
+                            cf_descriptors(),
+                        )
+                        .map_err(|err| {
                             internal_error(format!("failed to open the repaired database: {}", err))
-                        },
-                    )
-                } else {
-                    Err(internal_error(format!(
-                        "failed to open the database: {}",
-                        err
-                    )))
-                }
-            })?;
+                        })
+                    } else {
+                        Err(internal_error(format!(
+                            "failed to open the database: {}",
+                            err
+                        )))
+                    }
+                })?;
 
         if let Some(db_opt) = config.options.as_ref() {
             let rocksdb_options: Vec<(&str, &str)> = db_opt
@@ -117,6 +147,7 @@ impl RocksDB {
 
         Ok(RocksDB {
             inner: Arc::new(db),
+            columns,
         })
     }
 
@@ -172,6 +203,35 @@ impl RocksDB {
         }
     }
 
+    /// Flush the memtables of all columns into SST files, so the data written so far no
+    /// longer depends on the WAL to survive a crash
+    ///
+    /// If `wait` is false the flushes are only scheduled and this returns at once.
+    pub fn flush(&self, wait: bool) -> Result<()> {
+        let mut opts = FlushOptions::default();
+        opts.set_wait(wait);
+        for col in 0..self.columns {
+            let col = col.to_string();
+            let cf = self
+                .inner
+                .cf_handle(&col)
+                .ok_or_else(|| internal_error(format!("column {} not found", col)))?;
+            self.inner.flush_cf_opt(cf, &opts).map_err(|err| {
+                internal_error(format!("failed to flush column {}: {}", col, err))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Get a RocksDB property of a column, e.g. "rocksdb.estimate-pending-compaction-bytes",
+    /// return `None` if the property is unknown
+    pub fn property_value(&self, col: Col, name: &str) -> Result<Option<String>> {
+        let cf = cf_handle(&self.inner, col)?;
+        self.inner
+            .property_value_cf(cf, name)
+            .map_err(internal_error)
+    }
+
     pub fn get_snapshot(&self) -> RocksDBSnapshot {
         unsafe {
             let snapshot = ffi::rocksdb_create_snapshot(self.inner.base_db_ptr());
@@ -180,6 +240,24 @@ impl RocksDB {
     }
 }
 
+fn column_options(column: &ColumnOptions) -> Options {
+    let mut opts = Options::default();
+    if column.block_cache_size.is_some() || column.bloom_filter_bits_per_key.is_some() {
+        let mut block_opts = BlockBasedOptions::default();
+        if let Some(size) = column.block_cache_size {
+            block_opts.set_lru_cache(size);
+        }
+        if let Some(bits) = column.bloom_filter_bits_per_key {
+            block_opts.set_bloom_filter(bits, true);
+        }
+        opts.set_block_based_table_factory(&block_opts);
+    }
+    if let Some(len) = column.prefix_len {
+        opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(len));
+    }
+    opts
+}
+
 pub(crate) fn cf_handle(db: &OptimisticTransactionDB, col: Col) -> Result<&ColumnFamily> {
     db.cf_handle(col)
         .ok_or_else(|| internal_error(format!("column {} not found", col)))
@@ -187,8 +265,9 @@ pub(crate) fn cf_handle(db: &OptimisticTransactionDB, col: Col) -> Result<&Colum
 
 #[cfg(test)]
 mod tests {
-    use super::{DBConfig, Result, RocksDB, VERSION_KEY, VERSION_VALUE};
+    use super::{ColumnOptions, DBConfig, Result, RocksDB, VERSION_KEY, VERSION_VALUE};
     use crate::internal_error;
+    use crate::iter::{DBIterator, Direction};
     use ckb_error::assert_error_eq;
     use std::collections::HashMap;
     use tempfile;
@@ -225,6 +304,7 @@ mod tests {
                 opts.insert("disable_auto_compactions".to_owned(), "true".to_owned());
                 opts
             }),
+            ..Default::default()
         };
         RocksDB::open(&config, 2); // no panic
     }
@@ -243,10 +323,60 @@ mod tests {
                 opts.insert("letsrock".to_owned(), "true".to_owned());
                 opts
             }),
+            ..Default::default()
         };
         RocksDB::open(&config, 2); // panic
     }
 
+    #[test]
+    fn test_set_column_options() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("test_set_column_options")
+            .tempdir()
+            .unwrap();
+        let mut config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        config.columns.insert(
+            "1".to_owned(),
+            ColumnOptions {
+                block_cache_size: Some(8 << 20),
+                bloom_filter_bits_per_key: Some(10),
+                prefix_len: Some(2),
+            },
+        );
+        let db = RocksDB::open(&config, 2);
+        let txn = db.transaction();
+        txn.put("1", &[1, 1], &[1, 1, 1]).unwrap();
+        txn.put("1", &[1, 2, 3], &[1]).unwrap();
+        txn.put("1", &[2, 1], &[2]).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            db.get_pinned("1", &[1, 1]).unwrap().unwrap().as_ref(),
+            &[1, 1, 1]
+        );
+        // a full column scan crosses the prefixes
+        let keys: Vec<_> = db
+            .iter("1", &[], Direction::Forward)
+            .unwrap()
+            .map(|(key, _)| key.to_vec())
+            .collect();
+        assert_eq!(keys, vec![vec![1, 1], vec![1, 2, 3], vec![2, 1]]);
+        let keys: Vec<_> = db
+            .iter("1", &[2, 1], Direction::Reverse)
+            .unwrap()
+            .map(|(key, _)| key.to_vec())
+            .collect();
+        assert_eq!(keys, vec![vec![2, 1], vec![1, 2, 3], vec![1, 1]]);
+        drop(db);
+
+        config
+            .columns
+            .insert("2".to_owned(), ColumnOptions::default());
+        assert!(RocksDB::open_with_error(&config, 2).is_err());
+    }
+
     #[test]
     fn write_and_read() {
         let db = setup_db("write_and_read", 2);
@@ -280,6 +410,30 @@ mod tests {
         assert_eq!(r.get(&vec![1, 1]), Some(&vec![1, 1, 1]));
     }
 
+    #[test]
+    fn flush_and_reopen() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("flush_and_reopen")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        let db = RocksDB::open(&config, 2);
+        let txn = db.transaction();
+        txn.put("0", &[0], &[0, 0]).unwrap();
+        txn.put("1", &[1], &[1, 1]).unwrap();
+        txn.commit().unwrap();
+        db.flush(false).unwrap();
+        db.flush(true).unwrap();
+        drop(db);
+
+        let db = RocksDB::open(&config, 2);
+        assert_eq!(db.get_pinned("0", &[0]).unwrap().unwrap().as_ref(), &[0, 0]);
+        assert_eq!(db.get_pinned("1", &[1]).unwrap().unwrap().as_ref(), &[1, 1]);
+    }
+
     #[test]
     fn write_and_partial_read() {
         let db = setup_db("write_and_partial_read", 2);
@@ -330,4 +484,20 @@ mod tests {
         let _ = RocksDB::open_with_check(&config, 1, VERSION_KEY, VERSION_VALUE).unwrap();
         let _ = RocksDB::open_with_check(&config, 1, VERSION_KEY, VERSION_VALUE).unwrap();
     }
+
+    #[test]
+    fn property_value() {
+        let db = RocksDB::open_tmp(2);
+        assert!(db
+            .property_value("1", "rocksdb.num-files-at-level0")
+            .unwrap()
+            .is_some());
+        assert!(db
+            .property_value("1", "rocksdb.no-such-property")
+            .unwrap()
+            .is_none());
+        assert!(db
+            .property_value("2", "rocksdb.num-files-at-level0")
+            .is_err());
+    }
 }
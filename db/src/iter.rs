@@ -12,6 +12,8 @@ pub enum Direction {
     Reverse,
 }
 
+/// The iterators seek in total order, ignoring the prefix extractor of the column, so they
+/// can cross the prefixes, e.g. to scan a whole column
 pub trait DBIterator {
     fn iter<'a>(
         &'a self,
@@ -35,7 +37,7 @@ impl DBIterator for RocksDB {
         };
         let mode = IteratorMode::From(from_key, iter_direction);
         self.inner
-            .iterator_cf(cf, mode)
+            .full_iterator_cf(cf, mode)
             .map(|iter| Box::new(iter) as Box<_>)
             .map_err(internal_error)
     }
@@ -55,7 +57,7 @@ impl DBIterator for RocksDBTransaction {
         };
         let mode = IteratorMode::From(from_key, iter_direction);
         self.inner
-            .iterator_cf(cf, mode)
+            .full_iterator_cf(cf, mode)
             .map(|iter| Box::new(iter) as Box<_>)
             .map_err(internal_error)
     }
@@ -75,7 +77,7 @@ impl<'a> DBIterator for RocksDBTransactionSnapshot<'a> {
         };
         let mode = IteratorMode::From(from_key, iter_direction);
         self.inner
-            .iterator_cf(cf, mode)
+            .full_iterator_cf(cf, mode)
             .map(|iter| Box::new(iter) as Box<_>)
             .map_err(internal_error)
     }
@@ -94,7 +96,7 @@ impl DBIterator for RocksDBSnapshot {
             Direction::Reverse => RdbDirection::Reverse,
         };
         let mode = IteratorMode::From(from_key, iter_direction);
-        self.iterator_cf(cf, mode)
+        self.full_iterator_cf(cf, mode)
             .map(|iter| Box::new(iter) as Box<_>)
             .map_err(internal_error)
     }
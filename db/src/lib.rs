@@ -10,12 +10,14 @@ use std::result;
 pub mod config;
 pub mod db;
 pub mod iter;
+pub mod read_only_db;
 pub mod snapshot;
 pub mod transaction;
 
-pub use crate::config::DBConfig;
+pub use crate::config::{ColumnOptions, DBConfig, RetryPolicy};
 pub use crate::db::RocksDB;
 pub use crate::iter::{DBIterator, Direction};
+pub use crate::read_only_db::ReadOnlyDB;
 pub use crate::snapshot::RocksDBSnapshot;
 pub use crate::transaction::{RocksDBTransaction, RocksDBTransactionSnapshot};
 pub use rocksdb::{DBPinnableSlice, DBVector, Error as DBError};
@@ -26,3 +28,11 @@ pub type Result<T> = result::Result<T, Error>;
 fn internal_error<S: Display + Debug + Sync + Send + 'static>(reason: S) -> Error {
     InternalErrorKind::Database.reason(reason).into()
 }
+
+/// Build a `Database` error which records the column and the operation that failed
+fn operation_error<S: Display>(operation: &str, col: Col, reason: S) -> Error {
+    internal_error(format!(
+        "failed to {} on column {}: {}",
+        operation, col, reason
+    ))
+}
@@ -0,0 +1,99 @@
+use crate::iter::{DBIterator, DBIteratorItem, Direction};
+use crate::{internal_error, Col, DBConfig, Result};
+use rocksdb::ops::{GetColumnFamilys, GetPinnedCF, IterateCF};
+use rocksdb::{
+    ColumnFamily, DBPinnableSlice, Direction as RdbDirection, IteratorMode, Options, DB,
+};
+use std::sync::Arc;
+
+/// A database opened by rocksdb in read-only mode, e.g. a backup opened alongside a live
+/// node
+///
+/// Nothing is created or written when it's opened, the column families must exist and the
+/// database lock is not taken. It offers no write method at all.
+pub struct ReadOnlyDB {
+    inner: Arc<DB>,
+}
+
+impl ReadOnlyDB {
+    pub fn open(config: &DBConfig, columns: u32) -> Result<Self> {
+        let mut opts = Options::default();
+        opts.create_if_missing(false);
+        opts.create_missing_column_families(false);
+        let cfnames: Vec<_> = (0..columns).map(|c| c.to_string()).collect();
+        let db =
+            DB::open_cf_for_read_only(&opts, &config.path, &cfnames, false).map_err(|err| {
+                internal_error(format!("failed to open the database read-only: {}", err))
+            })?;
+        Ok(ReadOnlyDB {
+            inner: Arc::new(db),
+        })
+    }
+
+    pub fn get_pinned(&self, col: Col, key: &[u8]) -> Result<Option<DBPinnableSlice>> {
+        let cf = self.cf_handle(col)?;
+        self.inner.get_pinned_cf(cf, &key).map_err(internal_error)
+    }
+
+    fn cf_handle(&self, col: Col) -> Result<&ColumnFamily> {
+        self.inner
+            .cf_handle(col)
+            .ok_or_else(|| internal_error(format!("column {} not found", col)))
+    }
+}
+
+impl DBIterator for ReadOnlyDB {
+    fn iter<'a>(
+        &'a self,
+        col: Col,
+        from_key: &'a [u8],
+        direction: Direction,
+    ) -> Result<Box<Iterator<Item = DBIteratorItem> + 'a>> {
+        let cf = self.cf_handle(col)?;
+        let iter_direction = match direction {
+            Direction::Forward => RdbDirection::Forward,
+            Direction::Reverse => RdbDirection::Reverse,
+        };
+        let mode = IteratorMode::From(from_key, iter_direction);
+        self.inner
+            .full_iterator_cf(cf, mode)
+            .map(|iter| Box::new(iter) as Box<_>)
+            .map_err(internal_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RocksDB;
+
+    #[test]
+    fn open_read_only() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("open_read_only")
+            .tempdir()
+            .unwrap();
+        let config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        // nothing is created for a missing database
+        assert!(ReadOnlyDB::open(&config, 2).is_err());
+
+        {
+            let db = RocksDB::open(&config, 2);
+            let txn = db.transaction();
+            txn.put("1", &[0, 1], &[1, 2, 3]).unwrap();
+            txn.commit().unwrap();
+        }
+        let db = ReadOnlyDB::open(&config, 2).unwrap();
+        assert_eq!(
+            db.get_pinned("1", &[0, 1]).unwrap().unwrap().as_ref(),
+            &[1, 2, 3]
+        );
+        assert!(db.get_pinned("0", &[0, 1]).unwrap().is_none());
+        assert_eq!(db.iter("1", &[], Direction::Forward).unwrap().count(), 1);
+        // the columns are not created either
+        assert!(ReadOnlyDB::open(&config, 3).is_err());
+    }
+}
@@ -1,11 +1,15 @@
 use crate::db::cf_handle;
-use crate::{internal_error, Col, Result};
+use crate::snapshot::RocksDBSnapshot;
+use crate::{internal_error, operation_error, Col, Result, RetryPolicy};
 use rocksdb::ops::{DeleteCF, GetCF, PutCF};
-pub use rocksdb::{DBPinnableSlice, DBVector};
 use rocksdb::{
-    OptimisticTransaction, OptimisticTransactionDB, OptimisticTransactionSnapshot, ReadOptions,
+    ffi, Error as DBError, OptimisticTransaction, OptimisticTransactionDB,
+    OptimisticTransactionSnapshot, ReadOptions,
 };
+pub use rocksdb::{DBPinnableSlice, DBVector};
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 pub struct RocksDBTransaction {
     pub(crate) db: Arc<OptimisticTransactionDB>,
@@ -15,17 +19,23 @@ pub struct RocksDBTransaction {
 impl RocksDBTransaction {
     pub fn get(&self, col: Col, key: &[u8]) -> Result<Option<DBVector>> {
         let cf = cf_handle(&self.db, col)?;
-        self.inner.get_cf(cf, key).map_err(internal_error)
+        self.inner
+            .get_cf(cf, key)
+            .map_err(|err| operation_error("get", col, err))
     }
 
     pub fn put(&self, col: Col, key: &[u8], value: &[u8]) -> Result<()> {
         let cf = cf_handle(&self.db, col)?;
-        self.inner.put_cf(cf, key, value).map_err(internal_error)
+        self.inner
+            .put_cf(cf, key, value)
+            .map_err(|err| operation_error("put", col, err))
     }
 
     pub fn delete(&self, col: Col, key: &[u8]) -> Result<()> {
         let cf = cf_handle(&self.db, col)?;
-        self.inner.delete_cf(cf, key).map_err(internal_error)
+        self.inner
+            .delete_cf(cf, key)
+            .map_err(|err| operation_error("delete", col, err))
     }
 
     pub fn get_for_update<'a>(
@@ -43,7 +53,9 @@ impl RocksDBTransaction {
     }
 
     pub fn commit(&self) -> Result<()> {
-        self.inner.commit().map_err(internal_error)
+        self.inner
+            .commit()
+            .map_err(|err| internal_error(format!("failed to commit: {}", err)))
     }
 
     pub fn rollback(&self) -> Result<()> {
@@ -57,6 +69,15 @@ impl RocksDBTransaction {
         }
     }
 
+    /// Take a snapshot of the committed data in database, which is not tied to the
+    /// transaction, the uncommitted writes of the transaction are invisible in it
+    pub fn get_db_snapshot(&self) -> RocksDBSnapshot {
+        unsafe {
+            let snapshot = ffi::rocksdb_create_snapshot(self.db.base_db_ptr());
+            RocksDBSnapshot::new(&self.db, snapshot)
+        }
+    }
+
     pub fn set_savepoint(&self) {
         self.inner.set_savepoint()
     }
@@ -76,4 +97,63 @@ impl<'a> RocksDBTransactionSnapshot<'a> {
         let cf = cf_handle(&self.db, col)?;
         self.inner.get_cf(cf, key).map_err(internal_error)
     }
+
+    /// Like `get`, but retry by the policy while rocksdb reports a transient error, the
+    /// last error is returned once the retries are used up
+    pub fn get_with_retry(
+        &self,
+        col: Col,
+        key: &[u8],
+        policy: &RetryPolicy,
+    ) -> Result<Option<DBVector>> {
+        let cf = cf_handle(&self.db, col)?;
+        let mut backoff = Duration::from_millis(policy.backoff_ms);
+        let mut retries = 0;
+        loop {
+            match self.inner.get_cf(cf, key) {
+                Err(ref err) if retries < policy.retries && is_transient(err) => {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    retries += 1;
+                }
+                result => {
+                    return result.map_err(|err| {
+                        operation_error("get", col, format!("{} after {} retries", err, retries))
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Whether the error may go away by itself, these are the status messages of the
+/// rocksdb `Busy`, `TimedOut`, `TryAgain` and `Incomplete` codes
+pub(crate) fn is_transient(err: &DBError) -> bool {
+    is_transient_message(err.as_ref())
+}
+
+fn is_transient_message(message: &str) -> bool {
+    [
+        "Resource busy",
+        "Operation timed out",
+        "Operation failed. Try again.",
+        "Result incomplete",
+    ]
+    .iter()
+    .any(|status| message.starts_with(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_transient_message;
+
+    #[test]
+    fn transient_messages() {
+        assert!(is_transient_message("Resource busy: "));
+        assert!(is_transient_message("Operation timed out: "));
+        assert!(is_transient_message("Operation failed. Try again.: "));
+        assert!(is_transient_message("Result incomplete: Write stall"));
+        assert!(!is_transient_message("Corruption: bad block contents"));
+        assert!(!is_transient_message("IO error: No such file or directory"));
+    }
 }
@@ -25,6 +25,12 @@ pub enum InternalErrorKind {
     /// Database exception
     Database,
 
+    /// A packed value fails to be encoded before it's written into the store
+    Serialization,
+
+    /// A block exceeds the maximum size the store accepts, e.g. the size of its body
+    ExceededMaximumBlockBytes,
+
     /// VM internal error
     VM,
 
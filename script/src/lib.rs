@@ -5,6 +5,7 @@ mod type_id;
 mod verify;
 
 pub use crate::error::ScriptError;
+pub use crate::syscalls::{CostTable, CycleMeter};
 pub use crate::verify::{ScriptGroup, ScriptGroupType, TransactionScriptsVerifier};
 
 /// re-export DataLoader
@@ -0,0 +1,12 @@
+//! The results a syscall writes to register A0, they are part of the script ABI.
+
+/// `0`, the syscall succeeded
+pub const SUCCESS: u8 = 0;
+/// `1`, the requested index doesn't exist, e.g. requesting the 4th output in a
+/// transaction with only 3 outputs
+pub const INDEX_OUT_OF_BOUND: u8 = 1;
+/// `2`, the requested item is absent, e.g. the type field on an output without type
+/// script, or the cell data for a dep OutPoint which only references a block header
+pub const ITEM_MISSING: u8 = 2;
+/// `3`, the requested slice exceeds the data or the memory given to hold it
+pub const SLICE_OUT_OF_BOUND: u8 = 3;
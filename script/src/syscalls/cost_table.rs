@@ -0,0 +1,51 @@
+use ckb_types::core::Cycle;
+
+/// The cycles charged per byte by each syscall family
+///
+/// The default values are part of the consensus, changing them forks the chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CostTable {
+    /// LoadScript, LoadScriptHash and LoadScriptGroupInputIndices
+    pub load_script: Cycle,
+    /// LoadTxHash and LoadTxCounts
+    pub load_tx: Cycle,
+    /// LoadCell, the whole cell output is loaded
+    pub load_cell: Cycle,
+    /// LoadCellByField
+    pub load_cell_by_field: Cycle,
+    /// LoadCellDepOutPoint
+    pub load_cell_dep: Cycle,
+    /// LoadCellData, LoadCellDataAsCode and LoadCellDataLength
+    pub load_cell_data: Cycle,
+    /// LoadInput, the whole cell input is loaded
+    pub load_input: Cycle,
+    /// LoadInputByField
+    pub load_input_by_field: Cycle,
+    /// LoadHeader and LoadHeaderByField
+    pub load_header: Cycle,
+    /// LoadWitness
+    pub load_witness: Cycle,
+    /// DebugPrint
+    pub debug_print: Cycle,
+    /// LoadRemainingCycles, a fixed cost per query
+    pub load_remaining_cycles: Cycle,
+}
+
+impl Default for CostTable {
+    fn default() -> Self {
+        CostTable {
+            load_script: 10,
+            load_tx: 10,
+            load_cell: 100,
+            load_cell_by_field: 10,
+            load_cell_dep: 10,
+            load_cell_data: 10,
+            load_input: 100,
+            load_input_by_field: 10,
+            load_header: 10,
+            load_witness: 10,
+            debug_print: 10,
+            load_remaining_cycles: 10,
+        }
+    }
+}
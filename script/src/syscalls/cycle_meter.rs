@@ -0,0 +1,55 @@
+use ckb_types::core::Cycle;
+use ckb_vm::{registers::A7, Error as VMError, Register, SupportMachine, Syscalls};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Collects the cycles consumed by each syscall number, this is only used for profiling,
+/// the cycles are still charged to the machine as usual.
+#[derive(Clone, Default, Debug)]
+pub struct CycleMeter {
+    cycles: Arc<Mutex<HashMap<u64, Cycle>>>,
+}
+
+impl CycleMeter {
+    pub fn new() -> CycleMeter {
+        CycleMeter::default()
+    }
+
+    fn record(&self, number: u64, cycles: Cycle) {
+        let mut histogram = self.cycles.lock().expect("cycle meter lock");
+        *histogram.entry(number).or_insert(0) += cycles;
+    }
+
+    /// Returns the consumed cycles keyed by syscall number
+    pub fn histogram(&self) -> HashMap<u64, Cycle> {
+        self.cycles.lock().expect("cycle meter lock").clone()
+    }
+}
+
+/// Wraps a syscall and reports the cycles it adds to the machine into a `CycleMeter`
+pub struct Metered<S> {
+    inner: S,
+    meter: CycleMeter,
+}
+
+impl<S> Metered<S> {
+    pub fn new(inner: S, meter: CycleMeter) -> Metered<S> {
+        Metered { inner, meter }
+    }
+}
+
+impl<Mac: SupportMachine, S: Syscalls<Mac>> Syscalls<Mac> for Metered<S> {
+    fn initialize(&mut self, machine: &mut Mac) -> Result<(), VMError> {
+        self.inner.initialize(machine)
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, VMError> {
+        let number = machine.registers()[A7].to_u64();
+        let cycles = machine.cycles();
+        let processed = self.inner.ecall(machine)?;
+        if processed {
+            self.meter.record(number, machine.cycles() - cycles);
+        }
+        Ok(processed)
+    }
+}
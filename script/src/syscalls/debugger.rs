@@ -1,16 +1,34 @@
-use crate::syscalls::DEBUG_PRINT_SYSCALL_NUMBER;
+use crate::syscalls::{CostTable, DEBUG_PRINT_SYSCALL_NUMBER, DEBUG_WRITE_SYSCALL_NUMBER};
 use ckb_vm::{
-    registers::{A0, A7},
+    registers::{A0, A3, A4, A7},
     Error as VMError, Memory, Register, SupportMachine, Syscalls,
 };
 
+/// Forwards the debug strings of scripts to the printer
+///
+/// DebugPrint reads a null terminated string from the address in A0, DebugWrite reads the
+/// number of bytes in A4 from the address in A3. The bytes are charged before they are read
+/// and the string is read whether there is a printer or not, so the consensus mode which
+/// discards the output charges the same cycles and fails the same way as a debug run.
 pub struct Debugger<'a> {
-    printer: &'a Fn(&str),
+    printer: Option<&'a Fn(&str)>,
+    cost_table: CostTable,
 }
 
 impl<'a> Debugger<'a> {
-    pub fn new(printer: &'a Fn(&str)) -> Debugger<'a> {
-        Debugger { printer }
+    pub fn new(printer: &'a Fn(&str), cost_table: CostTable) -> Debugger<'a> {
+        Debugger {
+            printer: Some(printer),
+            cost_table,
+        }
+    }
+
+    /// The debugger of the consensus mode, it charges the cycles and discards the output
+    pub fn discarding(cost_table: CostTable) -> Debugger<'a> {
+        Debugger {
+            printer: None,
+            cost_table,
+        }
     }
 }
 
@@ -20,30 +38,58 @@ impl<'a, Mac: SupportMachine> Syscalls<Mac> for Debugger<'a> {
     }
 
     fn ecall(&mut self, machine: &mut Mac) -> Result<bool, VMError> {
-        let number = machine.registers()[A7].to_u64();
-        if number != DEBUG_PRINT_SYSCALL_NUMBER {
-            return Ok(false);
-        }
+        let buffer = match machine.registers()[A7].to_u64() {
+            DEBUG_PRINT_SYSCALL_NUMBER => load_c_string(machine, self.cost_table.debug_print)?,
+            DEBUG_WRITE_SYSCALL_NUMBER => load_bytes(machine, self.cost_table.debug_print)?,
+            _ => return Ok(false),
+        };
 
-        let mut addr = machine.registers()[A0].to_u64();
-        let mut buffer = Vec::new();
-
-        loop {
-            let byte = machine
-                .memory_mut()
-                .load8(&Mac::REG::from_u64(addr))?
-                .to_u8();
-            if byte == 0 {
-                break;
-            }
-            buffer.push(byte);
-            addr += 1;
+        if let Some(printer) = self.printer {
+            printer(&String::from_utf8_lossy(&buffer));
         }
 
-        machine.add_cycles(buffer.len() as u64 * 10)?;
-        let s = String::from_utf8(buffer).map_err(|_| VMError::ParseError)?;
-        (self.printer)(s.as_str());
-
         Ok(true)
     }
 }
+
+// Each byte is charged right after it is read, the terminator is free
+fn load_c_string<Mac: SupportMachine>(
+    machine: &mut Mac,
+    byte_cycles: u64,
+) -> Result<Vec<u8>, VMError> {
+    let mut addr = machine.registers()[A0].to_u64();
+    let mut buffer = Vec::new();
+
+    loop {
+        let byte = machine
+            .memory_mut()
+            .load8(&Mac::REG::from_u64(addr))?
+            .to_u8();
+        if byte == 0 {
+            break;
+        }
+        machine.add_cycles(byte_cycles)?;
+        buffer.push(byte);
+        addr += 1;
+    }
+    Ok(buffer)
+}
+
+// All the bytes are charged before any is read, the charge fails beyond the remaining cycles
+fn load_bytes<Mac: SupportMachine>(
+    machine: &mut Mac,
+    byte_cycles: u64,
+) -> Result<Vec<u8>, VMError> {
+    let addr = machine.registers()[A3].to_u64();
+    let len = machine.registers()[A4].to_u64();
+    machine.add_cycles(len.saturating_mul(byte_cycles))?;
+    let mut buffer = Vec::new();
+    for offset in 0..len {
+        let byte = machine
+            .memory_mut()
+            .load8(&Mac::REG::from_u64(addr.wrapping_add(offset)))?
+            .to_u8();
+        buffer.push(byte);
+    }
+    Ok(buffer)
+}
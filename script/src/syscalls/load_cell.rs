@@ -1,10 +1,10 @@
 use crate::syscalls::{
-    utils::store_data, CellField, Source, SourceEntry, INDEX_OUT_OF_BOUND, ITEM_MISSING,
+    utils::store_data, CellField, CostTable, Source, SourceEntry, INDEX_OUT_OF_BOUND, ITEM_MISSING,
     LOAD_CELL_BY_FIELD_SYSCALL_NUMBER, LOAD_CELL_SYSCALL_NUMBER, SUCCESS,
 };
 use byteorder::{LittleEndian, WriteBytesExt};
 use ckb_types::{
-    core::{cell::CellMeta, Capacity},
+    core::{cell::CellMeta, Capacity, Cycle},
     packed::CellOutput,
     prelude::*,
 };
@@ -19,6 +19,7 @@ pub struct LoadCell<'a> {
     resolved_cell_deps: &'a [CellMeta],
     group_inputs: &'a [usize],
     group_outputs: &'a [usize],
+    cost_table: CostTable,
 }
 
 impl<'a> LoadCell<'a> {
@@ -28,6 +29,7 @@ impl<'a> LoadCell<'a> {
         resolved_cell_deps: &'a [CellMeta],
         group_inputs: &'a [usize],
         group_outputs: &'a [usize],
+        cost_table: CostTable,
     ) -> LoadCell<'a> {
         LoadCell {
             outputs,
@@ -35,6 +37,7 @@ impl<'a> LoadCell<'a> {
             resolved_cell_deps,
             group_inputs,
             group_outputs,
+            cost_table,
         }
     }
 
@@ -73,6 +76,7 @@ impl<'a> LoadCell<'a> {
         &self,
         machine: &mut Mac,
         output: &CellOutput,
+        cycle_factor: Cycle,
     ) -> Result<(u8, usize), VMError> {
         // NOTE: this is a very expensive operation here since we need to copy
         // everything in a cell to a flatbuffer object, serialize the object
@@ -85,7 +89,7 @@ impl<'a> LoadCell<'a> {
         // TODO: find a way to cache this without consuming too much memory
         let data = output.as_slice();
 
-        store_data(machine, data)?;
+        store_data(machine, data, cycle_factor)?;
         Ok((SUCCESS, data.len()))
     }
 
@@ -93,6 +97,7 @@ impl<'a> LoadCell<'a> {
         &self,
         machine: &mut Mac,
         cell: &CellMeta,
+        cycle_factor: Cycle,
     ) -> Result<(u8, usize), VMError> {
         let field = CellField::parse_from_u64(machine.registers()[A5].to_u64())?;
         let output = &cell.cell_output;
@@ -102,13 +107,13 @@ impl<'a> LoadCell<'a> {
                 let capacity: Capacity = output.capacity().unpack();
                 let mut buffer = vec![];
                 buffer.write_u64::<LittleEndian>(capacity.as_u64())?;
-                store_data(machine, &buffer)?;
+                store_data(machine, &buffer, cycle_factor)?;
                 (SUCCESS, buffer.len())
             }
             CellField::DataHash => {
                 if let Some((_, data_hash)) = &cell.mem_cell_data {
                     let bytes = data_hash.raw_data();
-                    store_data(machine, &bytes)?;
+                    store_data(machine, &bytes, cycle_factor)?;
                     (SUCCESS, bytes.len())
                 } else {
                     (ITEM_MISSING, 0)
@@ -121,25 +126,25 @@ impl<'a> LoadCell<'a> {
                         .map_err(|_| VMError::Unexpected)?
                         .as_u64(),
                 )?;
-                store_data(machine, &buffer)?;
+                store_data(machine, &buffer, cycle_factor)?;
                 (SUCCESS, buffer.len())
             }
             CellField::Lock => {
                 let lock = output.lock();
                 let data = lock.as_slice();
-                store_data(machine, data)?;
+                store_data(machine, data, cycle_factor)?;
                 (SUCCESS, data.len())
             }
             CellField::LockHash => {
                 let hash = output.calc_lock_hash();
                 let bytes = hash.as_bytes();
-                store_data(machine, &bytes)?;
+                store_data(machine, &bytes, cycle_factor)?;
                 (SUCCESS, bytes.len())
             }
             CellField::Type => match output.type_().to_opt() {
                 Some(type_) => {
                     let data = type_.as_slice();
-                    store_data(machine, data)?;
+                    store_data(machine, data, cycle_factor)?;
                     (SUCCESS, data.len())
                 }
                 None => (ITEM_MISSING, 0),
@@ -148,7 +153,7 @@ impl<'a> LoadCell<'a> {
                 Some(type_) => {
                     let hash = type_.calc_script_hash();
                     let bytes = hash.as_bytes();
-                    store_data(machine, &bytes)?;
+                    store_data(machine, &bytes, cycle_factor)?;
                     (SUCCESS, bytes.len())
                 }
                 None => (ITEM_MISSING, 0),
@@ -165,8 +170,8 @@ impl<'a, Mac: SupportMachine> Syscalls<Mac> for LoadCell<'a> {
 
     fn ecall(&mut self, machine: &mut Mac) -> Result<bool, VMError> {
         let (load_by_field, cycle_factor) = match machine.registers()[A7].to_u64() {
-            LOAD_CELL_SYSCALL_NUMBER => (false, 100),
-            LOAD_CELL_BY_FIELD_SYSCALL_NUMBER => (true, 10),
+            LOAD_CELL_SYSCALL_NUMBER => (false, self.cost_table.load_cell),
+            LOAD_CELL_BY_FIELD_SYSCALL_NUMBER => (true, self.cost_table.load_cell_by_field),
             _ => return Ok(false),
         };
 
@@ -180,9 +185,9 @@ impl<'a, Mac: SupportMachine> Syscalls<Mac> for LoadCell<'a> {
         }
         let cell = cell.unwrap();
         let (return_code, len) = if load_by_field {
-            self.load_by_field(machine, cell)?
+            self.load_by_field(machine, cell, cycle_factor)?
         } else {
-            self.load_full(machine, &cell.cell_output)?
+            self.load_full(machine, &cell.cell_output, cycle_factor)?
         };
 
         machine.add_cycles(len as u64 * cycle_factor)?;
@@ -1,7 +1,8 @@
 use crate::{
     syscalls::{
-        utils::store_data, Source, SourceEntry, INDEX_OUT_OF_BOUND,
-        LOAD_CELL_DATA_AS_CODE_SYSCALL_NUMBER, LOAD_CELL_DATA_SYSCALL_NUMBER, SLICE_OUT_OF_BOUND,
+        utils::{store_data, store_u64},
+        CostTable, Source, SourceEntry, INDEX_OUT_OF_BOUND, LOAD_CELL_DATA_AS_CODE_SYSCALL_NUMBER,
+        LOAD_CELL_DATA_LENGTH_SYSCALL_NUMBER, LOAD_CELL_DATA_SYSCALL_NUMBER, SLICE_OUT_OF_BOUND,
         SUCCESS,
     },
     DataLoader,
@@ -20,6 +21,7 @@ pub struct LoadCellData<'a, DL> {
     resolved_cell_deps: &'a [CellMeta],
     group_inputs: &'a [usize],
     group_outputs: &'a [usize],
+    cost_table: CostTable,
 }
 
 impl<'a, DL: DataLoader + 'a> LoadCellData<'a, DL> {
@@ -30,6 +32,7 @@ impl<'a, DL: DataLoader + 'a> LoadCellData<'a, DL> {
         resolved_cell_deps: &'a [CellMeta],
         group_inputs: &'a [usize],
         group_outputs: &'a [usize],
+        cost_table: CostTable,
     ) -> LoadCellData<'a, DL> {
         LoadCellData {
             data_loader,
@@ -38,6 +41,7 @@ impl<'a, DL: DataLoader + 'a> LoadCellData<'a, DL> {
             resolved_cell_deps,
             group_inputs,
             group_outputs,
+            cost_table,
         }
     }
 
@@ -111,7 +115,7 @@ impl<'a, DL: DataLoader + 'a> LoadCellData<'a, DL> {
             0,
         )?;
 
-        machine.add_cycles(cell.data_bytes * 10)?;
+        machine.add_cycles(cell.data_bytes * self.cost_table.load_cell_data)?;
         machine.set_register(A0, Mac::REG::from_u8(SUCCESS));
         Ok(())
     }
@@ -132,8 +136,26 @@ impl<'a, DL: DataLoader + 'a> LoadCellData<'a, DL> {
             .ok_or(VMError::Unexpected)?
             .0;
 
-        let wrote_size = store_data(machine, &data)?;
-        machine.add_cycles(wrote_size * 10)?;
+        let wrote_size = store_data(machine, &data, self.cost_table.load_cell_data)?;
+        machine.add_cycles(wrote_size * self.cost_table.load_cell_data)?;
+        machine.set_register(A0, Mac::REG::from_u8(SUCCESS));
+        Ok(())
+    }
+
+    // The length comes from the cell meta, the data itself is not loaded
+    fn load_data_length<Mac: SupportMachine>(&self, machine: &mut Mac) -> Result<(), VMError> {
+        let index = machine.registers()[A3].to_u64();
+        let source = Source::parse_from_u64(machine.registers()[A4].to_u64())?;
+
+        let cell = self.fetch_cell(source, index as usize);
+        if cell.is_err() {
+            machine.set_register(A0, Mac::REG::from_u8(cell.unwrap_err()));
+            return Ok(());
+        }
+        let cell = cell.unwrap();
+
+        let wrote_size = store_u64(machine, cell.data_bytes, self.cost_table.load_cell_data)?;
+        machine.add_cycles(wrote_size * self.cost_table.load_cell_data)?;
         machine.set_register(A0, Mac::REG::from_u8(SUCCESS));
         Ok(())
     }
@@ -152,6 +174,9 @@ impl<'a, Mac: SupportMachine, DL: DataLoader> Syscalls<Mac> for LoadCellData<'a,
         } else if code == LOAD_CELL_DATA_SYSCALL_NUMBER {
             self.load_data(machine)?;
             return Ok(true);
+        } else if code == LOAD_CELL_DATA_LENGTH_SYSCALL_NUMBER {
+            self.load_data_length(machine)?;
+            return Ok(true);
         }
         Ok(false)
     }
@@ -0,0 +1,56 @@
+use crate::syscalls::{
+    utils::store_data, CostTable, INDEX_OUT_OF_BOUND, LOAD_CELL_DEP_OUT_POINT_SYSCALL_NUMBER,
+    SUCCESS,
+};
+use ckb_types::{packed::CellDepVec, prelude::*};
+use ckb_vm::{
+    registers::{A0, A3, A7},
+    Error as VMError, Register, SupportMachine, Syscalls,
+};
+
+/// Loads the serialized out point of the cell dep at the index in A3, for a dep group
+/// it's the out point of the group cell itself rather than the expanded cells.
+#[derive(Debug)]
+pub struct LoadCellDepOutPoint {
+    cell_deps: CellDepVec,
+    cost_table: CostTable,
+}
+
+impl LoadCellDepOutPoint {
+    pub fn new(cell_deps: CellDepVec, cost_table: CostTable) -> LoadCellDepOutPoint {
+        LoadCellDepOutPoint {
+            cell_deps,
+            cost_table,
+        }
+    }
+}
+
+impl<Mac: SupportMachine> Syscalls<Mac> for LoadCellDepOutPoint {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), VMError> {
+        Ok(())
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, VMError> {
+        if machine.registers()[A7].to_u64() != LOAD_CELL_DEP_OUT_POINT_SYSCALL_NUMBER {
+            return Ok(false);
+        }
+
+        let index = machine.registers()[A3].to_u64();
+        let cell_dep = match self.cell_deps.get(index as usize) {
+            Some(cell_dep) => cell_dep,
+            None => {
+                machine.set_register(A0, Mac::REG::from_u8(INDEX_OUT_OF_BOUND));
+                return Ok(true);
+            }
+        };
+
+        let wrote_size = store_data(
+            machine,
+            cell_dep.out_point().as_slice(),
+            self.cost_table.load_cell_dep,
+        )?;
+        machine.set_register(A0, Mac::REG::from_u8(SUCCESS));
+        machine.add_cycles(wrote_size * self.cost_table.load_cell_dep)?;
+        Ok(true)
+    }
+}
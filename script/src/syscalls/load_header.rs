@@ -1,6 +1,6 @@
 use crate::syscalls::{
     utils::{store_data, store_u64},
-    HeaderField, Source, SourceEntry, INDEX_OUT_OF_BOUND, ITEM_MISSING,
+    CostTable, HeaderField, Source, SourceEntry, INDEX_OUT_OF_BOUND, ITEM_MISSING,
     LOAD_HEADER_BY_FIELD_SYSCALL_NUMBER, LOAD_HEADER_SYSCALL_NUMBER, SUCCESS,
 };
 use crate::DataLoader;
@@ -22,6 +22,7 @@ pub struct LoadHeader<'a, DL> {
     resolved_inputs: &'a [CellMeta],
     resolved_cell_deps: &'a [CellMeta],
     group_inputs: &'a [usize],
+    cost_table: CostTable,
 }
 
 impl<'a, DL: DataLoader + 'a> LoadHeader<'a, DL> {
@@ -31,6 +32,7 @@ impl<'a, DL: DataLoader + 'a> LoadHeader<'a, DL> {
         resolved_inputs: &'a [CellMeta],
         resolved_cell_deps: &'a [CellMeta],
         group_inputs: &'a [usize],
+        cost_table: CostTable,
     ) -> LoadHeader<'a, DL> {
         LoadHeader {
             data_loader,
@@ -38,6 +40,7 @@ impl<'a, DL: DataLoader + 'a> LoadHeader<'a, DL> {
             resolved_inputs,
             resolved_cell_deps,
             group_inputs,
+            cost_table,
         }
     }
 
@@ -101,7 +104,7 @@ impl<'a, DL: DataLoader + 'a> LoadHeader<'a, DL> {
         header: &HeaderView,
     ) -> Result<(u8, u64), VMError> {
         let data = header.data().as_bytes();
-        store_data(machine, &data)?;
+        store_data(machine, &data, self.cost_table.load_header)?;
         Ok((SUCCESS, data.len() as u64))
     }
 
@@ -117,11 +120,18 @@ impl<'a, DL: DataLoader + 'a> LoadHeader<'a, DL> {
         };
 
         let result = match field {
-            HeaderField::EpochNumber => (SUCCESS, store_u64(machine, epoch.number())?),
-            HeaderField::EpochStartBlockNumber => {
-                (SUCCESS, store_u64(machine, epoch.start_number())?)
-            }
-            HeaderField::EpochLength => (SUCCESS, store_u64(machine, epoch.length())?),
+            HeaderField::EpochNumber => (
+                SUCCESS,
+                store_u64(machine, epoch.number(), self.cost_table.load_header)?,
+            ),
+            HeaderField::EpochStartBlockNumber => (
+                SUCCESS,
+                store_u64(machine, epoch.start_number(), self.cost_table.load_header)?,
+            ),
+            HeaderField::EpochLength => (
+                SUCCESS,
+                store_u64(machine, epoch.length(), self.cost_table.load_header)?,
+            ),
         };
 
         Ok(result)
@@ -155,7 +165,7 @@ impl<'a, DL: DataLoader + 'a, Mac: SupportMachine> Syscalls<Mac> for LoadHeader<
             self.load_full(machine, &header)?
         };
 
-        machine.add_cycles(len * 10)?;
+        machine.add_cycles(len * self.cost_table.load_header)?;
         machine.set_register(A0, Mac::REG::from_u8(return_code));
         Ok(true)
     }
@@ -1,9 +1,10 @@
 use crate::syscalls::{
-    utils::store_data, InputField, Source, SourceEntry, INDEX_OUT_OF_BOUND,
+    utils::store_data, CostTable, InputField, Source, SourceEntry, INDEX_OUT_OF_BOUND,
     LOAD_INPUT_BY_FIELD_SYSCALL_NUMBER, LOAD_INPUT_SYSCALL_NUMBER, SUCCESS,
 };
 use byteorder::{LittleEndian, WriteBytesExt};
 use ckb_types::{
+    core::Cycle,
     packed::{CellInput, CellInputVec},
     prelude::*,
 };
@@ -16,13 +17,19 @@ use ckb_vm::{
 pub struct LoadInput<'a> {
     inputs: CellInputVec,
     group_inputs: &'a [usize],
+    cost_table: CostTable,
 }
 
 impl<'a> LoadInput<'a> {
-    pub fn new(inputs: CellInputVec, group_inputs: &'a [usize]) -> LoadInput<'a> {
+    pub fn new(
+        inputs: CellInputVec,
+        group_inputs: &'a [usize],
+        cost_table: CostTable,
+    ) -> LoadInput<'a> {
         LoadInput {
             inputs,
             group_inputs,
+            cost_table,
         }
     }
 
@@ -49,6 +56,7 @@ impl<'a> LoadInput<'a> {
         &self,
         machine: &mut Mac,
         input: &CellInput,
+        cycle_factor: Cycle,
     ) -> Result<usize, VMError> {
         // NOTE: like LOAD_CELL, this could also be expensive assuming the
         // input has too many args. So right now we also charge for the full
@@ -57,7 +65,7 @@ impl<'a> LoadInput<'a> {
         // costs here.
 
         let data = input.as_slice();
-        store_data(machine, data)?;
+        store_data(machine, data, cycle_factor)?;
         Ok(data.len())
     }
 
@@ -65,6 +73,7 @@ impl<'a> LoadInput<'a> {
         &self,
         machine: &mut Mac,
         input: &CellInput,
+        cycle_factor: Cycle,
     ) -> Result<usize, VMError> {
         let field = InputField::parse_from_u64(machine.registers()[A5].to_u64())?;
 
@@ -72,13 +81,13 @@ impl<'a> LoadInput<'a> {
             InputField::OutPoint => {
                 let previous_output = input.previous_output();
                 let data = previous_output.as_slice();
-                store_data(machine, data)?;
+                store_data(machine, data, cycle_factor)?;
                 data.len()
             }
             InputField::Since => {
                 let mut buffer = vec![];
                 buffer.write_u64::<LittleEndian>(input.since().unpack())?;
-                store_data(machine, &buffer)?;
+                store_data(machine, &buffer, cycle_factor)?;
                 buffer.len()
             }
         };
@@ -93,8 +102,8 @@ impl<'a, Mac: SupportMachine> Syscalls<Mac> for LoadInput<'a> {
 
     fn ecall(&mut self, machine: &mut Mac) -> Result<bool, VMError> {
         let (load_by_field, cycle_factor) = match machine.registers()[A7].to_u64() {
-            LOAD_INPUT_SYSCALL_NUMBER => (false, 100),
-            LOAD_INPUT_BY_FIELD_SYSCALL_NUMBER => (true, 10),
+            LOAD_INPUT_SYSCALL_NUMBER => (false, self.cost_table.load_input),
+            LOAD_INPUT_BY_FIELD_SYSCALL_NUMBER => (true, self.cost_table.load_input_by_field),
             _ => return Ok(false),
         };
 
@@ -109,9 +118,9 @@ impl<'a, Mac: SupportMachine> Syscalls<Mac> for LoadInput<'a> {
         let input = input.unwrap();
 
         let len = if load_by_field {
-            self.load_by_field(machine, &input)?
+            self.load_by_field(machine, &input, cycle_factor)?
         } else {
-            self.load_full(machine, &input)?
+            self.load_full(machine, &input, cycle_factor)?
         };
 
         machine.add_cycles(len as u64 * cycle_factor)?;
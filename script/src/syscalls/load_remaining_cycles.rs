@@ -0,0 +1,38 @@
+use crate::syscalls::{CostTable, LOAD_REMAINING_CYCLES_SYSCALL_NUMBER};
+use ckb_vm::{
+    registers::{A0, A7},
+    Error as VMError, Register, SupportMachine, Syscalls,
+};
+
+/// Returns the cycles left before the limit in A0, or `u64::max_value()` if the machine has
+/// no limit. The fixed cost of the query is charged first, so it's already deducted.
+#[derive(Debug)]
+pub struct LoadRemainingCycles {
+    cost_table: CostTable,
+}
+
+impl LoadRemainingCycles {
+    pub fn new(cost_table: CostTable) -> LoadRemainingCycles {
+        LoadRemainingCycles { cost_table }
+    }
+}
+
+impl<Mac: SupportMachine> Syscalls<Mac> for LoadRemainingCycles {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), VMError> {
+        Ok(())
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, VMError> {
+        if machine.registers()[A7].to_u64() != LOAD_REMAINING_CYCLES_SYSCALL_NUMBER {
+            return Ok(false);
+        }
+
+        machine.add_cycles(self.cost_table.load_remaining_cycles)?;
+        let remaining = match machine.max_cycles() {
+            Some(max_cycles) => max_cycles.saturating_sub(machine.cycles()),
+            None => u64::max_value(),
+        };
+        machine.set_register(A0, Mac::REG::from_u64(remaining));
+        Ok(true)
+    }
+}
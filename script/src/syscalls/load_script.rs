@@ -0,0 +1,37 @@
+use crate::syscalls::{utils::store_data, CostTable, LOAD_SCRIPT_SYSCALL_NUMBER, SUCCESS};
+use ckb_types::{packed::Script, prelude::*};
+use ckb_vm::{
+    registers::{A0, A7},
+    Error as VMError, Register, SupportMachine, Syscalls,
+};
+
+#[derive(Debug)]
+pub struct LoadScript {
+    script: Script,
+    cost_table: CostTable,
+}
+
+impl LoadScript {
+    pub fn new(script: Script, cost_table: CostTable) -> LoadScript {
+        LoadScript { script, cost_table }
+    }
+}
+
+impl<Mac: SupportMachine> Syscalls<Mac> for LoadScript {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), VMError> {
+        Ok(())
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, VMError> {
+        if machine.registers()[A7].to_u64() != LOAD_SCRIPT_SYSCALL_NUMBER {
+            return Ok(false);
+        }
+
+        let data = self.script.as_slice();
+        store_data(machine, data, self.cost_table.load_script)?;
+
+        machine.set_register(A0, Mac::REG::from_u8(SUCCESS));
+        machine.add_cycles(data.len() as u64 * self.cost_table.load_script)?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,50 @@
+use crate::syscalls::{
+    utils::store_data, CostTable, LOAD_SCRIPT_GROUP_INPUT_INDICES_SYSCALL_NUMBER, SUCCESS,
+};
+use byteorder::{LittleEndian, WriteBytesExt};
+use ckb_vm::{
+    registers::{A0, A7},
+    Error as VMError, Register, SupportMachine, Syscalls,
+};
+
+/// Loads the indices of the inputs in the current script group, each one is a little
+/// endian u64, in the order they appear in the transaction.
+#[derive(Debug)]
+pub struct LoadScriptGroupInputIndices<'a> {
+    group_inputs: &'a [usize],
+    cost_table: CostTable,
+}
+
+impl<'a> LoadScriptGroupInputIndices<'a> {
+    pub fn new(
+        group_inputs: &'a [usize],
+        cost_table: CostTable,
+    ) -> LoadScriptGroupInputIndices<'a> {
+        LoadScriptGroupInputIndices {
+            group_inputs,
+            cost_table,
+        }
+    }
+}
+
+impl<'a, Mac: SupportMachine> Syscalls<Mac> for LoadScriptGroupInputIndices<'a> {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), VMError> {
+        Ok(())
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, VMError> {
+        if machine.registers()[A7].to_u64() != LOAD_SCRIPT_GROUP_INPUT_INDICES_SYSCALL_NUMBER {
+            return Ok(false);
+        }
+
+        let mut buffer = Vec::with_capacity(self.group_inputs.len() * 8);
+        for index in self.group_inputs {
+            buffer.write_u64::<LittleEndian>(*index as u64)?;
+        }
+        let written = store_data(machine, &buffer, self.cost_table.load_script)?;
+
+        machine.set_register(A0, Mac::REG::from_u8(SUCCESS));
+        machine.add_cycles(written * self.cost_table.load_script)?;
+        Ok(true)
+    }
+}
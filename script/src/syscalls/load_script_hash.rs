@@ -1,4 +1,4 @@
-use crate::syscalls::{utils::store_data, LOAD_SCRIPT_HASH_SYSCALL_NUMBER, SUCCESS};
+use crate::syscalls::{utils::store_data, CostTable, LOAD_SCRIPT_HASH_SYSCALL_NUMBER, SUCCESS};
 use ckb_types::packed::Byte32;
 use ckb_vm::{
     registers::{A0, A7},
@@ -8,11 +8,12 @@ use ckb_vm::{
 #[derive(Debug)]
 pub struct LoadScriptHash {
     hash: Byte32,
+    cost_table: CostTable,
 }
 
 impl LoadScriptHash {
-    pub fn new(hash: Byte32) -> LoadScriptHash {
-        LoadScriptHash { hash }
+    pub fn new(hash: Byte32, cost_table: CostTable) -> LoadScriptHash {
+        LoadScriptHash { hash, cost_table }
     }
 }
 
@@ -27,10 +28,10 @@ impl<Mac: SupportMachine> Syscalls<Mac> for LoadScriptHash {
         }
 
         let data = self.hash.as_reader().raw_data();
-        store_data(machine, data)?;
+        store_data(machine, data, self.cost_table.load_script)?;
 
         machine.set_register(A0, Mac::REG::from_u8(SUCCESS));
-        machine.add_cycles(data.len() as u64 * 10)?;
+        machine.add_cycles(data.len() as u64 * self.cost_table.load_script)?;
         Ok(true)
     }
 }
@@ -0,0 +1,53 @@
+use crate::syscalls::{utils::store_data, CostTable, LOAD_TX_COUNTS_SYSCALL_NUMBER, SUCCESS};
+use byteorder::{LittleEndian, WriteBytesExt};
+use ckb_types::core::TransactionView;
+use ckb_vm::{
+    registers::{A0, A7},
+    Error as VMError, Register, SupportMachine, Syscalls,
+};
+
+/// Loads the counts of inputs, outputs, cell deps and header deps, each one is
+/// a little endian u64.
+#[derive(Debug)]
+pub struct LoadTxCounts {
+    inputs: u64,
+    outputs: u64,
+    cell_deps: u64,
+    header_deps: u64,
+    cost_table: CostTable,
+}
+
+impl LoadTxCounts {
+    pub fn new(tx: &TransactionView, cost_table: CostTable) -> LoadTxCounts {
+        LoadTxCounts {
+            inputs: tx.inputs().len() as u64,
+            outputs: tx.outputs().len() as u64,
+            cell_deps: tx.cell_deps().len() as u64,
+            header_deps: tx.header_deps().len() as u64,
+            cost_table,
+        }
+    }
+}
+
+impl<Mac: SupportMachine> Syscalls<Mac> for LoadTxCounts {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), VMError> {
+        Ok(())
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, VMError> {
+        if machine.registers()[A7].to_u64() != LOAD_TX_COUNTS_SYSCALL_NUMBER {
+            return Ok(false);
+        }
+
+        let mut buffer = vec![];
+        buffer.write_u64::<LittleEndian>(self.inputs)?;
+        buffer.write_u64::<LittleEndian>(self.outputs)?;
+        buffer.write_u64::<LittleEndian>(self.cell_deps)?;
+        buffer.write_u64::<LittleEndian>(self.header_deps)?;
+        let written = store_data(machine, &buffer, self.cost_table.load_tx)?;
+
+        machine.set_register(A0, Mac::REG::from_u8(SUCCESS));
+        machine.add_cycles(written * self.cost_table.load_tx)?;
+        Ok(true)
+    }
+}
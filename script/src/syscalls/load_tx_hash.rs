@@ -1,4 +1,4 @@
-use crate::syscalls::{utils::store_data, LOAD_TX_HASH_SYSCALL_NUMBER, SUCCESS};
+use crate::syscalls::{utils::store_data, CostTable, LOAD_TX_HASH_SYSCALL_NUMBER, SUCCESS};
 use ckb_types::{packed::Byte32, prelude::*};
 use ckb_vm::{
     registers::{A0, A7},
@@ -8,11 +8,15 @@ use ckb_vm::{
 #[derive(Debug)]
 pub struct LoadTxHash {
     tx_hash: Byte32,
+    cost_table: CostTable,
 }
 
 impl LoadTxHash {
-    pub fn new(tx_hash: Byte32) -> LoadTxHash {
-        LoadTxHash { tx_hash }
+    pub fn new(tx_hash: Byte32, cost_table: CostTable) -> LoadTxHash {
+        LoadTxHash {
+            tx_hash,
+            cost_table,
+        }
     }
 }
 
@@ -27,10 +31,10 @@ impl<Mac: SupportMachine> Syscalls<Mac> for LoadTxHash {
         }
 
         let data = self.tx_hash.as_slice();
-        store_data(machine, data)?;
+        store_data(machine, data, self.cost_table.load_tx)?;
 
         machine.set_register(A0, Mac::REG::from_u8(SUCCESS));
-        machine.add_cycles(data.len() as u64 * 10)?;
+        machine.add_cycles(data.len() as u64 * self.cost_table.load_tx)?;
         Ok(true)
     }
 }
@@ -1,6 +1,6 @@
 use crate::syscalls::{
-    utils::store_data, Source, SourceEntry, INDEX_OUT_OF_BOUND, LOAD_WITNESS_SYSCALL_NUMBER,
-    SUCCESS,
+    utils::store_data, CostTable, Source, SourceEntry, INDEX_OUT_OF_BOUND,
+    LOAD_WITNESS_SYSCALL_NUMBER, SUCCESS,
 };
 use ckb_types::{
     packed::{Witness, WitnessVec},
@@ -11,17 +11,28 @@ use ckb_vm::{
     Error as VMError, Register, SupportMachine, Syscalls,
 };
 
+/// Loads the serialized witness at the index in A3 of the source in A4, the witnesses
+/// are matched to the inputs by index.
+///
+/// The whole witness is charged however much of it is stored, both are part of the
+/// consensus.
 #[derive(Debug)]
 pub struct LoadWitness<'a> {
     witnesses: WitnessVec,
     group_inputs: &'a [usize],
+    cost_table: CostTable,
 }
 
 impl<'a> LoadWitness<'a> {
-    pub fn new(witnesses: WitnessVec, group_inputs: &'a [usize]) -> LoadWitness<'a> {
+    pub fn new(
+        witnesses: WitnessVec,
+        group_inputs: &'a [usize],
+        cost_table: CostTable,
+    ) -> LoadWitness<'a> {
         LoadWitness {
             witnesses,
             group_inputs,
+            cost_table,
         }
     }
 
@@ -58,9 +69,9 @@ impl<'a, Mac: SupportMachine> Syscalls<Mac> for LoadWitness<'a> {
         let witness = witness.unwrap();
         let data = witness.as_slice();
 
-        store_data(machine, data)?;
+        store_data(machine, data, self.cost_table.load_witness)?;
         machine.set_register(A0, Mac::REG::from_u8(SUCCESS));
-        machine.add_cycles(data.len() as u64 * 10)?;
+        machine.add_cycles(data.len() as u64 * self.cost_table.load_witness)?;
         Ok(true)
     }
 }
@@ -1,45 +1,59 @@
+pub mod codes;
+mod cost_table;
+mod cycle_meter;
 mod debugger;
 mod load_cell;
 mod load_cell_data;
+mod load_cell_dep_out_point;
 mod load_header;
 mod load_input;
+mod load_remaining_cycles;
+mod load_script;
+mod load_script_group_input_indices;
 mod load_script_hash;
+mod load_tx_counts;
 mod load_tx_hash;
 mod load_witness;
 mod utils;
 
+pub use self::codes::{INDEX_OUT_OF_BOUND, ITEM_MISSING, SLICE_OUT_OF_BOUND, SUCCESS};
+pub use self::cost_table::CostTable;
+pub use self::cycle_meter::{CycleMeter, Metered};
 pub use self::debugger::Debugger;
 pub use self::load_cell::LoadCell;
 pub use self::load_cell_data::LoadCellData;
+pub use self::load_cell_dep_out_point::LoadCellDepOutPoint;
 pub use self::load_header::LoadHeader;
 pub use self::load_input::LoadInput;
+pub use self::load_remaining_cycles::LoadRemainingCycles;
+pub use self::load_script::LoadScript;
+pub use self::load_script_group_input_indices::LoadScriptGroupInputIndices;
 pub use self::load_script_hash::LoadScriptHash;
+pub use self::load_tx_counts::LoadTxCounts;
 pub use self::load_tx_hash::LoadTxHash;
 pub use self::load_witness::LoadWitness;
 
 use ckb_vm::Error;
 
-pub const SUCCESS: u8 = 0;
-// INDEX_OUT_OF_BOUND is returned when requesting the 4th output in a transaction
-// with only 3 outputs; while ITEM_MISSING is returned when requesting (for example)
-// the type field on an output without type script, or requesting the cell data
-// for a dep OutPoint which only references a block header.
-pub const INDEX_OUT_OF_BOUND: u8 = 1;
-pub const ITEM_MISSING: u8 = 2;
-pub const SLICE_OUT_OF_BOUND: u8 = 3;
-
+pub const LOAD_SCRIPT_SYSCALL_NUMBER: u64 = 2052;
 pub const LOAD_TX_HASH_SYSCALL_NUMBER: u64 = 2061;
 pub const LOAD_SCRIPT_HASH_SYSCALL_NUMBER: u64 = 2062;
+pub const LOAD_TX_COUNTS_SYSCALL_NUMBER: u64 = 2063;
+pub const LOAD_SCRIPT_GROUP_INPUT_INDICES_SYSCALL_NUMBER: u64 = 2064;
 pub const LOAD_CELL_SYSCALL_NUMBER: u64 = 2071;
 pub const LOAD_HEADER_SYSCALL_NUMBER: u64 = 2072;
 pub const LOAD_INPUT_SYSCALL_NUMBER: u64 = 2073;
 pub const LOAD_WITNESS_SYSCALL_NUMBER: u64 = 2074;
+pub const LOAD_CELL_DEP_OUT_POINT_SYSCALL_NUMBER: u64 = 2075;
 pub const LOAD_CELL_BY_FIELD_SYSCALL_NUMBER: u64 = 2081;
 pub const LOAD_HEADER_BY_FIELD_SYSCALL_NUMBER: u64 = 2082;
 pub const LOAD_INPUT_BY_FIELD_SYSCALL_NUMBER: u64 = 2083;
 pub const LOAD_CELL_DATA_AS_CODE_SYSCALL_NUMBER: u64 = 2091;
 pub const LOAD_CELL_DATA_SYSCALL_NUMBER: u64 = 2092;
+pub const LOAD_CELL_DATA_LENGTH_SYSCALL_NUMBER: u64 = 2093;
+pub const LOAD_REMAINING_CYCLES_SYSCALL_NUMBER: u64 = 2101;
 pub const DEBUG_PRINT_SYSCALL_NUMBER: u64 = 2177;
+pub const DEBUG_WRITE_SYSCALL_NUMBER: u64 = 2178;
 
 #[derive(Debug, PartialEq, Clone, Copy, Eq)]
 enum CellField {
@@ -89,6 +103,9 @@ impl HeaderField {
     }
 }
 
+// The out point of an input is loaded by LOAD_INPUT_BY_FIELD with the OutPoint field, and
+// Source::Transaction(SourceEntry::Input) for the index of the transaction inputs. There is no
+// separate syscall for it.
 #[derive(Debug, PartialEq, Clone, Copy, Eq)]
 enum InputField {
     OutPoint = 0,
@@ -179,9 +196,10 @@ mod tests {
     use ckb_types::{
         bytes::Bytes,
         core::{
-            cell::CellMeta, BlockExt, Capacity, EpochExt, HeaderBuilder, HeaderView, ScriptHashType,
+            cell::CellMeta, BlockExt, Capacity, EpochExt, HeaderBuilder, HeaderView,
+            ScriptHashType, TransactionBuilder,
         },
-        packed::{Byte32, CellOutput, OutPoint, Script, Witness},
+        packed::{Byte32, CellDep, CellInput, CellOutput, OutPoint, Script, Witness},
         prelude::*,
         H256, U256,
     };
@@ -189,9 +207,11 @@ mod tests {
     use ckb_vm::{
         memory::{FLAG_EXECUTABLE, FLAG_FREEZED, FLAG_WRITABLE},
         registers::{A0, A1, A2, A3, A4, A5, A7},
-        CoreMachine, Memory, SparseMemory, Syscalls, WXorXMemory, RISCV_PAGESIZE,
+        CoreMachine, Memory, SparseMemory, SupportMachine, Syscalls, WXorXMemory, RISCV_MAX_MEMORY,
+        RISCV_PAGESIZE,
     };
     use proptest::{collection::size_range, prelude::*};
+    use std::cell::RefCell;
     use std::collections::HashMap;
 
     fn new_store() -> ChainDB {
@@ -243,6 +263,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         prop_assert!(load_cell.ecall(&mut machine).is_ok());
@@ -284,6 +305,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         let input_correct_data = input_cell.cell_output.as_slice();
@@ -373,6 +395,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         let input_correct_data = input_cell.cell_output.as_slice();
@@ -424,6 +447,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         let input_correct_data = input_cell.cell_output.as_slice();
@@ -487,6 +511,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         prop_assert!(machine.memory_mut().store64(&size_addr, &16).is_ok());
@@ -538,6 +563,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         assert!(machine.memory_mut().store64(&size_addr, &100).is_ok());
@@ -608,6 +634,7 @@ mod tests {
             &resolved_inputs,
             &resolved_cell_deps,
             &group_inputs,
+            CostTable::default(),
         );
 
         prop_assert!(machine
@@ -685,6 +712,7 @@ mod tests {
             &resolved_inputs,
             &resolved_cell_deps,
             &group_inputs,
+            CostTable::default(),
         );
 
         prop_assert!(machine
@@ -728,7 +756,7 @@ mod tests {
 
         let hash = blake2b_256(&data);
         let hash_len = 32u64;
-        let mut load_tx_hash = LoadTxHash::new(hash.pack());
+        let mut load_tx_hash = LoadTxHash::new(hash.pack(), CostTable::default());
 
         prop_assert!(machine
             .memory_mut()
@@ -753,6 +781,199 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_load_cell_dep_out_point() {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        let size_addr: u64 = 0;
+        let addr: u64 = 100;
+
+        machine.set_register(A0, addr); // addr
+        machine.set_register(A1, size_addr); // size_addr
+        machine.set_register(A2, 0); // offset
+        machine.set_register(A3, 1); // index
+        machine.set_register(A7, LOAD_CELL_DEP_OUT_POINT_SYSCALL_NUMBER); // syscall number
+
+        let out_point = OutPoint::new(Byte32::new([1u8; 32]), 2);
+        let cell_deps = vec![
+            CellDep::default(),
+            CellDep::new_builder().out_point(out_point.clone()).build(),
+        ];
+        let mut load_cell_dep_out_point =
+            LoadCellDepOutPoint::new(cell_deps.pack(), CostTable::default());
+
+        assert!(machine.memory_mut().store64(&size_addr, &64).is_ok());
+        assert!(load_cell_dep_out_point.ecall(&mut machine).is_ok());
+        assert_eq!(machine.registers()[A0], u64::from(SUCCESS));
+        assert_eq!(
+            machine.memory_mut().load64(&size_addr),
+            Ok(out_point.as_slice().len() as u64)
+        );
+        for (i, addr) in (addr..addr + out_point.as_slice().len() as u64).enumerate() {
+            assert_eq!(
+                machine.memory_mut().load8(&addr),
+                Ok(u64::from(out_point.as_slice()[i]))
+            );
+        }
+        assert_eq!(machine.cycles(), out_point.as_slice().len() as u64 * 10);
+
+        machine.set_register(A3, 2); // index
+        assert!(load_cell_dep_out_point.ecall(&mut machine).is_ok());
+        assert_eq!(machine.registers()[A0], u64::from(INDEX_OUT_OF_BOUND));
+    }
+
+    #[test]
+    fn test_load_tx_counts() {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        let size_addr: u64 = 0;
+        let addr: u64 = 100;
+
+        machine.set_register(A0, addr); // addr
+        machine.set_register(A1, size_addr); // size_addr
+        machine.set_register(A2, 8); // offset
+        machine.set_register(A7, LOAD_TX_COUNTS_SYSCALL_NUMBER); // syscall number
+
+        let tx = TransactionBuilder::default()
+            .input(CellInput::new(OutPoint::null(), 0))
+            .input(CellInput::new(OutPoint::null(), 0))
+            .output(CellOutput::default())
+            .output_data(Bytes::new().pack())
+            .header_dep(Byte32::zero())
+            .build();
+        let mut load_tx_counts = LoadTxCounts::new(&tx, CostTable::default());
+
+        assert!(machine.memory_mut().store64(&size_addr, &64).is_ok());
+
+        assert!(load_tx_counts.ecall(&mut machine).is_ok());
+        assert_eq!(machine.registers()[A0], u64::from(SUCCESS));
+        assert_eq!(machine.memory_mut().load64(&size_addr), Ok(24));
+        // the input count is skipped by the offset
+        assert_eq!(machine.memory_mut().load64(&addr), Ok(1));
+        assert_eq!(machine.memory_mut().load64(&(addr + 8)), Ok(0));
+        assert_eq!(machine.memory_mut().load64(&(addr + 16)), Ok(1));
+        assert_eq!(machine.cycles(), 24 * 10);
+    }
+
+    #[test]
+    fn test_load_script_group_input_indices() {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        let size_addr: u64 = 0;
+        let addr: u64 = 100;
+
+        machine.set_register(A0, addr); // addr
+        machine.set_register(A1, size_addr); // size_addr
+        machine.set_register(A2, 0); // offset
+        machine.set_register(A7, LOAD_SCRIPT_GROUP_INPUT_INDICES_SYSCALL_NUMBER); // syscall number
+
+        let group_inputs = vec![1, 3, 4];
+        let mut load_indices =
+            LoadScriptGroupInputIndices::new(&group_inputs, CostTable::default());
+
+        assert!(machine.memory_mut().store64(&size_addr, &16).is_ok());
+        assert!(load_indices.ecall(&mut machine).is_ok());
+        assert_eq!(machine.registers()[A0], u64::from(SUCCESS));
+        assert_eq!(machine.memory_mut().load64(&size_addr), Ok(24));
+        assert_eq!(machine.memory_mut().load64(&addr), Ok(1));
+        assert_eq!(machine.memory_mut().load64(&(addr + 8)), Ok(3));
+        assert_eq!(machine.memory_mut().load64(&(addr + 16)), Ok(0));
+        assert_eq!(machine.cycles(), 16 * 10);
+
+        // the buffer runs past the end of the memory
+        machine.set_register(A0, RISCV_MAX_MEMORY as u64 - 8);
+        assert!(machine.memory_mut().store64(&size_addr, &24).is_ok());
+        assert!(load_indices.ecall(&mut machine).is_err());
+    }
+
+    #[test]
+    fn test_load_remaining_cycles() {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::new_with_max_cycles(1000);
+        machine.set_register(A7, LOAD_REMAINING_CYCLES_SYSCALL_NUMBER); // syscall number
+        assert!(machine.add_cycles(100).is_ok());
+
+        let mut load_remaining_cycles = LoadRemainingCycles::new(CostTable::default());
+        assert!(load_remaining_cycles.ecall(&mut machine).is_ok());
+        assert_eq!(machine.registers()[A0], 1000 - 100 - 10);
+        assert_eq!(machine.cycles(), 110);
+
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        machine.set_register(A7, LOAD_REMAINING_CYCLES_SYSCALL_NUMBER); // syscall number
+        assert!(load_remaining_cycles.ecall(&mut machine).is_ok());
+        assert_eq!(machine.registers()[A0], u64::max_value());
+    }
+
+    #[test]
+    fn test_debug_write() {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        let addr: u64 = 100;
+        assert!(machine
+            .memory_mut()
+            .store_bytes(addr, b"hello\0world")
+            .is_ok());
+        machine.set_register(A3, addr); // addr
+        machine.set_register(A4, 11); // length
+        machine.set_register(A7, DEBUG_WRITE_SYSCALL_NUMBER); // syscall number
+
+        let output = RefCell::new(Vec::new());
+        let printer = |message: &str| output.borrow_mut().push(message.to_owned());
+        let mut debugger = Debugger::new(&printer, CostTable::default());
+        assert!(debugger.ecall(&mut machine).is_ok());
+        assert_eq!(output.borrow().as_slice(), &["hello\0world".to_owned()]);
+        assert_eq!(machine.cycles(), 11 * CostTable::default().debug_print);
+
+        machine.set_register(A7, DEBUG_PRINT_SYSCALL_NUMBER); // syscall number
+        machine.set_register(A0, addr); // addr
+        assert!(debugger.ecall(&mut machine).is_ok());
+        assert_eq!(output.borrow().last().map(String::as_str), Some("hello"));
+
+        // the invalid utf-8 is printed lossily
+        assert!(machine.memory_mut().store_bytes(addr, b"\xffok\0").is_ok());
+        assert!(debugger.ecall(&mut machine).is_ok());
+        assert_eq!(
+            output.borrow().last().map(String::as_str),
+            Some("\u{fffd}ok")
+        );
+    }
+
+    #[test]
+    fn test_debug_write_discarding() {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        let addr: u64 = 100;
+        assert!(machine.memory_mut().store_bytes(addr, b"hello").is_ok());
+        machine.set_register(A3, addr); // addr
+        machine.set_register(A4, 5); // length
+        machine.set_register(A7, DEBUG_WRITE_SYSCALL_NUMBER); // syscall number
+
+        let mut debugger = Debugger::discarding(CostTable::default());
+        assert!(debugger.ecall(&mut machine).is_ok());
+        assert_eq!(machine.cycles(), 5 * CostTable::default().debug_print);
+
+        // the length is charged before any byte is read
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::new_with_max_cycles(100);
+        machine.set_register(A3, addr); // addr
+        machine.set_register(A4, u64::max_value()); // length
+        machine.set_register(A7, DEBUG_WRITE_SYSCALL_NUMBER); // syscall number
+        assert!(debugger.ecall(&mut machine).is_err());
+        assert_eq!(machine.cycles(), 0);
+    }
+
+    #[test]
+    fn test_charge_stored_bytes_on_fault() {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        let size_addr: u64 = 0;
+        // 10 bytes are left in the last page
+        let addr = RISCV_MAX_MEMORY as u64 - 10;
+
+        machine.set_register(A0, addr); // addr
+        machine.set_register(A1, size_addr); // size_addr
+        machine.set_register(A2, 0); // offset
+        machine.set_register(A7, LOAD_SCRIPT_HASH_SYSCALL_NUMBER); // syscall number
+        assert!(machine.memory_mut().store64(&size_addr, &32).is_ok());
+
+        let hash = Script::default().calc_script_hash();
+        let mut load_script_hash = LoadScriptHash::new(hash, CostTable::default());
+        assert!(load_script_hash.ecall(&mut machine).is_err());
+        assert_eq!(machine.cycles(), 10 * CostTable::default().load_script);
+    }
+
     fn _test_load_current_script_hash(data: &[u8]) -> Result<(), TestCaseError> {
         let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
         let size_addr: u64 = 0;
@@ -769,7 +990,7 @@ mod tests {
             .build();
         let hash = script.calc_script_hash();
         let data = hash.raw_data();
-        let mut load_script_hash = LoadScriptHash::new(hash);
+        let mut load_script_hash = LoadScriptHash::new(hash, CostTable::default());
 
         prop_assert!(machine.memory_mut().store64(&size_addr, &64).is_ok());
 
@@ -805,6 +1026,101 @@ mod tests {
         }
     }
 
+    fn _test_load_current_script(data: &[u8]) -> Result<(), TestCaseError> {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        let size_addr: u64 = 0;
+        let addr: u64 = 100;
+
+        machine.set_register(A0, addr); // addr
+        machine.set_register(A1, size_addr); // size_addr
+        machine.set_register(A2, 0); // offset
+        machine.set_register(A7, LOAD_SCRIPT_SYSCALL_NUMBER); // syscall number
+
+        let script = Script::new_builder()
+            .args(vec![Bytes::from(data)].pack())
+            .hash_type(ScriptHashType::Data.pack())
+            .build();
+        let data = script.as_slice().to_vec();
+        let mut load_script = LoadScript::new(script, CostTable::default());
+
+        prop_assert!(machine
+            .memory_mut()
+            .store64(&size_addr, &(data.len() as u64))
+            .is_ok());
+
+        prop_assert!(load_script.ecall(&mut machine).is_ok());
+        prop_assert_eq!(machine.registers()[A0], u64::from(SUCCESS));
+
+        prop_assert_eq!(
+            machine.memory_mut().load64(&size_addr),
+            Ok(data.len() as u64)
+        );
+
+        for (i, addr) in (addr..addr + data.len() as u64).enumerate() {
+            prop_assert_eq!(machine.memory_mut().load8(&addr), Ok(u64::from(data[i])));
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn test_load_current_script(ref data in any_with::<Vec<u8>>(size_range(1000).lift())) {
+            _test_load_current_script(data)?;
+        }
+    }
+
+    #[test]
+    fn test_metered_syscall_records_cycles() {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        let size_addr: u64 = 0;
+        let addr: u64 = 100;
+
+        machine.set_register(A0, addr); // addr
+        machine.set_register(A1, size_addr); // size_addr
+        machine.set_register(A2, 0); // offset
+        machine.set_register(A7, LOAD_SCRIPT_HASH_SYSCALL_NUMBER); // syscall number
+
+        let hash = Script::default().calc_script_hash();
+        let meter = CycleMeter::new();
+        let mut load_script_hash = Metered::new(
+            LoadScriptHash::new(hash, CostTable::default()),
+            meter.clone(),
+        );
+
+        assert!(machine.memory_mut().store64(&size_addr, &64).is_ok());
+        assert!(load_script_hash.ecall(&mut machine).is_ok());
+        assert_eq!(machine.registers()[A0], u64::from(SUCCESS));
+
+        let histogram = meter.histogram();
+        assert_eq!(histogram.len(), 1);
+        assert_eq!(histogram[&LOAD_SCRIPT_HASH_SYSCALL_NUMBER], 32 * 10);
+        assert_eq!(machine.cycles(), 32 * 10);
+    }
+
+    #[test]
+    fn test_load_script_hash_with_cost_table() {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        let size_addr: u64 = 0;
+        let addr: u64 = 100;
+
+        machine.set_register(A0, addr); // addr
+        machine.set_register(A1, size_addr); // size_addr
+        machine.set_register(A2, 0); // offset
+        machine.set_register(A7, LOAD_SCRIPT_HASH_SYSCALL_NUMBER); // syscall number
+
+        let hash = Script::default().calc_script_hash();
+        let cost_table = CostTable {
+            load_script: 3,
+            ..Default::default()
+        };
+        let mut load_script_hash = LoadScriptHash::new(hash, cost_table);
+
+        assert!(machine.memory_mut().store64(&size_addr, &64).is_ok());
+        assert!(load_script_hash.ecall(&mut machine).is_ok());
+        assert_eq!(machine.registers()[A0], u64::from(SUCCESS));
+        assert_eq!(machine.cycles(), 32 * 3);
+    }
+
     fn _test_load_input_lock_script_hash(data: &[u8]) -> Result<(), TestCaseError> {
         let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
         let size_addr: u64 = 0;
@@ -844,6 +1160,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         prop_assert!(machine.memory_mut().store64(&size_addr, &64).is_ok());
@@ -887,7 +1204,8 @@ mod tests {
 
         let witnesses = vec![witness.clone()];
         let group_inputs = vec![];
-        let mut load_witness = LoadWitness::new(witnesses.pack(), &group_inputs);
+        let mut load_witness =
+            LoadWitness::new(witnesses.pack(), &group_inputs, CostTable::default());
 
         prop_assert!(machine
             .memory_mut()
@@ -918,6 +1236,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_load_witness_with_offset() {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        let size_addr: u64 = 0;
+        let addr: u64 = 100;
+
+        machine.set_register(A0, addr); // addr
+        machine.set_register(A1, size_addr); // size_addr
+        machine.set_register(A2, 4); // offset
+        machine.set_register(A3, 1); //index
+        machine.set_register(A4, u64::from(Source::Transaction(SourceEntry::Input))); //source
+        machine.set_register(A7, LOAD_WITNESS_SYSCALL_NUMBER); // syscall number
+
+        let witness: Witness = vec![Bytes::from(vec![1, 2, 3]).pack()].pack();
+        let witness_data = witness.as_slice();
+        let witnesses = vec![vec![].pack(), witness.clone()];
+        let group_inputs = vec![];
+        let mut load_witness =
+            LoadWitness::new(witnesses.pack(), &group_inputs, CostTable::default());
+
+        assert!(machine.memory_mut().store64(&size_addr, &2).is_ok());
+        assert!(load_witness.ecall(&mut machine).is_ok());
+        assert_eq!(machine.registers()[A0], u64::from(SUCCESS));
+        // the full remaining length is reported, only the buffer size is written
+        assert_eq!(
+            machine.memory_mut().load64(&size_addr),
+            Ok(witness_data.len() as u64 - 4)
+        );
+        for (i, addr) in (addr..addr + 2).enumerate() {
+            assert_eq!(
+                machine.memory_mut().load8(&addr),
+                Ok(u64::from(witness_data[4 + i]))
+            );
+        }
+        // the whole witness is charged
+        assert_eq!(
+            machine.cycles(),
+            witness_data.len() as u64 * CostTable::default().load_witness
+        );
+
+        machine.set_register(A3, 2); //index
+        assert!(load_witness.ecall(&mut machine).is_ok());
+        assert_eq!(machine.registers()[A0], u64::from(INDEX_OUT_OF_BOUND));
+
+        // the witnesses are not matched to the outputs
+        machine.set_register(A3, 1); //index
+        machine.set_register(A4, u64::from(Source::Transaction(SourceEntry::Output))); //source
+        assert!(load_witness.ecall(&mut machine).is_ok());
+        assert_eq!(machine.registers()[A0], u64::from(INDEX_OUT_OF_BOUND));
+    }
+
     fn _test_load_group_witness(data: &[u8]) -> Result<(), TestCaseError> {
         let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
         let size_addr: u64 = 0;
@@ -937,7 +1306,8 @@ mod tests {
         let dummy_witness = vec![];
         let witnesses = vec![dummy_witness.pack(), witness.clone()];
         let group_inputs = vec![1];
-        let mut load_witness = LoadWitness::new(witnesses.pack(), &group_inputs);
+        let mut load_witness =
+            LoadWitness::new(witnesses.pack(), &group_inputs, CostTable::default());
 
         prop_assert!(machine
             .memory_mut()
@@ -998,6 +1368,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         prop_assert!(machine.memory_mut().store_byte(addr, addr_size, 1).is_ok());
@@ -1055,6 +1426,7 @@ mod tests {
             &resolved_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         prop_assert!(load_code.ecall(&mut machine).is_ok());
@@ -1093,6 +1465,60 @@ mod tests {
         }
     }
 
+    fn _test_load_cell_data_length(data: &[u8]) -> Result<(), TestCaseError> {
+        let mut machine = DefaultCoreMachine::<u64, SparseMemory<u64>>::default();
+        let size_addr: u64 = 0;
+        let addr: u64 = 100;
+
+        machine.set_register(A0, addr); // addr
+        machine.set_register(A1, size_addr); // size_addr
+        machine.set_register(A2, 0); // offset
+        machine.set_register(A3, 0); //index
+        machine.set_register(A4, u64::from(Source::Transaction(SourceEntry::CellDep))); //source
+        machine.set_register(A7, LOAD_CELL_DATA_LENGTH_SYSCALL_NUMBER); // syscall number
+
+        prop_assert!(machine.memory_mut().store64(&size_addr, &8).is_ok());
+
+        // the cell data in memory is dropped, the length must come from the cell meta
+        let mut dep_cell = build_cell_meta(10000, Bytes::from(data));
+        dep_cell.mem_cell_data = None;
+
+        let store = new_store();
+        let data_loader = DataLoaderWrapper::new(&store);
+        let outputs = vec![];
+        let resolved_inputs = vec![];
+        let resolved_deps = vec![dep_cell];
+        let group_inputs = vec![];
+        let group_outputs = vec![];
+        let mut load_cell_data = LoadCellData::new(
+            &data_loader,
+            &outputs,
+            &resolved_inputs,
+            &resolved_deps,
+            &group_inputs,
+            &group_outputs,
+            CostTable::default(),
+        );
+
+        prop_assert!(load_cell_data.ecall(&mut machine).is_ok());
+        prop_assert_eq!(machine.registers()[A0], u64::from(SUCCESS));
+        prop_assert_eq!(machine.memory_mut().load64(&size_addr), Ok(8));
+        prop_assert_eq!(machine.memory_mut().load64(&addr), Ok(data.len() as u64));
+
+        machine.set_register(A0, addr); // addr
+        machine.set_register(A3, 1); //index
+        prop_assert!(load_cell_data.ecall(&mut machine).is_ok());
+        prop_assert_eq!(machine.registers()[A0], u64::from(INDEX_OUT_OF_BOUND));
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn test_load_cell_data_length(ref data in any_with::<Vec<u8>>(size_range(1000).lift())) {
+            _test_load_cell_data_length(data)?;
+        }
+    }
+
     fn _test_load_cell_data_on_freezed_memory(
         as_code: bool,
         data: &[u8],
@@ -1136,6 +1562,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         prop_assert!(load_code.ecall(&mut machine).is_err());
@@ -1192,6 +1619,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         assert!(machine.memory_mut().store_byte(addr, addr_size, 1).is_ok());
@@ -1235,6 +1663,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         assert!(machine.memory_mut().store_byte(addr, addr_size, 1).is_ok());
@@ -1281,6 +1710,7 @@ mod tests {
             &resolved_cell_deps,
             &group_inputs,
             &group_outputs,
+            CostTable::default(),
         );
 
         assert!(machine.memory_mut().store_byte(addr, addr_size, 1).is_ok());
@@ -1,11 +1,24 @@
 use byteorder::{ByteOrder, LittleEndian};
+use ckb_types::core::Cycle;
 use ckb_vm::{
     registers::{A0, A1, A2},
-    Error as VMError, Memory, Register, SupportMachine,
+    Error as VMError, Memory, Register, SupportMachine, RISCV_PAGESIZE,
 };
 use std::cmp;
 
-pub fn store_data<Mac: SupportMachine>(machine: &mut Mac, data: &[u8]) -> Result<u64, VMError> {
+/// Store the data from the offset in A2 into the buffer at A0, whose size is loaded from and
+/// the full remaining size is stored to A1, return the count of bytes stored
+///
+/// All load syscalls store through this, so they behave identically on a memory fault:
+/// the size is stored first, then the data page by page. If storing the data faults,
+/// `cycles_per_byte` is charged for the bytes of the pages already stored before the fault
+/// is returned, a fault on the size charges nothing. Nothing is charged on success, the
+/// syscall charges afterwards by its own rule.
+pub fn store_data<Mac: SupportMachine>(
+    machine: &mut Mac,
+    data: &[u8],
+    cycles_per_byte: Cycle,
+) -> Result<u64, VMError> {
     let addr = machine.registers()[A0].to_u64();
     let size_addr = machine.registers()[A1].clone();
     let data_len = data.len() as u64;
@@ -17,15 +30,30 @@ pub fn store_data<Mac: SupportMachine>(machine: &mut Mac, data: &[u8]) -> Result
     machine
         .memory_mut()
         .store64(&size_addr, &Mac::REG::from_u64(full_size))?;
-    machine
-        .memory_mut()
-        .store_bytes(addr, &data[offset as usize..(offset + real_size) as usize])?;
+
+    let data = &data[offset as usize..(offset + real_size) as usize];
+    let mut written = 0;
+    while written < real_size {
+        let chunk_addr = addr.wrapping_add(written);
+        let page_left = RISCV_PAGESIZE as u64 - chunk_addr % RISCV_PAGESIZE as u64;
+        let chunk_size = cmp::min(page_left, real_size - written);
+        let chunk = &data[written as usize..(written + chunk_size) as usize];
+        if let Err(err) = machine.memory_mut().store_bytes(chunk_addr, chunk) {
+            machine.add_cycles(written * cycles_per_byte)?;
+            return Err(err);
+        }
+        written += chunk_size;
+    }
     Ok(real_size)
 }
 
-pub fn store_u64<Mac: SupportMachine>(machine: &mut Mac, v: u64) -> Result<u64, VMError> {
+pub fn store_u64<Mac: SupportMachine>(
+    machine: &mut Mac,
+    v: u64,
+    cycles_per_byte: Cycle,
+) -> Result<u64, VMError> {
     let mut buffer = [0u8; std::mem::size_of::<u64>()];
     LittleEndian::write_u64(&mut buffer, v);
-    store_data(machine, &buffer)?;
+    store_data(machine, &buffer, cycles_per_byte)?;
     Ok(buffer.len() as u64)
 }
@@ -1,8 +1,9 @@
 use crate::{
     cost_model::instruction_cycles,
     syscalls::{
-        Debugger, LoadCell, LoadCellData, LoadHeader, LoadInput, LoadScriptHash, LoadTxHash,
-        LoadWitness,
+        CostTable, CycleMeter, Debugger, LoadCell, LoadCellData, LoadCellDepOutPoint, LoadHeader,
+        LoadInput, LoadRemainingCycles, LoadScript, LoadScriptGroupInputIndices, LoadScriptHash,
+        LoadTxCounts, LoadTxHash, LoadWitness, Metered,
     },
     type_id::TypeIdSystemScript,
     DataLoader, ScriptError,
@@ -20,6 +21,7 @@ use ckb_types::{
     packed::{Byte32, Byte32Vec, CellInputVec, CellOutput, OutPoint, Script, WitnessVec},
     prelude::*,
 };
+use ckb_vm::Syscalls;
 #[cfg(has_asm)]
 use ckb_vm::{
     machine::asm::{AsmCoreMachine, AsmMachine},
@@ -66,6 +68,9 @@ pub enum ScriptGroupType {
 pub struct TransactionScriptsVerifier<'a, DL> {
     data_loader: &'a DL,
     debug_printer: Option<Box<dyn Fn(&Byte32, &str)>>,
+    debug_output: bool,
+    cycle_meter: Option<CycleMeter>,
+    cost_table: CostTable,
 
     outputs: Vec<CellMeta>,
     rtx: &'a ResolvedTransaction,
@@ -152,6 +157,9 @@ impl<'a, DL: DataLoader> TransactionScriptsVerifier<'a, DL> {
             lock_groups,
             type_groups,
             debug_printer: None,
+            debug_output: false,
+            cycle_meter: None,
+            cost_table: CostTable::default(),
         }
     }
 
@@ -159,6 +167,25 @@ impl<'a, DL: DataLoader> TransactionScriptsVerifier<'a, DL> {
         self.debug_printer = Some(Box::new(func));
     }
 
+    // The debug syscalls only forward their output to the printer, or to the logger without
+    // a printer, when it is enabled for the test and debug runs. It is disabled by default,
+    // the consensus mode still charges the cycles but discards the output.
+    pub fn set_debug_output(&mut self, enabled: bool) {
+        self.debug_output = enabled;
+    }
+
+    // The cycles consumed by each syscall will be reported into the meter,
+    // it doesn't affect the cycles charged for the scripts.
+    pub fn set_cycle_meter(&mut self, meter: CycleMeter) {
+        self.cycle_meter = Some(meter);
+    }
+
+    // Overrides the per byte cycles charged by the syscalls, only for experimenting
+    // with cost models, the default table is the one used by consensus.
+    pub fn set_cost_table(&mut self, cost_table: CostTable) {
+        self.cost_table = cost_table;
+    }
+
     #[inline]
     fn inputs(&self) -> CellInputVec {
         self.rtx.transaction.inputs()
@@ -190,7 +217,11 @@ impl<'a, DL: DataLoader> TransactionScriptsVerifier<'a, DL> {
     }
 
     fn build_load_tx_hash(&self) -> LoadTxHash {
-        LoadTxHash::new(self.hash())
+        LoadTxHash::new(self.hash(), self.cost_table)
+    }
+
+    fn build_load_tx_counts(&self) -> LoadTxCounts {
+        LoadTxCounts::new(&self.rtx.transaction, self.cost_table)
     }
 
     fn build_load_cell(
@@ -204,6 +235,7 @@ impl<'a, DL: DataLoader> TransactionScriptsVerifier<'a, DL> {
             self.resolved_cell_deps(),
             group_inputs,
             group_outputs,
+            self.cost_table,
         )
     }
 
@@ -219,15 +251,35 @@ impl<'a, DL: DataLoader> TransactionScriptsVerifier<'a, DL> {
             self.resolved_cell_deps(),
             group_inputs,
             group_outputs,
+            self.cost_table,
         )
     }
 
+    fn build_load_cell_dep_out_point(&self) -> LoadCellDepOutPoint {
+        LoadCellDepOutPoint::new(self.rtx.transaction.cell_deps(), self.cost_table)
+    }
+
     fn build_load_input(&self, group_inputs: &'a [usize]) -> LoadInput {
-        LoadInput::new(self.inputs(), group_inputs)
+        LoadInput::new(self.inputs(), group_inputs, self.cost_table)
+    }
+
+    fn build_load_remaining_cycles(&self) -> LoadRemainingCycles {
+        LoadRemainingCycles::new(self.cost_table)
     }
 
     fn build_load_script_hash(&self, hash: Byte32) -> LoadScriptHash {
-        LoadScriptHash::new(hash)
+        LoadScriptHash::new(hash, self.cost_table)
+    }
+
+    fn build_load_script_group_input_indices(
+        &self,
+        group_inputs: &'a [usize],
+    ) -> LoadScriptGroupInputIndices<'a> {
+        LoadScriptGroupInputIndices::new(group_inputs, self.cost_table)
+    }
+
+    fn build_load_script(&self, script: Script) -> LoadScript {
+        LoadScript::new(script, self.cost_table)
     }
 
     fn build_load_header(&'a self, group_inputs: &'a [usize]) -> LoadHeader<'a, DL> {
@@ -237,11 +289,12 @@ impl<'a, DL: DataLoader> TransactionScriptsVerifier<'a, DL> {
             self.resolved_inputs(),
             self.resolved_cell_deps(),
             group_inputs,
+            self.cost_table,
         )
     }
 
     fn build_load_witness(&'a self, group_inputs: &'a [usize]) -> LoadWitness<'a> {
-        LoadWitness::new(self.witnesses(), group_inputs)
+        LoadWitness::new(self.witnesses(), group_inputs, self.cost_table)
     }
 
     // Extracts actual script binary either in dep cells.
@@ -346,6 +399,11 @@ impl<'a, DL: DataLoader> TransactionScriptsVerifier<'a, DL> {
                 debug!("{} DEBUG OUTPUT: {}", prefix, message);
             };
         };
+        let debugger = if self.debug_output {
+            Debugger::new(&debug_printer, self.cost_table)
+        } else {
+            Debugger::discarding(self.cost_table)
+        };
         let mut args = vec!["verify".into()];
         args.extend(
             script_group
@@ -367,28 +425,49 @@ impl<'a, DL: DataLoader> TransactionScriptsVerifier<'a, DL> {
                 );
             DefaultMachineBuilder::<DefaultCoreMachine<u64, WXorXMemory<u64, SparseMemory<u64>>>>::new(core_machine)
         };
+        let meter = &self.cycle_meter;
         let default_machine = machine_builder
             .instruction_cycle_func(Box::new(instruction_cycles))
-            .syscall(Box::new(
+            .syscall(metered(
+                meter,
                 self.build_load_script_hash(current_script_hash.clone()),
             ))
-            .syscall(Box::new(self.build_load_tx_hash()))
-            .syscall(Box::new(self.build_load_cell(
-                &script_group.input_indices,
-                &script_group.output_indices,
-            )))
-            .syscall(Box::new(self.build_load_input(&script_group.input_indices)))
-            .syscall(Box::new(
+            .syscall(metered(
+                meter,
+                self.build_load_script(script_group.script.clone()),
+            ))
+            .syscall(metered(
+                meter,
+                self.build_load_script_group_input_indices(&script_group.input_indices),
+            ))
+            .syscall(metered(meter, self.build_load_tx_hash()))
+            .syscall(metered(meter, self.build_load_tx_counts()))
+            .syscall(metered(
+                meter,
+                self.build_load_cell(&script_group.input_indices, &script_group.output_indices),
+            ))
+            .syscall(metered(meter, self.build_load_cell_dep_out_point()))
+            .syscall(metered(
+                meter,
+                self.build_load_input(&script_group.input_indices),
+            ))
+            .syscall(metered(
+                meter,
                 self.build_load_header(&script_group.input_indices),
             ))
-            .syscall(Box::new(
+            .syscall(metered(
+                meter,
                 self.build_load_witness(&script_group.input_indices),
             ))
-            .syscall(Box::new(self.build_load_cell_data(
-                &script_group.input_indices,
-                &script_group.output_indices,
-            )))
-            .syscall(Box::new(Debugger::new(&debug_printer)))
+            .syscall(metered(
+                meter,
+                self.build_load_cell_data(
+                    &script_group.input_indices,
+                    &script_group.output_indices,
+                ),
+            ))
+            .syscall(metered(meter, self.build_load_remaining_cycles()))
+            .syscall(metered(meter, debugger))
             .build();
         #[cfg(has_asm)]
         let mut machine = AsmMachine::new(default_machine, None);
@@ -406,6 +485,17 @@ impl<'a, DL: DataLoader> TransactionScriptsVerifier<'a, DL> {
     }
 }
 
+fn metered<'s, Mac, S>(meter: &Option<CycleMeter>, syscall: S) -> Box<dyn Syscalls<Mac> + 's>
+where
+    Mac: SupportMachine,
+    S: Syscalls<Mac> + 's,
+{
+    match meter {
+        Some(meter) => Box::new(Metered::new(syscall, meter.clone())),
+        None => Box::new(syscall),
+    }
+}
+
 fn internal_error(error: ckb_vm::Error) -> Error {
     InternalErrorKind::VM.reason(format!("{:?}", error)).into()
 }
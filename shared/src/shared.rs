@@ -7,7 +7,7 @@ use ckb_error::{Error, InternalErrorKind};
 use ckb_logger::info_target;
 use ckb_proposal_table::{ProposalTable, ProposalView};
 use ckb_store::ChainDB;
-use ckb_store::{ChainStore, StoreConfig, COLUMNS};
+use ckb_store::{CellSetMode, ChainStore, StoreConfig, COLUMNS};
 use ckb_tx_pool::{
     BlockAssemblerConfig, PollLock, TxPoolConfig, TxPoolController, TxPoolServiceBuilder,
 };
@@ -82,9 +82,18 @@ impl Shared {
         Ok((shared, proposal_table))
     }
 
+    /// Load the in-memory cell set, None in `CellSetMode::Store` since the cell set is read
+    /// from the store directly then
     pub(crate) fn init_cell_set(
         store: &ChainDB,
-    ) -> Result<HamtMap<Byte32, TransactionMeta>, Error> {
+    ) -> Result<Option<HamtMap<Byte32, TransactionMeta>>, Error> {
+        if store.cell_set_mode() == CellSetMode::Store {
+            info_target!(
+                crate::LOG_TARGET_CHAIN,
+                "the cell set is read from the store, skip loading live cells"
+            );
+            return Ok(None);
+        }
         let mut cell_set = HamtMap::new();
         let mut count = 0;
         info_target!(crate::LOG_TARGET_CHAIN, "Start: loading live cells ...");
@@ -110,7 +119,7 @@ impl Shared {
             count
         );
 
-        Ok(cell_set)
+        Ok(Some(cell_set))
     }
 
     pub(crate) fn init_proposal_table(
@@ -145,6 +154,7 @@ impl Shared {
         store: &ChainDB,
         consensus: &Consensus,
     ) -> Result<(HeaderView, EpochExt), Error> {
+        store.check_genesis()?;
         match store
             .get_tip_header()
             .and_then(|header| store.get_current_epoch_ext().map(|epoch| (header, epoch)))
@@ -197,7 +207,7 @@ impl Shared {
         tip_header: HeaderView,
         total_difficulty: U256,
         epoch_ext: EpochExt,
-        cell_set: HamtMap<Byte32, TransactionMeta>,
+        cell_set: Option<HamtMap<Byte32, TransactionMeta>>,
         proposals: ProposalView,
     ) -> Arc<Snapshot> {
         Arc::new(Snapshot::new(
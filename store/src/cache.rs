@@ -1,11 +1,12 @@
 use crate::config::StoreConfig;
 use ckb_types::{
     bytes::Bytes,
-    core::{HeaderView, TransactionView, UncleBlockVecView},
+    core::{HeaderView, TransactionMeta, TransactionView, UncleBlockVecView},
     packed::{Byte32, ProposalShortIdVec},
 };
 use ckb_util::Mutex;
 use lru_cache::LruCache;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 pub struct StoreCache {
     pub headers: Mutex<LruCache<Byte32, HeaderView>>,
@@ -14,6 +15,7 @@ pub struct StoreCache {
     pub block_tx_hashes: Mutex<LruCache<Byte32, Vec<Byte32>>>,
     pub block_uncles: Mutex<LruCache<Byte32, UncleBlockVecView>>,
     pub cellbase: Mutex<LruCache<Byte32, TransactionView>>,
+    pub tx_meta: Option<TxMetaCache>,
 }
 
 impl Default for StoreCache {
@@ -31,6 +33,126 @@ impl StoreCache {
             block_tx_hashes: Mutex::new(LruCache::new(config.block_tx_hashes_cache_size)),
             block_uncles: Mutex::new(LruCache::new(config.block_uncles_cache_size)),
             cellbase: Mutex::new(LruCache::new(config.cellbase_cache_size)),
+            tx_meta: config.tx_meta_cache_size.map(TxMetaCache::new),
         }
     }
 }
+
+/// An LRU of the committed cell set entries, shared by the store, its snapshots and its
+/// transactions
+///
+/// Unlike the other caches the entries are mutable, a committed transaction invalidates
+/// the entries it wrote. Every commit bumps the generation, a miss is only filled if no
+/// commit finished since the read started and none is in progress, otherwise a read racing
+/// with a commit could cache a stale entry. The lock is not held across the commit to the
+/// database, the entries a commit writes are removed before it starts and after it ends, so
+/// while it is in progress the cache only holds the entries it doesn't change. A snapshot is
+/// taken under the lock, so a snapshot pinned at the current generation sees the same
+/// entries as the cache.
+pub struct TxMetaCache {
+    inner: Mutex<TxMetaCacheInner>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct TxMetaCacheInner {
+    entries: LruCache<Byte32, TransactionMeta>,
+    generation: u64,
+    // the number of the commits in progress
+    committing: usize,
+}
+
+impl TxMetaCacheInner {
+    fn remove<'i, I>(&mut self, tx_hashes: I)
+    where
+        I: IntoIterator<Item = &'i Byte32>,
+    {
+        for tx_hash in tx_hashes {
+            self.entries.remove(tx_hash);
+        }
+    }
+}
+
+impl TxMetaCache {
+    pub fn new(capacity: usize) -> Self {
+        TxMetaCache {
+            inner: Mutex::new(TxMetaCacheInner {
+                entries: LruCache::new(capacity),
+                generation: 0,
+                committing: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Return the cached entry, or the current generation to pass to `fill` on a miss
+    ///
+    /// A store reading at a pinned generation, i.e. a snapshot, can't use the cache once
+    /// the generation moved on, neither the entry nor a generation is returned then.
+    pub(crate) fn get(
+        &self,
+        tx_hash: &Byte32,
+        pinned: Option<u64>,
+    ) -> Result<TransactionMeta, Option<u64>> {
+        let mut inner = self.inner.lock();
+        let generation = inner.generation;
+        if pinned.map_or(false, |pinned| pinned != generation) {
+            return Err(None);
+        }
+        match inner.entries.get_refresh(tx_hash) {
+            Some(meta) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Ok(meta.clone())
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Err(Some(generation))
+            }
+        }
+    }
+
+    /// Run `take_snapshot` under the lock, return the generation the snapshot is pinned at
+    pub(crate) fn pin<F, R>(&self, take_snapshot: F) -> (u64, R)
+    where
+        F: FnOnce() -> R,
+    {
+        let inner = self.inner.lock();
+        (inner.generation, take_snapshot())
+    }
+
+    /// Run `commit` without the lock and invalidate the entries it wrote, whether it
+    /// succeeds or not
+    pub(crate) fn commit<'i, I, F, R>(&self, tx_hashes: I, commit: F) -> R
+    where
+        I: IntoIterator<Item = &'i Byte32> + Copy,
+        F: FnOnce() -> R,
+    {
+        {
+            let mut inner = self.inner.lock();
+            inner.committing += 1;
+            inner.remove(tx_hashes);
+        }
+        let ret = commit();
+        let mut inner = self.inner.lock();
+        inner.committing -= 1;
+        inner.generation += 1;
+        inner.remove(tx_hashes);
+        ret
+    }
+
+    pub(crate) fn fill(&self, generation: u64, tx_hash: Byte32, meta: TransactionMeta) {
+        let mut inner = self.inner.lock();
+        if inner.generation == generation && inner.committing == 0 {
+            inner.entries.insert(tx_hash, meta);
+        }
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
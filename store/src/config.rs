@@ -1,3 +1,4 @@
+use ckb_db::RetryPolicy;
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Hash, Debug)]
@@ -8,6 +9,42 @@ pub struct StoreConfig {
     pub block_tx_hashes_cache_size: usize,
     pub block_uncles_cache_size: usize,
     pub cellbase_cache_size: usize,
+    /// The cache of the cell set entries is disabled if it's not set
+    #[serde(default)]
+    pub tx_meta_cache_size: Option<usize>,
+    /// Index the input spending each cell, see `ChainStore::get_cell_spender`
+    #[serde(default)]
+    pub cell_spender_index: bool,
+    /// Refuse to insert a block whose serialized transactions exceed the bytes, no limit
+    /// if it's not set
+    #[serde(default)]
+    pub max_block_body_bytes: Option<usize>,
+    /// Record the blocks whose transactions lose their cell set entries, so
+    /// `ChainDB::prune_bodies` examines the bodies it kept again once they may be pruned
+    #[serde(default)]
+    pub track_prunable_bodies: bool,
+    /// Where the live cell set is read from, see `CellSetMode`
+    #[serde(default)]
+    pub cell_set_mode: CellSetMode,
+    /// How the reads through a transaction snapshot retry on the transient rocksdb errors
+    #[serde(default)]
+    pub read_retry: RetryPolicy,
+}
+
+/// How the chain keeps the live cell set
+#[derive(Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CellSetMode {
+    /// Load the whole `COLUMN_CELL_SET` into an in-memory HAMT at startup and read from it
+    Hamt,
+    /// Read `COLUMN_CELL_SET` directly, trading a read per lookup for the memory of the HAMT
+    Store,
+}
+
+impl Default for CellSetMode {
+    fn default() -> Self {
+        CellSetMode::Hamt
+    }
 }
 
 impl Default for StoreConfig {
@@ -19,6 +56,12 @@ impl Default for StoreConfig {
             block_tx_hashes_cache_size: 30,
             block_uncles_cache_size: 30,
             cellbase_cache_size: 30,
+            tx_meta_cache_size: None,
+            cell_spender_index: false,
+            max_block_body_bytes: None,
+            track_prunable_bodies: false,
+            cell_set_mode: CellSetMode::Hamt,
+            read_retry: RetryPolicy::default(),
         }
     }
 }
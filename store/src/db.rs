@@ -1,25 +1,49 @@
-use crate::cache::StoreCache;
-use crate::config::StoreConfig;
+use crate::cache::{StoreCache, TxMetaCache};
+use crate::config::{CellSetMode, StoreConfig};
+use crate::metrics::{Counters, StoreMetrics};
+use crate::observer::Observers;
 use crate::store::ChainStore;
 use crate::transaction::StoreTransaction;
-use crate::StoreSnapshot;
-use crate::COLUMN_CELL_SET;
+use crate::{check_column, CommittedChanges, Observer, ObserverId, StoreSnapshot};
+use crate::{
+    COLUMN_BLOCK_BODY, COLUMN_BLOCK_BODY_CHECKSUM, COLUMN_BLOCK_EXT, COLUMN_BLOCK_HEADER,
+    COLUMN_CELL_SET, COLUMN_INDEX, COLUMN_META, COLUMN_PRUNE_CANDIDATE, COLUMN_QUARANTINE,
+    COLUMN_TRANSACTION_INFO, META_PRUNE_CURSOR_KEY,
+};
 use ckb_chain_spec::consensus::Consensus;
 use ckb_db::{
     iter::{DBIterator, DBIteratorItem},
-    Col, DBPinnableSlice, Direction, RocksDB,
+    Col, DBPinnableSlice, Direction, RetryPolicy, RocksDB,
 };
-use ckb_error::Error;
+use ckb_error::{Error, InternalErrorKind};
+use ckb_hash::new_blake2b;
 use ckb_types::{
-    core::{BlockExt, TransactionMeta},
+    core::{BlockExt, BlockNumber, BlockView, EpochExt, HeaderView, TransactionMeta},
     packed,
     prelude::*,
+    utilities::merkle_root,
 };
+use ckb_util::Mutex;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The count of blocks `ChainDB::prune_bodies` examines in one transaction
+const PRUNE_CHUNK_BLOCKS: usize = 1000;
 
 pub struct ChainDB {
     db: RocksDB,
     cache: Arc<StoreCache>,
+    cell_spender_index: bool,
+    max_block_body_bytes: Option<usize>,
+    track_prunable_bodies: bool,
+    cell_set_mode: CellSetMode,
+    read_retry: RetryPolicy,
+    corruption_recovery: bool,
+    // the malformed values met by the checked reads, by their keys in `COLUMN_QUARANTINE`
+    quarantined: Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>,
+    observers: Arc<Observers>,
+    metrics: Arc<Counters>,
 }
 
 impl<'a> ChainStore<'a> for ChainDB {
@@ -29,7 +53,16 @@ impl<'a> ChainStore<'a> for ChainDB {
         Some(&self.cache)
     }
 
+    fn tx_meta_cache(
+        &'a self,
+        _tx_hash: &packed::Byte32,
+    ) -> Option<(&'a TxMetaCache, Option<u64>)> {
+        self.cache.tx_meta.as_ref().map(|cache| (cache, None))
+    }
+
     fn get(&'a self, col: Col, key: &[u8]) -> Option<Self::Vector> {
+        debug_assert!(check_column(col).is_ok(), "unknown column {:?}", col);
+        self.metrics.record_get();
         self.db
             .get_pinned(col, key)
             .expect("db operation should be ok")
@@ -53,7 +86,103 @@ impl ChainDB {
         ChainDB {
             db,
             cache: Arc::new(cache),
+            cell_spender_index: config.cell_spender_index,
+            max_block_body_bytes: config.max_block_body_bytes,
+            track_prunable_bodies: config.track_prunable_bodies,
+            cell_set_mode: config.cell_set_mode,
+            read_retry: config.read_retry,
+            corruption_recovery: false,
+            quarantined: Default::default(),
+            observers: Default::default(),
+            metrics: Default::default(),
+        }
+    }
+
+    /// The counts of the reads and writes issued by the store, its snapshots and its
+    /// transactions since it's created, always zeros without the `metrics` feature
+    pub fn metrics(&self) -> StoreMetrics {
+        self.metrics.load()
+    }
+
+    /// Quarantine the malformed values met by the checked reads, `get_verified` and the
+    /// `try_*` getters, so they can be inspected while the node keeps running
+    ///
+    /// The checked reads return a `DataCorrupted` error for a malformed value in either
+    /// mode. With the recovery enabled the value is also recorded under the column id, a
+    /// `/` and the key, see `get_quarantined`, and written into `COLUMN_QUARANTINE` by the
+    /// next commit of a store transaction, a read never commits by itself. The `ChainStore`
+    /// getters are not checked, they behave the same in both modes. It's disabled by default.
+    pub fn enable_corruption_recovery(self, enabled: bool) -> Self {
+        ChainDB {
+            corruption_recovery: enabled,
+            ..self
+        }
+    }
+
+    pub fn cell_set_mode(&self) -> CellSetMode {
+        self.cell_set_mode
+    }
+
+    /// Read a raw value and check it with `verify`, a malformed value is a `DataCorrupted`
+    /// error and it's quarantined as described in `enable_corruption_recovery`
+    pub fn get_verified<F>(
+        &self,
+        col: Col,
+        key: &[u8],
+        verify: F,
+    ) -> Result<Option<DBPinnableSlice>, Error>
+    where
+        F: Fn(&[u8]) -> bool,
+    {
+        check_column(col)?;
+        self.metrics.record_get();
+        let value = match self.db.get_pinned(col, key)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        if verify(value.as_ref()) {
+            return Ok(Some(value));
+        }
+        if self.corruption_recovery {
+            self.quarantined
+                .lock()
+                .insert(quarantine_key(col, key), value.as_ref().to_vec());
         }
+        Err(InternalErrorKind::DataCorrupted.reason(format!(
+            "malformed value of key {:?} in column {}",
+            key, col
+        )))?
+    }
+
+    /// Get the quarantined copy of a malformed value, whether it's written into
+    /// `COLUMN_QUARANTINE` yet or not
+    pub fn get_quarantined(&self, col: Col, key: &[u8]) -> Option<Vec<u8>> {
+        let key = quarantine_key(col, key);
+        if let Some(value) = self.quarantined.lock().get(&key) {
+            return Some(value.clone());
+        }
+        self.get(COLUMN_QUARANTINE, &key)
+            .map(|value| value.as_ref().to_vec())
+    }
+
+    /// Checked `get_block_header`, the cache is not used
+    pub fn try_get_block_header(&self, hash: &packed::Byte32) -> Result<Option<HeaderView>, Error> {
+        let value = self.get_verified(COLUMN_BLOCK_HEADER, hash.as_slice(), |value| {
+            packed::HeaderViewReader::verify(value, false).is_ok()
+        })?;
+        Ok(value.map(|value| {
+            let reader = packed::HeaderViewReader::from_slice_should_be_ok(value.as_ref());
+            Unpack::<HeaderView>::unpack(&reader)
+        }))
+    }
+
+    /// Checked `get_block_ext`
+    pub fn try_get_block_ext(&self, hash: &packed::Byte32) -> Result<Option<BlockExt>, Error> {
+        let value = self.get_verified(COLUMN_BLOCK_EXT, hash.as_slice(), |value| {
+            packed::BlockExtReader::verify(value, false).is_ok()
+        })?;
+        Ok(value
+            .map(|value| packed::BlockExtReader::from_slice_should_be_ok(value.as_ref()).unpack()))
     }
 
     pub fn traverse_cell_set<F>(&self, mut callback: F) -> Result<(), Error>
@@ -69,23 +198,356 @@ impl ChainDB {
             })
     }
 
+    /// Compute a commitment over the stored cell set, nodes with the same cell set get the
+    /// same root
+    ///
+    /// Each entry is hashed into a leaf as `blake2b_256(tx_hash || meta)`, where `meta` is
+    /// the molecule serialized `TransactionMeta` and `blake2b_256` is the one of `ckb-hash`
+    /// with the personalization `ckb-default-hash`. The leaves are ordered by the tx hash as
+    /// unsigned bytes, which is the order of the column, and the root is the CBMT root of
+    /// them, the same tree as the transactions root. An empty cell set gives the zero hash.
+    pub fn cell_set_root(&self) -> Result<packed::Byte32, Error> {
+        let mut leaves = Vec::new();
+        self.traverse_cell_set(|tx_hash, tx_meta| {
+            let mut leaf = [0u8; 32];
+            let mut blake2b = new_blake2b();
+            blake2b.update(tx_hash.as_slice());
+            blake2b.update(tx_meta.as_slice());
+            blake2b.finalize(&mut leaf);
+            leaves.push(leaf.pack());
+            Ok(())
+        })?;
+        Ok(merkle_root(&leaves))
+    }
+
+    /// Repair the cell set entries with live outputs whose cell metas can't be resolved,
+    /// which `CellProvider::cell` of the snapshot panics on, return the count of repaired
+    /// entries
+    ///
+    /// A cell meta is decoded from the block body through the transaction info. If the
+    /// transaction is still in the body of the block recorded in the entry, its transaction
+    /// info is written again. Otherwise the entry is copied into `COLUMN_QUARANTINE` under
+    /// the key of `COLUMN_CELL_SET`, see `get_quarantined`, and removed from the cell set.
+    pub fn repair_cell_set(&self) -> Result<u64, Error> {
+        let mut dangling = Vec::new();
+        self.traverse_cell_set(|tx_hash, tx_meta| {
+            let meta: TransactionMeta = tx_meta.unpack();
+            let has_live = (0..meta.len()).any(|index| meta.is_dead(index) == Some(false));
+            let resolvable = match self.get_transaction(&tx_hash) {
+                Some((tx, _)) => tx.outputs().len() >= meta.len(),
+                None => false,
+            };
+            if has_live && !resolvable {
+                dangling.push((tx_hash, tx_meta));
+            }
+            Ok(())
+        })?;
+
+        let txn = self.begin_transaction();
+        for (tx_hash, tx_meta) in &dangling {
+            let meta: TransactionMeta = tx_meta.unpack();
+            let block_hash = meta.block_hash();
+            let found = self.get_block_header(&block_hash).and_then(|header| {
+                self.get_block_body(&block_hash)
+                    .iter()
+                    .position(|tx| &tx.hash() == tx_hash && tx.outputs().len() >= meta.len())
+                    .map(|index| (header, index))
+            });
+            match found {
+                Some((header, index)) => {
+                    let key = packed::TransactionKey::new_builder()
+                        .block_hash(block_hash)
+                        .index(index.pack())
+                        .build();
+                    let info = packed::TransactionInfo::new_builder()
+                        .key(key)
+                        .block_number(header.number().pack())
+                        .block_epoch(header.epoch().pack())
+                        .build();
+                    txn.insert_raw(COLUMN_TRANSACTION_INFO, tx_hash.as_slice(), info.as_slice())?;
+                }
+                None => {
+                    txn.insert_raw(
+                        COLUMN_QUARANTINE,
+                        &quarantine_key(COLUMN_CELL_SET, tx_hash.as_slice()),
+                        tx_meta.as_slice(),
+                    )?;
+                    txn.delete_cell_set(tx_hash)?;
+                }
+            }
+        }
+        txn.commit()?;
+        Ok(dangling.len() as u64)
+    }
+
     pub fn begin_transaction(&self) -> StoreTransaction {
         StoreTransaction {
             inner: self.db.transaction(),
             cache: Arc::clone(&self.cache),
+            cell_spender_index: self.cell_spender_index,
+            max_block_body_bytes: self.max_block_body_bytes,
+            track_prunable_bodies: self.track_prunable_bodies,
+            read_retry: self.read_retry,
+            quarantined: Arc::clone(&self.quarantined),
+            written_tx_metas: Mutex::new(HashSet::new()),
+            observers: Arc::clone(&self.observers),
+            metrics: Arc::clone(&self.metrics),
+            changes: if self.observers.is_empty() {
+                None
+            } else {
+                Some(Mutex::new(CommittedChanges::default()))
+            },
         }
     }
 
+    /// Register a callback invoked after every commit which attached or detached blocks or
+    /// changed the cell set, so subscribers never see uncommitted state
+    ///
+    /// The transactions begun before the subscription are not observed.
+    pub fn subscribe(&self, observer: Observer) -> ObserverId {
+        self.observers.subscribe(observer)
+    }
+
+    /// Remove a callback, return false if it's not registered
+    pub fn unsubscribe(&self, id: ObserverId) -> bool {
+        self.observers.unsubscribe(id)
+    }
+
+    /// Flush all columns into SST files, e.g. as a durability checkpoint between import
+    /// batches, block until the flushes are done if `wait` is true
+    pub fn flush(&self, wait: bool) -> Result<(), Error> {
+        self.db.flush(wait)
+    }
+
+    /// Get a RocksDB property of a column for monitoring, e.g.
+    /// "rocksdb.estimate-pending-compaction-bytes" to spot the risk of write stalls,
+    /// return `None` if the column or the property is unknown
+    pub fn column_property(&self, col: Col, name: &str) -> Option<String> {
+        self.db
+            .property_value(col, name)
+            .ok()
+            .and_then(|value| value)
+    }
+
     pub fn get_snapshot(&self) -> StoreSnapshot {
-        StoreSnapshot {
-            inner: self.db.get_snapshot(),
-            cache: Arc::clone(&self.cache),
+        StoreSnapshot::new(Arc::clone(&self.cache), Arc::clone(&self.metrics), || {
+            self.db.get_snapshot()
+        })
+    }
+
+    /// Get block by block header hash, every part of the block is read from one snapshot
+    ///
+    /// Return None like `get_block` if the body is pruned, and `DataCorrupted` error if the
+    /// header is stored but another part is missing, so that a concurrent detaching can
+    /// never produce a torn block.
+    pub fn get_consistent_block(&self, hash: &packed::Byte32) -> Result<Option<BlockView>, Error> {
+        read_consistent_block(&self.get_snapshot(), hash)
+    }
+
+    /// Get block and its ext by block header hash, both are read from one snapshot
+    ///
+    /// Return `DataCorrupted` error if the block is stored but a part of it or its ext is
+    /// missing
+    pub fn get_block_and_ext(
+        &self,
+        hash: &packed::Byte32,
+    ) -> Result<Option<(BlockView, BlockExt)>, Error> {
+        let snapshot = self.get_snapshot();
+        let block = match read_consistent_block(&snapshot, hash)? {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+        let ext = snapshot
+            .get_block_ext(hash)
+            .ok_or_else(|| missing_block_part(hash, "ext"))?;
+        Ok(Some((block, ext)))
+    }
+
+    /// Get the ext of the tip block, the tip and its ext are read from one snapshot
+    ///
+    /// Return None only if there is no tip, the ext of a stored tip is always present
+    pub fn get_tip_block_ext(&self) -> Option<BlockExt> {
+        let snapshot = self.get_snapshot();
+        let tip = snapshot.get_tip_header()?;
+        let ext = snapshot
+            .get_block_ext(&tip.hash())
+            .expect("the ext of the tip should be stored");
+        Some(ext)
+    }
+
+    /// Rebuild the tip from the highest block number in the index, return its header and ext
+    ///
+    /// This is an offline recovery tool and should only be run when the tip header key is
+    /// known to be lost or corrupted, the result is not written back.
+    pub fn recover_tip(&self) -> Option<(HeaderView, BlockExt)> {
+        let number = self.indexed_numbers().max()?;
+        let hash = self.get_block_hash(number)?;
+        let header = self.get_block_header(&hash)?;
+        let ext = self.get_block_ext(&hash)?;
+        Some((header, ext))
+    }
+
+    /// Delete the bodies of the main chain blocks below the finalized one, keeping their
+    /// headers and indexes, return the count of pruned bodies
+    ///
+    /// A body is kept while any of its transactions has a live output, since the cell data
+    /// and the cell deps are read from it. The checksum of a pruned body is replaced by an
+    /// empty marker, `get_block` returns None for it and `verify_block_body` an error. This
+    /// assumes the block spending the last output of a pruned transaction is never
+    /// detached, otherwise the cell can't be revived, so the finalized block should be deep
+    /// enough.
+    ///
+    /// The blocks are examined once, in chunks of `PRUNE_CHUNK_BLOCKS` each committed with
+    /// the prune progress, so the next call starts from the blocks finalized since then. A
+    /// kept body is only examined again once the cell set entry of one of its transactions
+    /// is deleted, which is recorded if `StoreConfig::track_prunable_bodies` is enabled,
+    /// otherwise it's kept for good.
+    pub fn prune_bodies(&self) -> Result<u64, Error> {
+        let finalized = match self.get_finalized_header() {
+            Some(finalized) => finalized.number(),
+            None => return Ok(0),
+        };
+        let mut pruned = 0;
+        let mut cursor = self.get_prune_cursor();
+        while cursor < finalized {
+            let end = finalized.min(cursor + PRUNE_CHUNK_BLOCKS as BlockNumber);
+            let txn = self.begin_transaction();
+            for number in cursor..end {
+                if prune_body(&txn, number)? {
+                    pruned += 1;
+                }
+            }
+            let next_cursor: packed::Uint64 = end.pack();
+            txn.insert_raw(COLUMN_META, META_PRUNE_CURSOR_KEY, next_cursor.as_slice())?;
+            txn.commit()?;
+            cursor = end;
+        }
+
+        // the candidates above the cursor are examined once the cursor passes them
+        loop {
+            let candidates: Vec<_> = self
+                .get_iter(COLUMN_PRUNE_CANDIDATE, &[], Direction::Forward)
+                .map(|(key, _)| key)
+                .take_while(|key| prune_candidate_number(key) < cursor)
+                .take(PRUNE_CHUNK_BLOCKS)
+                .collect();
+            if candidates.is_empty() {
+                break;
+            }
+            let txn = self.begin_transaction();
+            let mut examined = None;
+            for key in &candidates {
+                let number = prune_candidate_number(key);
+                // the candidates of a block are adjacent
+                if examined != Some(number) && prune_body(&txn, number)? {
+                    pruned += 1;
+                }
+                examined = Some(number);
+                txn.delete(COLUMN_PRUNE_CANDIDATE, key)?;
+            }
+            txn.commit()?;
+        }
+        Ok(pruned)
+    }
+
+    // The lowest block number never examined by `prune_bodies`
+    fn get_prune_cursor(&self) -> BlockNumber {
+        self.get(COLUMN_META, META_PRUNE_CURSOR_KEY)
+            .map(|raw| packed::Uint64Reader::from_slice_should_be_ok(&raw.as_ref()).unpack())
+            .unwrap_or(0)
+    }
+
+    pub fn check_genesis(&self) -> Result<(), Error> {
+        let has_headers = self
+            .get_iter(COLUMN_BLOCK_HEADER, &[], Direction::Forward)
+            .next()
+            .is_some();
+        if has_headers && !self.has_genesis() {
+            Err(InternalErrorKind::DataCorrupted.reason("genesis missing"))?;
         }
+        Ok(())
+    }
+
+    /// Get the lowest block number still in the index, None if the store is empty
+    ///
+    /// The value advances when old blocks are pruned
+    pub fn get_lowest_stored_number(&self) -> Option<BlockNumber> {
+        self.indexed_numbers().min()
+    }
+
+    // The number keys are little endian, so they are not sorted by number and the
+    // whole index has to be scanned
+    fn indexed_numbers(&self) -> impl Iterator<Item = BlockNumber> + '_ {
+        self.get_iter(COLUMN_INDEX, &[], Direction::Forward)
+            .filter(|(key, _)| key.len() == packed::Uint64::TOTAL_SIZE)
+            .map(|(key, _)| packed::Uint64Reader::from_slice_should_be_ok(&key).unpack())
+    }
+
+    /// Read the headers, the bodies and the spent cells of the most recent main chain
+    /// blocks, so the block cache of RocksDB is warm for the first verification after a
+    /// restart
+    ///
+    /// The blocks are read from the tip backwards, it stops early once `max_bytes` are read
+    /// or `timeout` has elapsed.
+    pub fn warm_up(
+        &self,
+        recent_blocks: usize,
+        max_bytes: usize,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        let started = Instant::now();
+        let tip = match self.get_tip_header() {
+            Some(tip) => tip,
+            None => Err(InternalErrorKind::Database.reason("the store is not initialized"))?,
+        };
+        let mut read = 0;
+        for number in (0..=tip.number()).rev().take(recent_blocks) {
+            if read >= max_bytes || started.elapsed() >= timeout {
+                break;
+            }
+            let hash = match self.get_block_hash(number) {
+                Some(hash) => hash,
+                None => break,
+            };
+            if let Some(header) = self.get(COLUMN_BLOCK_HEADER, hash.as_slice()) {
+                read += header.as_ref().len();
+            }
+            let prefix = hash.as_slice();
+            for (_key, value) in self
+                .get_iter(COLUMN_BLOCK_BODY, prefix, Direction::Forward)
+                .take_while(|(key, _)| key.starts_with(prefix))
+            {
+                read += value.len();
+                let reader = packed::TransactionViewReader::from_slice_should_be_ok(&value);
+                for input in reader.data().raw().inputs().iter() {
+                    let out_point = input.previous_output();
+                    if let Some(cell) = self
+                        .get_cell_meta(&out_point.tx_hash().to_entity(), out_point.index().unpack())
+                    {
+                        read += cell.cell_output.as_slice().len();
+                    }
+                }
+            }
+        }
+        Ok(())
     }
 
     pub fn init(&self, consensus: &Consensus) -> Result<(), Error> {
-        let genesis = consensus.genesis_block();
-        let epoch = consensus.genesis_epoch_ext();
+        self.init_genesis(consensus.genesis_block(), consensus.genesis_epoch_ext())
+    }
+
+    /// Write the genesis block, its ext, cell set, epoch and indexes, and make it the tip
+    /// and the finalized block, all in one transaction
+    ///
+    /// Return `DataCorrupted` error if the store already has a tip. The database version
+    /// is not written here, it's recorded when the database is opened.
+    pub fn init_genesis(&self, genesis: &BlockView, epoch: &EpochExt) -> Result<(), Error> {
+        if let Some(tip) = self.get_tip_header() {
+            Err(InternalErrorKind::DataCorrupted.reason(format!(
+                "failed to init genesis: the store already has tip {}",
+                tip.hash()
+            )))?;
+        }
         let db_txn = self.begin_transaction();
         let genesis_hash = genesis.hash();
         let ext = BlockExt {
@@ -126,22 +588,107 @@ impl ChainDB {
 
         db_txn.insert_block(genesis)?;
         db_txn.insert_block_ext(&genesis_hash, &ext)?;
-        db_txn.insert_tip_header(&genesis.header())?;
-        db_txn.insert_current_epoch_ext(epoch)?;
+        db_txn.advance_tip(&genesis.header(), Some(epoch))?;
         db_txn.insert_block_epoch_index(&genesis_hash, &last_block_hash_in_previous_epoch)?;
         db_txn.insert_epoch_ext(&last_block_hash_in_previous_epoch, &epoch)?;
         db_txn.attach_block(genesis)?;
+        db_txn.set_finalized(&genesis.header())?;
         db_txn.commit()?;
         Ok(())
     }
 }
 
+// Prune the body of the main chain block at the number unless it's already pruned or any
+// of its transactions has a live output, return whether it's pruned
+fn prune_body(txn: &StoreTransaction, number: BlockNumber) -> Result<bool, Error> {
+    let hash = match txn.get_block_hash(number) {
+        Some(hash) => hash,
+        None => return Ok(false),
+    };
+    let prefix = hash.as_slice();
+    let body: Vec<_> = txn
+        .get_iter(COLUMN_BLOCK_BODY, prefix, Direction::Forward)
+        .take_while(|(key, _)| key.starts_with(prefix))
+        .map(|(key, value)| {
+            let tx_hash = packed::TransactionViewReader::from_slice_should_be_ok(&value)
+                .hash()
+                .to_entity();
+            (key, tx_hash)
+        })
+        .collect();
+    let live = body.iter().any(|(_, tx_hash)| {
+        txn.get_tx_meta(tx_hash)
+            .map(|meta| !meta.all_dead())
+            .unwrap_or(false)
+    });
+    if body.is_empty() || live {
+        return Ok(false);
+    }
+    for (key, _) in &body {
+        txn.delete(COLUMN_BLOCK_BODY, key)?;
+    }
+    txn.insert_raw(COLUMN_BLOCK_BODY_CHECKSUM, prefix, &[])?;
+    txn.cache.block_tx_hashes.lock().remove(&hash);
+    txn.cache.cellbase.lock().remove(&hash);
+    Ok(true)
+}
+
+fn prune_candidate_number(key: &[u8]) -> BlockNumber {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&key[..8]);
+    BlockNumber::from_be_bytes(buf)
+}
+
+fn read_consistent_block(
+    snapshot: &StoreSnapshot,
+    hash: &packed::Byte32,
+) -> Result<Option<BlockView>, Error> {
+    let header = match snapshot.get_block_header(hash) {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let uncles = snapshot
+        .get_block_uncles(hash)
+        .ok_or_else(|| missing_block_part(hash, "uncles"))?;
+    let proposals = snapshot
+        .get_block_proposal_txs_ids(hash)
+        .ok_or_else(|| missing_block_part(hash, "proposals"))?;
+    let body = snapshot.get_block_body(hash);
+    if body.is_empty() && snapshot.is_block_pruned(hash) {
+        return Ok(None);
+    }
+    Ok(Some(BlockView::new_unchecked(
+        header, uncles, body, proposals,
+    )))
+}
+
+fn missing_block_part(hash: &packed::Byte32, part: &str) -> Error {
+    InternalErrorKind::DataCorrupted
+        .reason(format!("block {} is stored without {}", hash, part))
+        .into()
+}
+
+fn quarantine_key(col: Col, key: &[u8]) -> Vec<u8> {
+    let mut quarantine_key = Vec::with_capacity(col.len() + 1 + key.len());
+    quarantine_key.extend_from_slice(col.as_bytes());
+    quarantine_key.push(b'/');
+    quarantine_key.extend_from_slice(key);
+    quarantine_key
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::COLUMNS;
     use super::*;
+    use crate::{
+        prune_candidate_key, CellSetChange, LinkError, StoreTransactionSnapshot, COLUMN_BLOCK_BODY,
+        COLUMN_BLOCK_TOTAL_DIFFICULTY, COLUMN_META, COLUMN_TRANSACTION_INFO,
+    };
     use ckb_chain_spec::consensus::ConsensusBuilder;
     use ckb_db::RocksDB;
+    use ckb_error::{assert_error_eq, InternalError};
+    use ckb_hash::blake2b_256;
+    use ckb_types::core::{BlockBuilder, Capacity, TransactionBuilder};
 
     fn setup_db(columns: u32) -> RocksDB {
         RocksDB::open_tmp(columns)
@@ -162,65 +709,1822 @@ mod tests {
     }
 
     #[test]
-    fn save_and_get_block_with_transactions() {
+    fn get_transaction_infos() {
         let db = setup_db(COLUMNS);
         let store = ChainDB::new(db, Default::default());
-        let block = packed::Block::new_builder()
-            .transactions(
-                (0..3)
-                    .map(|_| packed::Transaction::new_builder().build())
-                    .collect::<Vec<_>>()
-                    .pack(),
-            )
-            .build()
-            .into_view();
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+
+        let mut hashes = genesis.tx_hashes().to_vec();
+        hashes.insert(1, packed::Byte32::zero());
+        let infos = store.get_transaction_infos(&hashes);
+        assert_eq!(infos.len(), hashes.len());
+        assert!(infos[1].is_none());
+        for (hash, info) in hashes
+            .iter()
+            .zip(infos.iter())
+            .filter(|(_, info)| info.is_some())
+        {
+            assert_eq!(info, &store.get_transaction_info(hash));
+        }
+    }
+
+    #[test]
+    fn attach_and_detach_uncles() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        let uncle = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .build();
+        let block = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .timestamp(1u64.pack())
+            .uncle(uncle.as_uncle())
+            .build();
 
-        let hash = block.hash();
         let txn = store.begin_transaction();
         txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
         txn.commit().unwrap();
-        assert_eq!(block, store.get_block(&hash).unwrap());
+        assert!(store.is_uncle(&uncle.hash()));
+        assert_eq!(store.uncles_iter().collect::<Vec<_>>(), vec![uncle.hash()]);
+
+        let txn = store.begin_transaction();
+        txn.detach_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert!(!store.is_uncle(&uncle.hash()));
+        assert_eq!(store.uncles_iter().count(), 0);
     }
 
     #[test]
-    fn save_and_get_block_ext() {
+    fn get_block_children() {
         let db = setup_db(COLUMNS);
         let store = ChainDB::new(db, Default::default());
         let consensus = ConsensusBuilder::default().build();
-        let block = consensus.genesis_block();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        let main = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .build();
+        let fork = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .timestamp(1u64.pack())
+            .build();
 
-        let ext = BlockExt {
-            received_at: block.timestamp(),
-            total_difficulty: block.difficulty(),
-            total_uncles_count: block.data().uncles().len() as u64,
-            verified: Some(true),
-            txs_fees: vec![],
-        };
+        let txn = store.begin_transaction();
+        txn.insert_block(&main).unwrap();
+        txn.insert_block(&fork).unwrap();
+        txn.commit().unwrap();
+        let mut expected = vec![main.hash(), fork.hash()];
+        expected.sort();
+        assert_eq!(store.get_block_children(&genesis.hash()), expected);
+        assert!(store.get_block_children(&main.hash()).is_empty());
 
-        let hash = block.hash();
         let txn = store.begin_transaction();
-        txn.insert_block_ext(&hash, &ext).unwrap();
+        txn.delete_block(&fork).unwrap();
         txn.commit().unwrap();
-        assert_eq!(ext, store.get_block_ext(&hash).unwrap());
+        assert_eq!(store.get_block_children(&genesis.hash()), vec![main.hash()]);
+        assert!(store.get_block_header(&fork.hash()).is_none());
     }
 
     #[test]
-    fn index_store() {
-        let db = RocksDB::open_tmp(COLUMNS);
+    fn detach_finalized_block() {
+        let db = setup_db(COLUMNS);
         let store = ChainDB::new(db, Default::default());
         let consensus = ConsensusBuilder::default().build();
-        let block = consensus.genesis_block();
-        let hash = block.hash();
         store.init(&consensus).unwrap();
-        assert_eq!(hash, store.get_block_hash(0).unwrap());
+        let genesis = consensus.genesis_block();
+        let block1 = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .build();
+        let block2 = BlockBuilder::default()
+            .parent_hash(block1.hash())
+            .number(2u64.pack())
+            .build();
 
+        let txn = store.begin_transaction();
+        for block in &[&block1, &block2] {
+            txn.insert_block(block).unwrap();
+            txn.attach_block(block).unwrap();
+        }
+        let orphan = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .timestamp(1u64.pack())
+            .build();
+        let err = txn.set_finalized(&orphan.header()).unwrap_err();
+        assert_error_eq(
+            err,
+            InternalErrorKind::System.reason(format!(
+                "failed to finalize block {}: the main chain block at 1 is {}",
+                orphan.hash(),
+                block1.hash()
+            )),
+        );
+        txn.set_finalized(&block1.header()).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(Some(block1.header()), store.get_finalized_header());
+
+        let txn = store.begin_transaction();
+        txn.detach_block(&block2).unwrap();
+        let err = txn.detach_block(&block1).unwrap_err();
         assert_eq!(
-            block.difficulty(),
-            store.get_block_ext(&hash).unwrap().total_difficulty
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::System)
         );
+    }
 
-        assert_eq!(block.number(), store.get_block_number(&hash).unwrap());
+    #[test]
+    fn get_block_hash_checked() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
 
-        assert_eq!(block.header(), store.get_tip_header().unwrap());
+        assert_eq!(
+            Some(consensus.genesis_block().hash()),
+            store.get_block_hash_checked(0).unwrap()
+        );
+        assert_eq!(None, store.get_block_hash_checked(1).unwrap());
+
+        let txn = store.begin_transaction();
+        txn.insert_raw(COLUMN_INDEX, 1u64.pack().as_slice(), &[0u8; 31])
+            .unwrap();
+        txn.commit().unwrap();
+        let err = store.get_block_hash_checked(1).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::DataCorrupted)
+        );
+    }
+
+    #[test]
+    fn get_block_number_checked() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+
+        assert_eq!(
+            Some(0),
+            store.get_block_number_checked(&genesis.hash()).unwrap()
+        );
+        assert_eq!(
+            None,
+            store
+                .get_block_number_checked(&packed::Byte32::zero())
+                .unwrap()
+        );
+
+        let txn = store.begin_transaction();
+        txn.insert_raw(COLUMN_INDEX, genesis.hash().as_slice(), &[0u8; 7])
+            .unwrap();
+        txn.commit().unwrap();
+        let err = store.get_block_number_checked(&genesis.hash()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::DataCorrupted)
+        );
+    }
+
+    #[test]
+    fn get_block_epoch_by_number() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+
+        assert_eq!(
+            Some(consensus.genesis_epoch_ext().to_owned()),
+            store.get_block_epoch_by_number(0)
+        );
+        assert_eq!(None, store.get_block_epoch_by_number(1));
+
+        let block = BlockBuilder::default()
+            .parent_hash(consensus.genesis_block().hash())
+            .number(1u64.pack())
+            .build();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(None, store.get_block_epoch_by_number(1));
+    }
+
+    #[test]
+    fn reorg_depth() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        let main = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .build();
+        let fork = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .timestamp(1u64.pack())
+            .build();
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&main).unwrap();
+        txn.attach_block(&main).unwrap();
+        txn.insert_block(&fork).unwrap();
+        txn.commit().unwrap();
+
+        assert!(store.is_main_chain(&main.hash()));
+        assert!(!store.is_main_chain(&fork.hash()));
+        assert!(!store.is_main_chain(&packed::Byte32::zero()));
+        assert_eq!(store.reorg_depth(&main.hash()), Some(0));
+        assert_eq!(store.reorg_depth(&fork.hash()), Some(1));
+        assert_eq!(store.reorg_depth(&packed::Byte32::zero()), None);
+    }
+
+    #[test]
+    fn check_chain_links() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        let block1 = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .build();
+        let orphan = BlockBuilder::default()
+            .parent_hash(packed::Byte32::zero())
+            .number(3u64.pack())
+            .build();
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block1).unwrap();
+        txn.attach_block(&block1).unwrap();
+        txn.insert_block(&orphan).unwrap();
+        txn.attach_block(&orphan).unwrap();
+        txn.commit().unwrap();
+
+        assert!(store.get_snapshot().check_chain_links(0, 1).is_empty());
+        assert_eq!(
+            store.get_snapshot().check_chain_links(0, 3),
+            vec![
+                LinkError::Gap { number: 2 },
+                LinkError::MissingParent {
+                    hash: orphan.hash(),
+                    parent: packed::Byte32::zero(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn get_common_ancestor() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        let block1 = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .build();
+        let main = BlockBuilder::default()
+            .parent_hash(block1.hash())
+            .number(2u64.pack())
+            .build();
+        let fork1 = BlockBuilder::default()
+            .parent_hash(block1.hash())
+            .number(2u64.pack())
+            .timestamp(1u64.pack())
+            .build();
+        let fork2 = BlockBuilder::default()
+            .parent_hash(fork1.hash())
+            .number(3u64.pack())
+            .build();
+
+        let txn = store.begin_transaction();
+        for block in &[&block1, &main, &fork1, &fork2] {
+            txn.insert_block(block).unwrap();
+        }
+        txn.commit().unwrap();
+
+        assert_eq!(
+            store.get_common_ancestor(&main.hash(), &fork2.hash()),
+            Some(block1.hash())
+        );
+        assert_eq!(
+            store.get_common_ancestor(&fork2.hash(), &fork1.hash()),
+            Some(fork1.hash())
+        );
+        assert_eq!(
+            store.get_common_ancestor(&genesis.hash(), &fork2.hash()),
+            Some(genesis.hash())
+        );
+        assert_eq!(
+            store.get_common_ancestor(&main.hash(), &packed::Byte32::zero()),
+            None
+        );
+    }
+
+    #[test]
+    fn find_tips() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        assert_eq!(store.find_tips(), vec![genesis.hash()]);
+
+        let block1 = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .build();
+        let main = BlockBuilder::default()
+            .parent_hash(block1.hash())
+            .number(2u64.pack())
+            .build();
+        let fork = BlockBuilder::default()
+            .parent_hash(block1.hash())
+            .number(2u64.pack())
+            .timestamp(1u64.pack())
+            .build();
+        let header = BlockBuilder::default()
+            .parent_hash(fork.hash())
+            .number(3u64.pack())
+            .build()
+            .header();
+        let txn = store.begin_transaction();
+        for block in &[&block1, &main, &fork] {
+            txn.insert_block(block).unwrap();
+        }
+        txn.insert_headers(&[header.clone()]).unwrap();
+        txn.commit().unwrap();
+
+        let mut tips = store.find_tips();
+        tips.sort_by_key(|hash| hash.as_slice().to_vec());
+        let mut expected = vec![main.hash(), header.hash()];
+        expected.sort_by_key(|hash| hash.as_slice().to_vec());
+        assert_eq!(tips, expected);
+    }
+
+    #[test]
+    fn get_block_timestamp() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let block = BlockBuilder::default().timestamp(42u64.pack()).build();
+        assert_eq!(store.get_block_timestamp(&block.hash()), None);
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_block_timestamp(&block.hash()), Some(42));
+        // cached by the full decode
+        assert!(store.get_block_header(&block.hash()).is_some());
+        assert_eq!(store.get_block_timestamp(&block.hash()), Some(42));
+    }
+
+    #[test]
+    fn get_block_median_time() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        let mut parent = genesis.header();
+        let mut blocks = Vec::new();
+        let txn = store.begin_transaction();
+        for timestamp in &[10u64, 40, 20, 30] {
+            let block = BlockBuilder::default()
+                .parent_hash(parent.hash())
+                .number((parent.number() + 1).pack())
+                .timestamp(timestamp.pack())
+                .build();
+            txn.insert_block(&block).unwrap();
+            parent = block.header();
+            blocks.push(block);
+        }
+        txn.commit().unwrap();
+
+        let tip = blocks[3].hash();
+        assert_eq!(store.get_block_median_time(&tip, 1), Some(30));
+        assert_eq!(store.get_block_median_time(&tip, 3), Some(30));
+        // the greater one of 20 and 30
+        assert_eq!(store.get_block_median_time(&tip, 4), Some(30));
+        // only the genesis and block 1 exist
+        assert_eq!(
+            store.get_block_median_time(&blocks[0].hash(), 11),
+            Some(genesis.timestamp().max(10))
+        );
+        assert_eq!(store.get_block_median_time(&tip, 0), None);
+        assert_eq!(
+            store.get_block_median_time(&packed::Byte32::zero(), 3),
+            None
+        );
+    }
+
+    #[test]
+    fn check_genesis() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        assert!(!store.has_genesis());
+        assert!(store.check_genesis().is_ok());
+
+        let block = BlockBuilder::default().number(1u64.pack()).build();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert_error_eq(
+            store.check_genesis().unwrap_err(),
+            InternalErrorKind::DataCorrupted.reason("genesis missing"),
+        );
+
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        assert!(store.has_genesis());
+        assert!(store.check_genesis().is_ok());
+    }
+
+    #[test]
+    fn get_block_body_range() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        let block = consensus.genesis_block();
+        let hash = block.hash();
+        assert!(store.get_block_body_range(&hash, 0, 1).is_none());
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        let txs = block.transactions();
+        assert_eq!(
+            store.get_block_body_range(&hash, 0, txs.len() + 1),
+            Some(txs.clone())
+        );
+        assert_eq!(
+            store.get_block_body_range(&hash, 1, txs.len()),
+            Some(txs[1..].to_vec())
+        );
+        assert_eq!(
+            store.get_block_body_range(&hash, txs.len(), 1),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn stream_block_transactions() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let block = BlockBuilder::default()
+            .transaction(TransactionBuilder::default().build())
+            .transaction(TransactionBuilder::default().version(1u32.pack()).build())
+            .transaction(TransactionBuilder::default().version(2u32.pack()).build())
+            .build();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+
+        let mut streamed = Vec::new();
+        let count = store
+            .stream_block_transactions(&block.hash(), |index, tx| streamed.push((index, tx)))
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(
+            streamed,
+            block
+                .transactions()
+                .into_iter()
+                .enumerate()
+                .collect::<Vec<_>>()
+        );
+
+        // corrupt the second transaction
+        let key = store
+            .get_iter(
+                COLUMN_BLOCK_BODY,
+                block.hash().as_slice(),
+                Direction::Forward,
+            )
+            .nth(1)
+            .map(|(key, _)| key)
+            .unwrap();
+        let txn = store.begin_transaction();
+        txn.insert_raw(COLUMN_BLOCK_BODY, &key, b"garbage").unwrap();
+        txn.commit().unwrap();
+        let mut streamed = 0;
+        let err = store
+            .stream_block_transactions(&block.hash(), |_, _| streamed += 1)
+            .unwrap_err();
+        assert_error_eq(
+            err,
+            InternalErrorKind::DataCorrupted.reason(format!(
+                "transaction 1 of block {} is malformed",
+                block.hash()
+            )),
+        );
+        assert_eq!(streamed, 1);
+    }
+
+    #[test]
+    fn get_block_body_size() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        let block = consensus.genesis_block();
+        assert_eq!(store.get_block_body_size(&block.hash()), None);
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        let size: usize = block
+            .transactions()
+            .into_iter()
+            .map(|tx| tx.pack().as_slice().len())
+            .sum();
+        assert_eq!(store.get_block_body_size(&block.hash()), Some(size));
+
+        // a block stored before the size is stored with the checksum
+        let txn = store.begin_transaction();
+        txn.delete(COLUMN_BLOCK_BODY_CHECKSUM, block.hash().as_slice())
+            .unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_block_body_size(&block.hash()), Some(size));
+    }
+
+    #[test]
+    fn get_block_total_output_capacity() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let output = |shannons: u64| {
+            packed::CellOutput::new_builder()
+                .capacity(Capacity::shannons(shannons).pack())
+                .build()
+        };
+        let block = BlockBuilder::default()
+            .transaction(
+                TransactionBuilder::default()
+                    .output(output(100))
+                    .output(output(200))
+                    .build(),
+            )
+            .transaction(TransactionBuilder::default().output(output(300)).build())
+            .build();
+        let overflow = BlockBuilder::default()
+            .number(1.pack())
+            .transaction(
+                TransactionBuilder::default()
+                    .output(output(u64::max_value()))
+                    .output(output(1))
+                    .build(),
+            )
+            .build();
+        assert_eq!(
+            store
+                .get_block_total_output_capacity(&block.hash())
+                .unwrap(),
+            None
+        );
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.insert_block(&overflow).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            store
+                .get_block_total_output_capacity(&block.hash())
+                .unwrap(),
+            Some(Capacity::shannons(600))
+        );
+        let err = store
+            .get_block_total_output_capacity(&overflow.hash())
+            .unwrap_err();
+        assert_error_eq(err, InternalErrorKind::CapacityOverflow);
+    }
+
+    #[test]
+    fn get_block_reward() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let output = |shannons: u64| {
+            packed::CellOutput::new_builder()
+                .capacity(Capacity::shannons(shannons).pack())
+                .build()
+        };
+        let block = BlockBuilder::default()
+            .transaction(
+                TransactionBuilder::default()
+                    .output(output(100))
+                    .output(output(200))
+                    .build(),
+            )
+            .transaction(TransactionBuilder::default().output(output(300)).build())
+            .build();
+        let overflow = BlockBuilder::default()
+            .number(1.pack())
+            .transaction(
+                TransactionBuilder::default()
+                    .output(output(u64::max_value()))
+                    .output(output(1))
+                    .build(),
+            )
+            .build();
+        assert_eq!(store.get_block_reward(&block.hash()).unwrap(), None);
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.insert_block(&overflow).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            store.get_block_reward(&block.hash()).unwrap(),
+            Some(Capacity::shannons(300))
+        );
+        let err = store.get_block_reward(&overflow.hash()).unwrap_err();
+        assert_error_eq(err, InternalErrorKind::CapacityOverflow);
+    }
+
+    #[test]
+    fn get_cell_capacity() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let output = |shannons: u64| {
+            packed::CellOutput::new_builder()
+                .capacity(Capacity::shannons(shannons).pack())
+                .build()
+        };
+        let tx = TransactionBuilder::default()
+            .output(output(100))
+            .output(output(200))
+            .build();
+        let block = BlockBuilder::default().transaction(tx.clone()).build();
+        assert_eq!(store.get_cell_capacity(&tx.hash(), 0), None);
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            store.get_cell_capacity(&tx.hash(), 0),
+            Some(Capacity::shannons(100))
+        );
+        assert_eq!(
+            store.get_cell_capacity(&tx.hash(), 1),
+            Some(Capacity::shannons(200))
+        );
+        assert_eq!(store.get_cell_capacity(&tx.hash(), 2), None);
+    }
+
+    #[test]
+    fn get_cell_meta_and_header() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let tx = TransactionBuilder::default()
+            .output(Default::default())
+            .output_data(Default::default())
+            .build();
+        let block = BlockBuilder::default().transaction(tx.clone()).build();
+        assert!(store.get_cell_meta_and_header(&tx.hash(), 0).is_none());
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
+        txn.commit().unwrap();
+        let (cell_meta, header) = store.get_cell_meta_and_header(&tx.hash(), 0).unwrap();
+        assert_eq!(Some(cell_meta), store.get_cell_meta(&tx.hash(), 0));
+        assert_eq!(header, block.header());
+    }
+
+    #[test]
+    fn transaction_infos_ordered() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let txs: Vec<_> = (0..3u32)
+            .map(|version| {
+                TransactionBuilder::default()
+                    .version(version.pack())
+                    .build()
+            })
+            .collect();
+        let block = BlockBuilder::default().transactions(txs.clone()).build();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
+        txn.commit().unwrap();
+
+        let infos = store.transaction_infos_ordered(&block.hash()).unwrap();
+        assert_eq!(
+            infos.iter().map(|info| info.index).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+
+        // swap the infos of the first two transactions
+        let txn = store.begin_transaction();
+        let first = store.get(COLUMN_TRANSACTION_INFO, txs[0].hash().as_slice());
+        let second = store.get(COLUMN_TRANSACTION_INFO, txs[1].hash().as_slice());
+        txn.insert_raw(
+            COLUMN_TRANSACTION_INFO,
+            txs[0].hash().as_slice(),
+            second.unwrap().as_ref(),
+        )
+        .unwrap();
+        txn.insert_raw(
+            COLUMN_TRANSACTION_INFO,
+            txs[1].hash().as_slice(),
+            first.unwrap().as_ref(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+        let err = store.transaction_infos_ordered(&block.hash()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::DataCorrupted)
+        );
+    }
+
+    #[test]
+    fn verify_block_body() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        let block = consensus.genesis_block();
+        let hash = block.hash();
+        assert!(!store.verify_block_body(&hash).unwrap());
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert!(store.verify_block_body(&hash).unwrap());
+
+        let key = packed::TransactionKey::new_builder()
+            .block_hash(hash.clone())
+            .index(0usize.pack())
+            .build();
+        let txn = store.begin_transaction();
+        txn.insert_raw(
+            COLUMN_BLOCK_BODY,
+            key.as_slice(),
+            packed::TransactionView::default().as_slice(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+        let err = store.verify_block_body(&hash).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::DataCorrupted)
+        );
+    }
+
+    #[test]
+    fn owned_snapshot_outlives_transaction() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        let block = consensus.genesis_block();
+
+        let txn = store.begin_transaction();
+        let snapshot = Arc::new(txn.get_owned_snapshot());
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        drop(txn);
+
+        let shared = Arc::clone(&snapshot);
+        assert!(!shared.block_exists(&block.hash()));
+        assert!(store.block_exists(&block.hash()));
+    }
+
+    #[test]
+    fn get_block_proposal_ids() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let proposals = vec![
+            packed::ProposalShortId::new([1u8; 10]),
+            packed::ProposalShortId::new([2u8; 10]),
+        ];
+        let block = BlockBuilder::default().proposals(proposals.clone()).build();
+        assert!(store.get_block_proposal_ids(&block.hash()).is_none());
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_block_proposal_ids(&block.hash()), Some(proposals));
+    }
+
+    #[test]
+    fn refuse_unknown_column() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let txn = store.begin_transaction();
+        let err = txn.insert_raw("20", b"key", b"value").unwrap_err();
+        assert_error_eq(
+            err,
+            InternalErrorKind::System.reason("unknown column 20: only 20 columns"),
+        );
+        assert!(txn.delete("cells", b"key").is_err());
+    }
+
+    #[test]
+    fn snapshot_try_get() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let txn = store.begin_transaction();
+        txn.insert_raw(COLUMN_META, b"key", b"value").unwrap();
+        txn.commit().unwrap();
+
+        let txn = store.begin_transaction();
+        let snapshot = txn.get_snapshot();
+        assert_eq!(
+            snapshot
+                .try_get(COLUMN_META, b"key")
+                .unwrap()
+                .as_ref()
+                .map(|value| &value[..]),
+            Some(&b"value"[..])
+        );
+        assert!(snapshot.try_get(COLUMN_META, b"none").unwrap().is_none());
+        assert!(snapshot.try_get("20", b"key").is_err());
+    }
+
+    #[test]
+    fn cell_set_snapshot() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        let block = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .build();
+        let fork = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .timestamp(1u64.pack())
+            .build();
+        let tx_hash = packed::Byte32::zero();
+        let meta = TransactionMeta::new(0, 0, genesis.hash(), 1, false);
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
+        txn.insert_cell_set_snapshot(&genesis.header(), Some((&tx_hash, &meta)))
+            .unwrap();
+        txn.insert_cell_set_snapshot(&fork.header(), None).unwrap();
+        txn.commit().unwrap();
+
+        // the snapshot of the fork block is skipped
+        let expected = Some((0, vec![(tx_hash.clone(), meta.clone())]));
+        assert_eq!(store.get_cell_set_snapshot(5), expected);
+        assert_eq!(store.get_cell_set_snapshot(0), expected);
+
+        let txn = store.begin_transaction();
+        txn.insert_cell_set_snapshot(&block.header(), None).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_cell_set_snapshot(5), Some((1, vec![])));
+    }
+
+    #[test]
+    fn corruption_recovery() {
+        let consensus = ConsensusBuilder::default().build();
+        let block = consensus.genesis_block();
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default()).enable_corruption_recovery(true);
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            store.try_get_block_header(&block.hash()).unwrap(),
+            Some(block.header())
+        );
+        assert_eq!(store.try_get_block_ext(&block.hash()).unwrap(), None);
+
+        let txn = store.begin_transaction();
+        txn.insert_raw(COLUMN_BLOCK_HEADER, block.hash().as_slice(), b"garbage")
+            .unwrap();
+        txn.commit().unwrap();
+        let err = store.try_get_block_header(&block.hash()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::DataCorrupted)
+        );
+        // recorded by the read, written by the next commit
+        let key = quarantine_key(COLUMN_BLOCK_HEADER, block.hash().as_slice());
+        assert_eq!(
+            store.get_quarantined(COLUMN_BLOCK_HEADER, block.hash().as_slice()),
+            Some(b"garbage".to_vec())
+        );
+        assert!(store.get(COLUMN_QUARANTINE, &key).is_none());
+        store.begin_transaction().commit().unwrap();
+        assert_eq!(
+            store
+                .get(COLUMN_QUARANTINE, &key)
+                .map(|value| value.as_ref().to_vec()),
+            Some(b"garbage".to_vec())
+        );
+
+        // the getters are not checked
+        let txn = store.begin_transaction();
+        txn.insert_raw(COLUMN_BLOCK_EXT, block.hash().as_slice(), b"garbage")
+            .unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            store
+                .get(COLUMN_BLOCK_EXT, block.hash().as_slice())
+                .map(|value| value.as_ref().to_vec()),
+            Some(b"garbage".to_vec())
+        );
+
+        // a malformed value is an error without the recovery too, it's not quarantined
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let txn = store.begin_transaction();
+        txn.insert_raw(COLUMN_BLOCK_EXT, block.hash().as_slice(), b"garbage")
+            .unwrap();
+        txn.commit().unwrap();
+        let err = store.try_get_block_ext(&block.hash()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::DataCorrupted)
+        );
+        assert!(store
+            .get_quarantined(COLUMN_BLOCK_EXT, block.hash().as_slice())
+            .is_none());
+    }
+
+    #[test]
+    fn commit_if() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        let block = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .build();
+        let advanced = |snapshot: &StoreTransactionSnapshot<'_>| {
+            snapshot
+                .get_tip_header()
+                .map(|tip| tip.number() > genesis.number())
+                .unwrap_or(false)
+        };
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        assert!(!txn.commit_if(advanced).unwrap());
+        drop(txn);
+        assert!(store.get_block_header(&block.hash()).is_none());
+
+        // the tip read by the predicate is moved by another transaction
+        let fork = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .timestamp(1u64.pack())
+            .build();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.insert_tip_header(&block.header()).unwrap();
+        let other = store.begin_transaction();
+        other.insert_block(&fork).unwrap();
+        other.insert_tip_header(&fork.header()).unwrap();
+        other.commit().unwrap();
+        assert!(txn.commit_if(advanced).is_err());
+        assert_eq!(store.get_tip_header(), Some(fork.header()));
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.insert_tip_header(&block.header()).unwrap();
+        assert!(txn.commit_if(advanced).unwrap());
+        assert_eq!(store.get_tip_header(), Some(block.header()));
+    }
+
+    #[test]
+    fn refuse_oversized_block() {
+        let block = BlockBuilder::default()
+            .transaction(TransactionBuilder::default().build())
+            .build();
+        let size = block.data().transactions().as_slice().len();
+        let config = StoreConfig {
+            max_block_body_bytes: Some(size - 1),
+            ..Default::default()
+        };
+        let store = ChainDB::new(setup_db(COLUMNS), config);
+        let txn = store.begin_transaction();
+        let err = txn.insert_block(&block).unwrap_err();
+        assert_error_eq(
+            err,
+            InternalErrorKind::ExceededMaximumBlockBytes.reason(format!(
+                "the body of block {} is {} bytes, the maximum is {}",
+                block.hash(),
+                size,
+                size - 1
+            )),
+        );
+        assert_eq!(txn.get_block_header(&block.hash()), None);
+        // the limit holds for the bodies of the blocks inserted header-first too
+        txn.insert_headers(&[block.header()]).unwrap();
+        let err = txn.insert_block_body(&block).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::ExceededMaximumBlockBytes)
+        );
+
+        let config = StoreConfig {
+            max_block_body_bytes: Some(size),
+            ..Default::default()
+        };
+        let store = ChainDB::new(setup_db(COLUMNS), config);
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+    }
+
+    #[test]
+    fn cell_set_root() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        assert_eq!(store.cell_set_root().unwrap(), packed::Byte32::zero());
+
+        let entries: Vec<_> = (1u8..=3)
+            .map(|i| {
+                let tx_hash: packed::Byte32 = [i; 32].pack();
+                let meta = TransactionMeta::new(u64::from(i), 0, Default::default(), 2, false);
+                (tx_hash, meta.pack())
+            })
+            .collect();
+        // written in reverse, the root follows the key order
+        let txn = store.begin_transaction();
+        for (tx_hash, meta) in entries.iter().rev() {
+            txn.update_cell_set(tx_hash, meta).unwrap();
+        }
+        txn.commit().unwrap();
+
+        let leaves: Vec<packed::Byte32> = entries
+            .iter()
+            .map(|(tx_hash, meta)| {
+                blake2b_256([tx_hash.as_slice(), meta.as_slice()].concat()).pack()
+            })
+            .collect();
+        assert_eq!(store.cell_set_root().unwrap(), merkle_root(&leaves));
+    }
+
+    #[test]
+    fn repair_cell_set() {
+        let store = ChainDB::new(setup_db(COLUMNS), Default::default());
+        let tx = TransactionBuilder::default()
+            .output(Default::default())
+            .output_data(Default::default())
+            .build();
+        let block = BlockBuilder::default().transaction(tx.clone()).build();
+        let meta = TransactionMeta::new(block.number(), 0, block.hash(), 1, false).pack();
+        // an entry whose transaction is in no stored block
+        let orphan = blake2b_256(b"orphan").pack();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
+        txn.update_cell_set(&tx.hash(), &meta).unwrap();
+        txn.update_cell_set(&orphan, &meta).unwrap();
+        txn.commit().unwrap();
+        let info = store.get_transaction_info_packed(&tx.hash()).unwrap();
+        let txn = store.begin_transaction();
+        txn.delete(COLUMN_TRANSACTION_INFO, tx.hash().as_slice())
+            .unwrap();
+        txn.commit().unwrap();
+
+        assert_eq!(store.repair_cell_set().unwrap(), 2);
+        assert_eq!(
+            store
+                .get_transaction_info_packed(&tx.hash())
+                .map(|info| info.as_bytes()),
+            Some(info.as_bytes())
+        );
+        assert!(store.get_cell_meta(&tx.hash(), 0).is_some());
+        assert!(store.get_tx_meta(&orphan).is_none());
+        assert_eq!(
+            store.get_quarantined(COLUMN_CELL_SET, orphan.as_slice()),
+            Some(meta.as_slice().to_vec())
+        );
+        assert_eq!(store.repair_cell_set().unwrap(), 0);
+    }
+
+    #[test]
+    fn block_exists() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        let block = consensus.genesis_block();
+        assert!(!store.block_exists(&block.hash()));
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert!(store.block_exists(&block.hash()));
+        assert!(!store.block_exists(&packed::Byte32::zero()));
+    }
+
+    #[test]
+    fn get_lowest_stored_number() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        assert_eq!(store.get_lowest_stored_number(), None);
+
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        assert_eq!(store.get_lowest_stored_number(), Some(0));
+    }
+
+    #[test]
+    fn recover_tip() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let block = BlockBuilder::default().number(1u64.pack()).build();
+        let ext = BlockExt {
+            received_at: block.timestamp(),
+            total_difficulty: block.difficulty(),
+            total_uncles_count: 0,
+            verified: Some(true),
+            txs_fees: vec![],
+        };
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.insert_block_ext(&block.hash(), &ext).unwrap();
+        txn.attach_block(&block).unwrap();
+        txn.commit().unwrap();
+
+        let (header, recovered_ext) = store.recover_tip().unwrap();
+        assert_eq!(header, block.header());
+        assert_eq!(recovered_ext.verified, Some(true));
+    }
+
+    #[test]
+    fn tx_meta_cache() {
+        let db = setup_db(COLUMNS);
+        let config = StoreConfig {
+            tx_meta_cache_size: Some(16),
+            ..Default::default()
+        };
+        let store = ChainDB::new(db, config);
+        let tx_hash = packed::Byte32::zero();
+        let txn = store.begin_transaction();
+        txn.update_cell_set(
+            &tx_hash,
+            &TransactionMeta::new(0, 0, Default::default(), 3, false).pack(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+
+        // the inputs of a block spending many outputs of the same parent
+        let meta = store.get_tx_meta(&tx_hash).unwrap();
+        for _ in 0..9 {
+            assert_eq!(store.get_tx_meta(&tx_hash), Some(meta.clone()));
+        }
+        let cache = store.cache.tx_meta.as_ref().unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (9, 1));
+
+        // shared by the transactions and the snapshots
+        let txn = store.begin_transaction();
+        assert_eq!(txn.get_tx_meta(&tx_hash), Some(meta.clone()));
+        let snapshot = store.get_snapshot();
+        assert_eq!(snapshot.get_tx_meta(&tx_hash), Some(meta.clone()));
+        assert_eq!((cache.hits(), cache.misses()), (11, 1));
+
+        // an uncommitted write is only visible to its transaction
+        let mut dead = meta.clone();
+        dead.set_dead(0);
+        txn.update_cell_set(&tx_hash, &dead.pack()).unwrap();
+        assert_eq!(txn.get_tx_meta(&tx_hash).unwrap().is_dead(0), Some(true));
+        assert_eq!(store.get_tx_meta(&tx_hash), Some(meta.clone()));
+        // and it keeps the cached entry until the commit
+        assert_eq!((cache.hits(), cache.misses()), (12, 1));
+        txn.commit().unwrap();
+        assert_eq!(store.get_tx_meta(&tx_hash).unwrap().is_dead(0), Some(true));
+        // the snapshot taken before the commit still reads the old entry
+        assert_eq!(snapshot.get_tx_meta(&tx_hash), Some(meta));
+        assert_eq!(
+            store
+                .get_snapshot()
+                .get_tx_meta(&tx_hash)
+                .unwrap()
+                .is_dead(0),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn observe_committed_changes() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let block = BlockBuilder::default().number(1u64.pack()).build();
+        let tx_hash = packed::Byte32::zero();
+        let meta = TransactionMeta::new(1, 0, Default::default(), 1, false).pack();
+
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let id = {
+            let observed = Arc::clone(&observed);
+            store.subscribe(Box::new(move |changes: &CommittedChanges| {
+                observed.lock().push(changes.clone())
+            }))
+        };
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.attach_block(&block).unwrap();
+        txn.update_cell_set(&tx_hash, &meta).unwrap();
+        assert!(observed.lock().is_empty());
+        txn.commit().unwrap();
+        {
+            let observed = observed.lock();
+            assert_eq!(observed.len(), 1);
+            assert_eq!(observed[0].attached, vec![block.clone()]);
+            assert!(observed[0].detached.is_empty());
+            assert_eq!(observed[0].cell_set.len(), 1);
+        }
+
+        assert!(store.unsubscribe(id));
+        assert!(!store.unsubscribe(id));
+        let txn = store.begin_transaction();
+        txn.detach_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(observed.lock().len(), 1);
+    }
+
+    #[test]
+    fn delete_cell_sets() {
+        let db = setup_db(COLUMNS);
+        let config = StoreConfig {
+            tx_meta_cache_size: Some(16),
+            ..Default::default()
+        };
+        let store = ChainDB::new(db, config);
+        let tx_hashes = vec![packed::Byte32::zero(), packed::Byte32::new([1u8; 32])];
+        let meta = TransactionMeta::new(1, 0, Default::default(), 1, false);
+        let txn = store.begin_transaction();
+        for tx_hash in &tx_hashes {
+            txn.update_cell_set(tx_hash, &meta.pack()).unwrap();
+        }
+        txn.commit().unwrap();
+        for tx_hash in &tx_hashes {
+            assert_eq!(store.get_tx_meta(tx_hash), Some(meta.clone()));
+        }
+
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        {
+            let observed = Arc::clone(&observed);
+            store.subscribe(Box::new(move |changes: &CommittedChanges| {
+                observed.lock().push(changes.clone())
+            }));
+        }
+        let txn = store.begin_transaction();
+        txn.delete_cell_sets(&tx_hashes).unwrap();
+        for tx_hash in &tx_hashes {
+            assert!(txn.get_tx_meta(tx_hash).is_none());
+            assert_eq!(store.get_tx_meta(tx_hash), Some(meta.clone()));
+        }
+        txn.commit().unwrap();
+        for tx_hash in &tx_hashes {
+            assert!(store.get_tx_meta(tx_hash).is_none());
+        }
+        let observed = observed.lock();
+        assert_eq!(observed.len(), 1);
+        let deleted: Vec<_> = observed[0]
+            .cell_set
+            .iter()
+            .filter_map(|change| match change {
+                CellSetChange::Deleted(tx_hash) => Some(tx_hash.clone()),
+                CellSetChange::Updated(..) => None,
+            })
+            .collect();
+        assert_eq!(deleted, tx_hashes);
+    }
+
+    #[test]
+    fn get_block_and_ext() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        assert_eq!(
+            store.get_block_and_ext(&genesis.hash()).unwrap(),
+            Some((
+                genesis.clone(),
+                store.get_block_ext(&genesis.hash()).unwrap()
+            ))
+        );
+
+        let block = BlockBuilder::default().number(1u64.pack()).build();
+        assert_eq!(store.get_block_and_ext(&block.hash()).unwrap(), None);
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        let err = store.get_block_and_ext(&block.hash()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::DataCorrupted)
+        );
+    }
+
+    #[test]
+    fn get_cell_spender() {
+        let config = StoreConfig {
+            cell_spender_index: true,
+            ..Default::default()
+        };
+        let store = ChainDB::new(setup_db(COLUMNS), config);
+        let spent = packed::OutPoint::new(packed::Byte32::zero(), 1);
+        let tx = TransactionBuilder::default()
+            .input(packed::CellInput::new(packed::OutPoint::null(), 0))
+            .input(packed::CellInput::new(spent.clone(), 0))
+            .build();
+        let block = BlockBuilder::default()
+            .transaction(TransactionBuilder::default().build())
+            .transaction(tx.clone())
+            .build();
+
+        let txn = store.begin_transaction();
+        txn.attach_cell_spenders(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_cell_spender(&spent), Some((tx.hash(), 1)));
+
+        let txn = store.begin_transaction();
+        txn.detach_cell_spenders(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_cell_spender(&spent), None);
+
+        let store = ChainDB::new(setup_db(COLUMNS), Default::default());
+        let txn = store.begin_transaction();
+        txn.attach_cell_spenders(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_cell_spender(&spent), None);
+    }
+
+    #[test]
+    fn prune_bodies() {
+        let db = setup_db(COLUMNS);
+        let config = StoreConfig {
+            track_prunable_bodies: true,
+            ..Default::default()
+        };
+        let store = ChainDB::new(db, config);
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        let spent = TransactionBuilder::default()
+            .output(Default::default())
+            .output_data(Default::default())
+            .build();
+        let live = TransactionBuilder::default()
+            .output(Default::default())
+            .output_data(Default::default())
+            .witness(Default::default())
+            .build();
+        let block1 = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .transaction(spent.clone())
+            .build();
+        let block2 = BlockBuilder::default()
+            .parent_hash(block1.hash())
+            .number(2u64.pack())
+            .transaction(live.clone())
+            .build();
+        let block3 = BlockBuilder::default()
+            .parent_hash(block2.hash())
+            .number(3u64.pack())
+            .build();
+
+        let txn = store.begin_transaction();
+        for block in &[&block1, &block2, &block3] {
+            txn.insert_block(block).unwrap();
+            txn.attach_block(block).unwrap();
+        }
+        let meta = TransactionMeta::new(1, 0, block1.hash(), 1, true);
+        txn.update_cell_set(&spent.hash(), &meta.pack()).unwrap();
+        let meta = TransactionMeta::new(2, 0, block2.hash(), 1, false);
+        txn.update_cell_set(&live.hash(), &meta.pack()).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.prune_bodies().unwrap(), 0);
+
+        let txn = store.begin_transaction();
+        txn.set_finalized(&block3.header()).unwrap();
+        txn.commit().unwrap();
+        // the genesis cellbase has no outputs, block 2 has a live cell
+        assert_eq!(store.prune_bodies().unwrap(), 2);
+        assert_eq!(store.prune_bodies().unwrap(), 0);
+        assert_eq!(store.get_prune_cursor(), 3);
+        assert!(store.get_block_body(&block1.hash()).is_empty());
+        assert!(store.is_block_pruned(&block1.hash()));
+        assert_eq!(store.get_block(&block1.hash()), None);
+        assert_eq!(store.get_consistent_block(&block1.hash()).unwrap(), None);
+        assert!(store.verify_block_body(&block1.hash()).is_err());
+        assert_eq!(store.get_block_body(&block2.hash()), block2.transactions());
+        assert!(!store.is_block_pruned(&block2.hash()));
+        assert_eq!(store.block_range(0, 3).count(), 0);
+        assert_eq!(
+            store.block_range(2, 10).collect::<Vec<_>>(),
+            vec![block2.clone(), block3.clone()]
+        );
+
+        // the kept body is pruned once its last output is spent
+        let txn = store.begin_transaction();
+        txn.delete_cell_set(&live.hash()).unwrap();
+        txn.commit().unwrap();
+        let candidates = || {
+            store
+                .get_iter(COLUMN_PRUNE_CANDIDATE, &[], Direction::Forward)
+                .map(|(key, _)| key.to_vec())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(candidates(), vec![prune_candidate_key(2, &live.hash())]);
+        assert_eq!(store.prune_bodies().unwrap(), 1);
+        assert!(candidates().is_empty());
+        assert_eq!(store.get_block(&block2.hash()), None);
+        assert_eq!(
+            store.get_block_header(&block1.hash()),
+            Some(block1.header())
+        );
+        assert_eq!(store.get_block_hash(1), Some(block1.hash()));
+    }
+
+    #[test]
+    fn insert_headers_then_bodies() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let block1 = BlockBuilder::default().number(1u64.pack()).build();
+        let block2 = BlockBuilder::default()
+            .parent_hash(block1.hash())
+            .number(2u64.pack())
+            .build();
+
+        let txn = store.begin_transaction();
+        txn.insert_headers(&[block1.header(), block2.header()])
+            .unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            store.get_block_header(&block2.hash()),
+            Some(block2.header())
+        );
+        // the headers are not on the main chain until they are attached
+        assert_eq!(store.get_block_hash(2), None);
+        assert_eq!(store.get_block_number(&block1.hash()), None);
+        assert_eq!(store.get_header_hashes(2), vec![block2.hash()]);
+        assert_eq!(store.get_block(&block1.hash()), None);
+
+        let txn = store.begin_transaction();
+        txn.insert_block_body(&block1).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_block(&block1.hash()), Some(block1));
+        assert_eq!(store.get_block(&block2.hash()), None);
+    }
+
+    #[test]
+    fn insert_block_body() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let tx = TransactionBuilder::default().build();
+        let uncle = BlockBuilder::default().number(1u64.pack()).build();
+        let block = BlockBuilder::default()
+            .number(2u64.pack())
+            .transaction(tx.clone())
+            .uncle(uncle.as_uncle())
+            .proposal(packed::ProposalShortId::new([1u8; 10]))
+            .build();
+        let txn = store.begin_transaction();
+        let err = txn.insert_block_body(&block).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::DataCorrupted)
+        );
+
+        txn.insert_headers(&[block.header()]).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_block(&block.hash()), None);
+
+        let txn = store.begin_transaction();
+        let mismatched = [
+            block.as_advanced_builder().set_transactions(vec![]),
+            block.as_advanced_builder().set_uncles(vec![]),
+            block.as_advanced_builder().set_proposals(vec![]),
+        ];
+        for builder in mismatched.iter() {
+            let err = txn
+                .insert_block_body(&builder.clone().build_unchecked())
+                .unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<InternalError>().map(InternalError::kind),
+                Some(&InternalErrorKind::DataCorrupted)
+            );
+        }
+        txn.insert_block_body(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_block(&block.hash()), Some(block.clone()));
+        assert_eq!(store.get_block_body(&block.hash()), vec![tx]);
+        assert!(store.verify_block_body(&block.hash()).unwrap());
+    }
+
+    #[test]
+    fn warm_up() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let timeout = Duration::from_secs(60);
+        assert!(store.warm_up(10, usize::max_value(), timeout).is_err());
+
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        assert!(store.warm_up(10, usize::max_value(), timeout).is_ok());
+        assert!(store.warm_up(10, 0, Duration::from_secs(0)).is_ok());
+    }
+
+    #[test]
+    fn get_tip_block_ext() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        assert_eq!(store.get_tip_block_ext(), None);
+
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        assert_eq!(
+            store.get_tip_block_ext(),
+            store.get_block_ext(&genesis.hash())
+        );
+    }
+
+    #[test]
+    fn save_and_get_consistent_block() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        let block = consensus.genesis_block();
+
+        let hash = block.hash();
+        assert!(store.get_consistent_block(&hash).unwrap().is_none());
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            Some(block),
+            store.get_consistent_block(&hash).unwrap().as_ref()
+        );
+    }
+
+    #[test]
+    fn save_and_get_block_with_transactions() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let block = packed::Block::new_builder()
+            .transactions(
+                (0..3)
+                    .map(|_| packed::Transaction::new_builder().build())
+                    .collect::<Vec<_>>()
+                    .pack(),
+            )
+            .build()
+            .into_view();
+
+        let hash = block.hash();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(block, store.get_block(&hash).unwrap());
+    }
+
+    #[test]
+    fn save_and_get_cellbase() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        let block = consensus.genesis_block();
+        let empty_block = packed::Block::new_builder().build().into_view();
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.insert_block(&empty_block).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(block.transaction(0), store.get_cellbase(&block.hash()));
+        assert!(store.get_cellbase(&empty_block.hash()).is_none());
+    }
+
+    #[test]
+    fn save_and_get_block_ext() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        let block = consensus.genesis_block();
+
+        let ext = BlockExt {
+            received_at: block.timestamp(),
+            total_difficulty: block.difficulty(),
+            total_uncles_count: block.data().uncles().len() as u64,
+            verified: Some(true),
+            txs_fees: vec![],
+        };
+
+        let hash = block.hash();
+        let txn = store.begin_transaction();
+        txn.insert_block_ext(&hash, &ext).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(ext, store.get_block_ext(&hash).unwrap());
+        assert_eq!(Some(block.difficulty()), store.get_total_difficulty(&hash));
+        // a block ext stored before the total difficulty column was added
+        let txn = store.begin_transaction();
+        txn.delete(COLUMN_BLOCK_TOTAL_DIFFICULTY, hash.as_slice())
+            .unwrap();
+        txn.commit().unwrap();
+        assert_eq!(Some(block.difficulty()), store.get_total_difficulty(&hash));
+        assert_eq!(store.is_block_verified(&hash), Some(true));
+        assert_eq!(store.is_block_verified(&Default::default()), None);
+    }
+
+    #[test]
+    fn set_block_verified() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        let block = consensus.genesis_block();
+        let mut ext = BlockExt {
+            received_at: block.timestamp(),
+            total_difficulty: block.difficulty(),
+            total_uncles_count: 1,
+            verified: None,
+            txs_fees: vec![Capacity::shannons(1), Capacity::shannons(2)],
+        };
+        let hash = block.hash();
+        let txn = store.begin_transaction();
+        txn.insert_block_ext(&hash, &ext).unwrap();
+
+        for verified in &[true, false] {
+            txn.set_block_verified(&hash, *verified).unwrap();
+            ext.verified = Some(*verified);
+            assert_eq!(txn.get_block_ext(&hash), Some(ext.clone()));
+        }
+        assert!(txn.set_block_verified(&Default::default(), true).is_err());
+    }
+
+    #[test]
+    fn index_store() {
+        let db = RocksDB::open_tmp(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        let block = consensus.genesis_block();
+        let hash = block.hash();
+        store.init(&consensus).unwrap();
+        assert_eq!(hash, store.get_block_hash(0).unwrap());
+
+        assert_eq!(
+            block.difficulty(),
+            store.get_block_ext(&hash).unwrap().total_difficulty
+        );
+
+        assert_eq!(block.number(), store.get_block_number(&hash).unwrap());
+
+        assert_eq!(block.header(), store.get_tip_header().unwrap());
+    }
+
+    #[test]
+    fn get_tx_output_statuses() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+
+        let cellbase = consensus.genesis_block().transaction(0).unwrap();
+        assert_eq!(
+            Some(vec![false; cellbase.outputs().len()]),
+            store.get_tx_output_statuses(&cellbase.hash())
+        );
+        assert!(store
+            .get_tx_output_statuses(&packed::Byte32::zero())
+            .is_none());
+    }
+
+    #[test]
+    fn compare_and_set_tip() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+
+        let genesis = consensus.genesis_block().header();
+        let block = packed::Block::new_builder().build().into_view();
+        let header = block.header();
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        assert!(!txn.compare_and_set_tip(None, &header).unwrap());
+        assert!(txn
+            .compare_and_set_tip(Some(&genesis.hash()), &header)
+            .unwrap());
+        txn.commit().unwrap();
+        assert_eq!(
+            Some(header.hash()),
+            store.get_tip_header().map(|tip| tip.hash())
+        );
+    }
+
+    #[test]
+    fn advance_tip() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis_epoch = consensus.genesis_epoch_ext().clone();
+        assert_eq!(store.get_current_epoch_ext(), Some(genesis_epoch.clone()));
+
+        let header = BlockBuilder::default().number(1.pack()).build().header();
+        let txn = store.begin_transaction();
+        txn.advance_tip(&header, None).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_tip_header(), Some(header));
+        assert_eq!(store.get_current_epoch_ext(), Some(genesis_epoch.clone()));
+
+        let header = BlockBuilder::default().number(2.pack()).build().header();
+        let epoch = genesis_epoch
+            .clone()
+            .into_builder()
+            .number(1)
+            .start_number(2)
+            .build();
+        let txn = store.begin_transaction();
+        txn.advance_tip(&header, Some(&epoch)).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_tip_header(), Some(header));
+        assert_eq!(store.get_current_epoch_ext(), Some(epoch));
+    }
+
+    #[test]
+    fn column_property() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        assert!(store
+            .column_property(
+                COLUMN_BLOCK_HEADER,
+                "rocksdb.estimate-pending-compaction-bytes"
+            )
+            .is_some());
+        assert!(store
+            .column_property(COLUMN_BLOCK_HEADER, "rocksdb.no-such-property")
+            .is_none());
+        assert!(store
+            .column_property("255", "rocksdb.estimate-pending-compaction-bytes")
+            .is_none());
+    }
+
+    #[test]
+    fn transaction_exists() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let block = BlockBuilder::default()
+            .transaction(TransactionBuilder::default().build())
+            .build();
+        let tx_hash = block.transactions()[0].hash();
+        assert!(!store.transaction_exists(&tx_hash));
+
+        let txn = store.begin_transaction();
+        txn.insert_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert!(!store.transaction_exists(&tx_hash));
+
+        let txn = store.begin_transaction();
+        txn.attach_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert!(store.transaction_exists(&tx_hash));
+
+        let txn = store.begin_transaction();
+        txn.detach_block(&block).unwrap();
+        txn.commit().unwrap();
+        assert!(!store.transaction_exists(&tx_hash));
+    }
+
+    #[test]
+    fn init_genesis() {
+        let db = setup_db(COLUMNS);
+        let store = ChainDB::new(db, Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        let genesis = consensus.genesis_block();
+        let epoch = consensus.genesis_epoch_ext();
+        store.init_genesis(genesis, epoch).unwrap();
+        assert_eq!(store.get_tip_header(), Some(genesis.header()));
+        assert_eq!(store.get_finalized_header(), Some(genesis.header()));
+        assert_eq!(store.get_current_epoch_ext().as_ref(), Some(epoch));
+        assert_eq!(store.get_block_hash(0), Some(genesis.hash()));
+        assert_eq!(store.is_block_verified(&genesis.hash()), Some(true));
+        assert!(store.check_genesis().is_ok());
+
+        let err = store.init_genesis(genesis, epoch).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::DataCorrupted)
+        );
     }
 }
@@ -0,0 +1,231 @@
+use crate::{ChainDB, ChainStore, COLUMN_BLOCK_BODY, COLUMN_CELL_SET, COLUMN_TRANSACTION_INFO};
+use ckb_error::{Error, InternalErrorKind};
+use ckb_types::{
+    core::{BlockExt, BlockView},
+    packed,
+    prelude::*,
+};
+use std::io::{Read, Write};
+
+impl ChainDB {
+    /// Write the block, its ext and the transactions it spends into `writer`, the dump can
+    /// be loaded into another store by `load_block`
+    ///
+    /// Each spent transaction is dumped with its transaction info and its current cell set
+    /// entry, so the cells the block spends can be resolved in the loaded store. The entry
+    /// is absent if the transaction is already fully spent. Each item is framed by its
+    /// length as a little endian u32.
+    pub fn dump_block<W: Write>(&self, hash: &packed::Byte32, writer: &mut W) -> Result<(), Error> {
+        let block = match self.get_block(hash) {
+            Some(block) => block,
+            None => Err(InternalErrorKind::Database
+                .reason(format!("failed to dump block {}: not stored", hash)))?,
+        };
+        let ext = match self.get_block_ext(hash) {
+            Some(ext) => ext,
+            None => Err(InternalErrorKind::Database
+                .reason(format!("failed to dump block {}: ext not stored", hash)))?,
+        };
+        let mut parents: Vec<_> = block
+            .transactions()
+            .iter()
+            .flat_map(|tx| tx.input_pts_iter())
+            .map(|out_point| out_point.tx_hash())
+            .filter(|tx_hash| !tx_hash.is_zero())
+            .collect();
+        parents.sort();
+        parents.dedup();
+        let mut dumped_parents = Vec::with_capacity(parents.len());
+        for tx_hash in parents {
+            let info = self.get_transaction_info_packed(&tx_hash);
+            let tx = info
+                .as_ref()
+                .and_then(|info| self.get(COLUMN_BLOCK_BODY, info.key().as_slice()));
+            let (info, tx) = match (info, tx) {
+                (Some(info), Some(tx)) => (info, tx.as_ref().to_vec()),
+                _ => Err(InternalErrorKind::Database.reason(format!(
+                    "failed to dump block {}: the spent transaction {} is not stored",
+                    hash, tx_hash
+                )))?,
+            };
+            let meta = self
+                .get(COLUMN_CELL_SET, tx_hash.as_slice())
+                .map(|meta| meta.as_ref().to_vec())
+                .unwrap_or_default();
+            dumped_parents.push((tx_hash, info, tx, meta));
+        }
+
+        write_frame(writer, block.data().as_slice())?;
+        write_frame(writer, ext.pack().as_slice())?;
+        write_frame(writer, &(dumped_parents.len() as u32).to_le_bytes())?;
+        for (tx_hash, info, tx, meta) in dumped_parents {
+            write_frame(writer, tx_hash.as_slice())?;
+            write_frame(writer, info.as_slice())?;
+            write_frame(writer, &tx)?;
+            write_frame(writer, &meta)?;
+        }
+        Ok(())
+    }
+
+    /// Load a dump written by `dump_block`, the block is stored but not attached to the
+    /// main chain
+    ///
+    /// The spent transactions are stored under the blocks containing them, without the
+    /// headers of those blocks.
+    pub fn load_block<R: Read>(&self, reader: &mut R) -> Result<BlockView, Error> {
+        let block = packed::BlockReader::from_slice(&read_frame(reader)?)
+            .map_err(malformed)?
+            .to_entity()
+            .into_view();
+        let ext: BlockExt = packed::BlockExtReader::from_slice(&read_frame(reader)?)
+            .map_err(malformed)?
+            .unpack();
+        let count = {
+            let frame = read_frame(reader)?;
+            let mut buf = [0u8; 4];
+            if frame.len() != buf.len() {
+                Err(malformed("the count of spent transactions"))?;
+            }
+            buf.copy_from_slice(&frame);
+            u32::from_le_bytes(buf)
+        };
+
+        let txn = self.begin_transaction();
+        txn.insert_block(&block)?;
+        txn.insert_block_ext(&block.hash(), &ext)?;
+        for _ in 0..count {
+            let tx_hash = packed::Byte32Reader::from_slice(&read_frame(reader)?)
+                .map_err(malformed)?
+                .to_entity();
+            let info = packed::TransactionInfoReader::from_slice(&read_frame(reader)?)
+                .map_err(malformed)?
+                .to_entity();
+            let tx = packed::TransactionViewReader::from_slice(&read_frame(reader)?)
+                .map_err(malformed)?
+                .to_entity();
+            if tx.hash() != tx_hash {
+                Err(malformed(format!(
+                    "the spent transaction {} mismatch",
+                    tx_hash
+                )))?;
+            }
+            txn.insert_raw(COLUMN_TRANSACTION_INFO, tx_hash.as_slice(), info.as_slice())?;
+            txn.insert_raw(COLUMN_BLOCK_BODY, info.key().as_slice(), tx.as_slice())?;
+            let meta = read_frame(reader)?;
+            if !meta.is_empty() {
+                let meta = packed::TransactionMetaReader::from_slice(&meta)
+                    .map_err(malformed)?
+                    .to_entity();
+                txn.update_cell_set(&tx_hash, &meta)?;
+            }
+        }
+        txn.commit()?;
+        Ok(block)
+    }
+}
+
+fn write_frame<W: Write>(writer: &mut W, data: &[u8]) -> Result<(), Error> {
+    writer
+        .write_all(&(data.len() as u32).to_le_bytes())
+        .and_then(|_| writer.write_all(data))
+        .map_err(|err| InternalErrorKind::System.cause(err))?;
+    Ok(())
+}
+
+fn read_frame<R: Read>(reader: &mut R) -> Result<Vec<u8>, Error> {
+    let mut len = [0u8; 4];
+    reader
+        .read_exact(&mut len)
+        .map_err(|err| InternalErrorKind::System.cause(err))?;
+    let mut data = vec![0u8; u32::from_le_bytes(len) as usize];
+    reader
+        .read_exact(&mut data)
+        .map_err(|err| InternalErrorKind::System.cause(err))?;
+    Ok(data)
+}
+
+fn malformed<E: ::std::fmt::Debug>(err: E) -> Error {
+    InternalErrorKind::DataCorrupted
+        .reason(format!("malformed block dump: {:?}", err))
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::COLUMNS;
+    use ckb_chain_spec::consensus::ConsensusBuilder;
+    use ckb_db::RocksDB;
+    use ckb_types::core::{BlockBuilder, TransactionBuilder, TransactionMeta};
+
+    #[test]
+    fn dump_and_load_block() {
+        let store = ChainDB::new(RocksDB::open_tmp(COLUMNS), Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        let parent = TransactionBuilder::default()
+            .output(Default::default())
+            .output_data(Default::default())
+            .build();
+        let parent_block = BlockBuilder::default()
+            .parent_hash(genesis.hash())
+            .number(1u64.pack())
+            .transaction(parent.clone())
+            .build();
+        let tx = TransactionBuilder::default()
+            .input(packed::CellInput::new(
+                packed::OutPoint::new(parent.hash(), 0),
+                0,
+            ))
+            .build();
+        let block = BlockBuilder::default()
+            .parent_hash(parent_block.hash())
+            .number(2u64.pack())
+            .transaction(tx)
+            .build();
+        let ext = BlockExt {
+            received_at: block.timestamp(),
+            total_difficulty: block.difficulty(),
+            total_uncles_count: 0,
+            verified: None,
+            txs_fees: vec![],
+        };
+        let txn = store.begin_transaction();
+        txn.insert_block(&parent_block).unwrap();
+        txn.attach_block(&parent_block).unwrap();
+        let meta = TransactionMeta::new(1, 0, parent_block.hash(), 1, false);
+        txn.update_cell_set(&parent.hash(), &meta.pack()).unwrap();
+        txn.insert_block(&block).unwrap();
+        txn.insert_block_ext(&block.hash(), &ext).unwrap();
+        txn.commit().unwrap();
+
+        let mut dump = Vec::new();
+        store.dump_block(&block.hash(), &mut dump).unwrap();
+
+        let fixture = ChainDB::new(RocksDB::open_tmp(COLUMNS), Default::default());
+        let loaded = fixture.load_block(&mut &dump[..]).unwrap();
+        assert_eq!(loaded, block);
+        assert_eq!(fixture.get_block(&block.hash()), Some(block.clone()));
+        assert_eq!(fixture.get_block_ext(&block.hash()), Some(ext));
+        assert_eq!(
+            fixture.get_tx_meta(&parent.hash()),
+            store.get_tx_meta(&parent.hash())
+        );
+        // the spent cell resolves in the loaded store
+        assert_eq!(
+            fixture.get_cell_meta(&parent.hash(), 0),
+            store.get_cell_meta(&parent.hash(), 0)
+        );
+        assert!(fixture.get_cell_meta(&parent.hash(), 0).is_some());
+
+        assert!(fixture.load_block(&mut &dump[..dump.len() - 1]).is_err());
+
+        // a spent transaction which is not stored can't be dumped
+        let txn = fixture.begin_transaction();
+        txn.delete(COLUMN_TRANSACTION_INFO, parent.hash().as_slice())
+            .unwrap();
+        txn.commit().unwrap();
+        assert!(fixture.dump_block(&block.hash(), &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{ChainDB, ChainStore, StoreTransaction};
+use ckb_chain_spec::consensus::Consensus;
+use ckb_error::{Error, InternalErrorKind};
+use ckb_types::{
+    core::{BlockExt, BlockView, EpochExt, HeaderView, TransactionMeta},
+    packed,
+    prelude::*,
+};
+use std::collections::HashMap;
+
+/// Write a chain of blocks into the store in batches, so the memory held by the pending
+/// transaction is bounded however long the chain is
+///
+/// A batch is committed together with the tip once it reaches either limit, and the
+/// memtables are flushed after it if `flush_between_batches` is enabled. The blocks up to
+/// the stored tip are skipped, so an interrupted import resumes from the last committed
+/// batch when it's run again on the same blocks.
+///
+/// The blocks are attached with their exts, cell set entries, epochs and the main chain
+/// index, but not verified.
+pub struct BlockImporter<'a> {
+    store: &'a ChainDB,
+    consensus: &'a Consensus,
+    max_batch_blocks: usize,
+    max_batch_bytes: usize,
+    flush: bool,
+}
+
+impl<'a> BlockImporter<'a> {
+    pub fn new(
+        store: &'a ChainDB,
+        consensus: &'a Consensus,
+        max_batch_blocks: usize,
+        max_batch_bytes: usize,
+    ) -> Self {
+        BlockImporter {
+            store,
+            consensus,
+            max_batch_blocks,
+            max_batch_bytes,
+            flush: false,
+        }
+    }
+
+    /// Flush the memtables after each committed batch and wait for it, disabled by default
+    pub fn flush_between_batches(mut self, enabled: bool) -> Self {
+        self.flush = enabled;
+        self
+    }
+
+    /// Import the blocks in order, each one must be a child of the previous one, return the
+    /// count of imported blocks
+    pub fn import<I>(&self, blocks: I) -> Result<u64, Error>
+    where
+        I: IntoIterator<Item = BlockView>,
+    {
+        let mut tip = match self.store.get_tip_header() {
+            Some(tip) => tip,
+            None => Err(InternalErrorKind::Database.reason("the store is not initialized"))?,
+        };
+        let mut tip_ext = self.get_block_ext(&tip)?;
+        let mut tip_epoch = match self.store.get_block_epoch(&tip.hash()) {
+            Some(epoch) => epoch,
+            None => Err(InternalErrorKind::DataCorrupted
+                .reason(format!("the epoch of block {} is not stored", tip.hash())))?,
+        };
+        let mut imported = 0;
+        let mut batch = Batch::new(self.store.begin_transaction());
+
+        for block in blocks {
+            if block.number() <= tip.number() {
+                continue;
+            }
+            if block.parent_hash() != tip.hash() {
+                Err(InternalErrorKind::DataCorrupted.reason(format!(
+                    "failed to import block {}: its parent is not the tip {}",
+                    block.hash(),
+                    tip.hash()
+                )))?;
+            }
+            let ext = BlockExt {
+                received_at: block.timestamp(),
+                total_difficulty: &tip_ext.total_difficulty + block.difficulty(),
+                total_uncles_count: tip_ext.total_uncles_count + block.uncles().data().len() as u64,
+                verified: None,
+                txs_fees: vec![],
+            };
+            // read through the pending transaction, the previous blocks of the batch are
+            // not committed yet
+            let epoch = batch
+                .txn
+                .next_epoch_ext(self.consensus, &tip_epoch, &tip)
+                .unwrap_or(tip_epoch);
+            batch.txn.insert_block(&block)?;
+            batch.txn.insert_block_ext(&block.hash(), &ext)?;
+            batch.txn.insert_block_epoch_index(
+                &block.hash(),
+                &epoch.last_block_hash_in_previous_epoch(),
+            )?;
+            batch
+                .txn
+                .insert_epoch_ext(&epoch.last_block_hash_in_previous_epoch(), &epoch)?;
+            batch.txn.attach_block(&block)?;
+            attach_block_cell(&batch.txn, &block)?;
+            batch.blocks += 1;
+            batch.bytes += block.data().as_slice().len();
+            imported += 1;
+            tip = block.header();
+            tip_ext = ext;
+            tip_epoch = epoch;
+
+            if batch.blocks >= self.max_batch_blocks || batch.bytes >= self.max_batch_bytes {
+                self.commit(&batch, &tip, &tip_epoch)?;
+                batch = Batch::new(self.store.begin_transaction());
+            }
+        }
+        if batch.blocks > 0 {
+            self.commit(&batch, &tip, &tip_epoch)?;
+        }
+        Ok(imported)
+    }
+
+    fn commit(&self, batch: &Batch, tip: &HeaderView, epoch: &EpochExt) -> Result<(), Error> {
+        batch.txn.advance_tip(tip, Some(epoch))?;
+        batch.txn.commit()?;
+        if self.flush {
+            self.store.flush(true)?;
+        }
+        Ok(())
+    }
+
+    fn get_block_ext(&self, header: &HeaderView) -> Result<BlockExt, Error> {
+        match self.store.get_block_ext(&header.hash()) {
+            Some(ext) => Ok(ext),
+            None => Err(InternalErrorKind::DataCorrupted
+                .reason(format!("the ext of block {} is not stored", header.hash())))?,
+        }
+    }
+}
+
+// Spend the cells the block's transactions consume and add the cells they create, the
+// entries of the fully spent transactions are removed
+fn attach_block_cell(txn: &StoreTransaction, block: &BlockView) -> Result<(), Error> {
+    let mut touched: HashMap<packed::Byte32, TransactionMeta> = HashMap::new();
+    for tx in block.transactions() {
+        if !tx.is_cellbase() {
+            for out_point in tx.input_pts_iter() {
+                let tx_hash = out_point.tx_hash();
+                let index: u32 = out_point.index().unpack();
+                if !touched.contains_key(&tx_hash) {
+                    if let Some(meta) = txn.get_tx_meta(&tx_hash) {
+                        touched.insert(tx_hash.clone(), meta);
+                    }
+                }
+                match touched
+                    .get_mut(&tx_hash)
+                    .filter(|meta| meta.is_dead(index as usize) == Some(false))
+                {
+                    Some(meta) => meta.set_dead(index as usize),
+                    None => Err(InternalErrorKind::DataCorrupted.reason(format!(
+                        "failed to import block {}: the cell {}#{} is not live",
+                        block.hash(),
+                        tx_hash,
+                        index
+                    )))?,
+                }
+            }
+        }
+        let meta = if tx.is_cellbase() {
+            TransactionMeta::new_cellbase(
+                block.number(),
+                block.epoch().number(),
+                block.hash(),
+                tx.outputs().len(),
+                false,
+            )
+        } else {
+            TransactionMeta::new(
+                block.number(),
+                block.epoch().number(),
+                block.hash(),
+                tx.outputs().len(),
+                false,
+            )
+        };
+        touched.insert(tx.hash(), meta);
+    }
+    for (tx_hash, meta) in &touched {
+        if meta.all_dead() {
+            txn.delete_cell_set(tx_hash)?;
+        } else {
+            txn.update_cell_set(tx_hash, &meta.pack())?;
+        }
+    }
+    txn.attach_cell_spenders(block)
+}
+
+struct Batch {
+    txn: StoreTransaction,
+    blocks: usize,
+    bytes: usize,
+}
+
+impl Batch {
+    fn new(txn: StoreTransaction) -> Self {
+        Batch {
+            txn,
+            blocks: 0,
+            bytes: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::COLUMNS;
+    use ckb_chain_spec::consensus::ConsensusBuilder;
+    use ckb_db::RocksDB;
+    use ckb_types::core::{BlockBuilder, TransactionBuilder};
+
+    fn gen_chain(parent: &HeaderView, len: u64) -> Vec<BlockView> {
+        let mut parent = parent.clone();
+        (0..len)
+            .map(|_| {
+                let block = BlockBuilder::default()
+                    .parent_hash(parent.hash())
+                    .number((parent.number() + 1).pack())
+                    .difficulty(parent.difficulty().pack())
+                    .build();
+                parent = block.header();
+                block
+            })
+            .collect()
+    }
+
+    #[test]
+    fn import_in_batches() {
+        let store = ChainDB::new(RocksDB::open_tmp(COLUMNS), Default::default());
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let genesis = consensus.genesis_block();
+        let mut blocks = gen_chain(&genesis.header(), 5);
+        // block 1 creates a cell spent by block 3
+        let created = TransactionBuilder::default()
+            .output(Default::default())
+            .output_data(Default::default())
+            .build();
+        let spending = TransactionBuilder::default()
+            .input(packed::CellInput::new(
+                packed::OutPoint::new(created.hash(), 0),
+                0,
+            ))
+            .output(Default::default())
+            .output_data(Default::default())
+            .build();
+        blocks[0] = blocks[0]
+            .as_advanced_builder()
+            .transaction(created.clone())
+            .build();
+        for i in 1..blocks.len() {
+            let mut builder = blocks[i]
+                .as_advanced_builder()
+                .parent_hash(blocks[i - 1].hash());
+            if i == 2 {
+                builder = builder.transaction(spending.clone());
+            }
+            blocks[i] = builder.build();
+        }
+
+        let importer = BlockImporter::new(&store, &consensus, 2, usize::max_value())
+            .flush_between_batches(true);
+        // interrupted after the first 3 blocks, the second batch holds only block 3 and is
+        // committed at the end of the import
+        assert_eq!(importer.import(blocks[..3].to_vec()).unwrap(), 3);
+        assert_eq!(store.get_tip_header(), Some(blocks[2].header()));
+        // resumed with the whole chain, the imported blocks are skipped
+        assert_eq!(importer.import(blocks.clone()).unwrap(), 2);
+
+        let tip = blocks.last().unwrap();
+        let total_difficulty = blocks.iter().fold(genesis.difficulty(), |total, block| {
+            total + block.difficulty()
+        });
+        assert_eq!(store.get_tip_header(), Some(tip.header()));
+        assert_eq!(store.get_block_hash(tip.number()), Some(tip.hash()));
+        assert_eq!(
+            store.get_block_ext(&tip.hash()).unwrap().total_difficulty,
+            total_difficulty
+        );
+        assert_eq!(
+            store.get_block_epoch(&tip.hash()),
+            Some(consensus.genesis_epoch_ext().clone())
+        );
+        assert_eq!(
+            store.get_current_epoch_ext(),
+            Some(consensus.genesis_epoch_ext().clone())
+        );
+        assert_eq!(store.get_tx_meta(&created.hash()), None);
+        assert_eq!(
+            store
+                .get_tx_meta(&spending.hash())
+                .map(|meta| meta.block_hash()),
+            Some(blocks[2].hash())
+        );
+
+        let orphan = gen_chain(&genesis.header(), 6).pop().unwrap();
+        assert!(importer.import(vec![orphan]).is_err());
+    }
+}
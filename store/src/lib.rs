@@ -2,20 +2,35 @@ mod cache;
 mod config;
 pub mod data_loader_wrapper;
 mod db;
+mod dump;
+mod import;
+mod loader;
+mod memory;
+mod metrics;
+mod observer;
+mod read_only;
 mod snapshot;
 mod store;
 mod transaction;
 
-pub use cache::StoreCache;
-pub use config::StoreConfig;
+pub use cache::{StoreCache, TxMetaCache};
+pub use config::{CellSetMode, StoreConfig};
 pub use db::ChainDB;
+pub use import::BlockImporter;
+pub use loader::BlockLoader;
+pub use memory::{MemoryStore, MemoryStoreTransaction};
+pub use metrics::StoreMetrics;
+pub use observer::{CellSetChange, CommittedChanges, Observer, ObserverId};
+pub use read_only::ReadOnlyStore;
 pub use snapshot::StoreSnapshot;
-pub use store::ChainStore;
-pub use transaction::StoreTransaction;
+pub use store::{ChainStore, LinkError};
+pub use transaction::{StoreTransaction, StoreTransactionSnapshot};
 
 use ckb_db::Col;
+use ckb_error::{Error, InternalErrorKind};
+use ckb_types::{core::BlockNumber, packed, prelude::*};
 
-pub const COLUMNS: u32 = 12;
+pub const COLUMNS: u32 = 20;
 pub const COLUMN_INDEX: Col = "0";
 pub const COLUMN_BLOCK_HEADER: Col = "1";
 pub const COLUMN_BLOCK_BODY: Col = "2";
@@ -28,6 +43,77 @@ pub const COLUMN_BLOCK_EPOCH: Col = "8";
 pub const COLUMN_EPOCH: Col = "9";
 pub const COLUMN_CELL_SET: Col = "10";
 pub const COLUMN_UNCLES: Col = "11";
+pub const COLUMN_BLOCK_BODY_CHECKSUM: Col = "12";
+pub const COLUMN_BLOCK_TOTAL_DIFFICULTY: Col = "13";
+pub const COLUMN_BLOCK_CHILDREN: Col = "14";
+pub const COLUMN_CELL_SPENDER: Col = "15";
+/// The copies of the malformed values found in the corruption recovery mode, see
+/// `ChainDB::enable_corruption_recovery`
+pub const COLUMN_QUARANTINE: Col = "16";
+/// The stored headers by number, whether they are on the main chain or not, keyed by the
+/// number followed by the hash
+pub const COLUMN_HEADER_INDEX: Col = "17";
+/// The cell set snapshots of some main chain blocks, keyed by the number in big endian
+/// followed by the transaction hash, each snapshot ends with a marker holding the block hash
+pub const COLUMN_CELL_SET_SNAPSHOT: Col = "18";
+/// The transactions whose cell set entries are deleted, the bodies of their blocks may be
+/// pruned, keyed by the block number in big endian followed by the transaction hash, see
+/// `ChainDB::prune_bodies`
+pub const COLUMN_PRUNE_CANDIDATE: Col = "19";
 
 const META_TIP_HEADER_KEY: &[u8] = b"TIP_HEADER";
 const META_CURRENT_EPOCH_KEY: &[u8] = b"CURRENT_EPOCH";
+const META_FINALIZED_KEY: &[u8] = b"FINALIZED";
+const META_PRUNE_CURSOR_KEY: &[u8] = b"PRUNE_CURSOR";
+
+// The key of a prune candidate, it sorts by the block number
+pub(crate) fn prune_candidate_key(number: BlockNumber, tx_hash: &packed::Byte32) -> Vec<u8> {
+    [&number.to_be_bytes()[..], tx_hash.as_slice()].concat()
+}
+
+// The key of the marker of the cell set snapshot, it sorts after the entries of the snapshot
+pub(crate) fn cell_set_snapshot_marker(number: BlockNumber) -> Vec<u8> {
+    [&number.to_be_bytes()[..], &[0xff; 33][..]].concat()
+}
+
+// The registered columns, the length ties the table to `COLUMNS`
+const REGISTERED_COLUMNS: [Col; COLUMNS as usize] = [
+    COLUMN_INDEX,
+    COLUMN_BLOCK_HEADER,
+    COLUMN_BLOCK_BODY,
+    COLUMN_BLOCK_UNCLE,
+    COLUMN_META,
+    COLUMN_TRANSACTION_INFO,
+    COLUMN_BLOCK_EXT,
+    COLUMN_BLOCK_PROPOSAL_IDS,
+    COLUMN_BLOCK_EPOCH,
+    COLUMN_EPOCH,
+    COLUMN_CELL_SET,
+    COLUMN_UNCLES,
+    COLUMN_BLOCK_BODY_CHECKSUM,
+    COLUMN_BLOCK_TOTAL_DIFFICULTY,
+    COLUMN_BLOCK_CHILDREN,
+    COLUMN_CELL_SPENDER,
+    COLUMN_QUARANTINE,
+    COLUMN_HEADER_INDEX,
+    COLUMN_CELL_SET_SNAPSHOT,
+    COLUMN_PRUNE_CANDIDATE,
+];
+
+/// Return a `System` error if the column is not one of the `COLUMNS` registered ones
+///
+/// The column is looked up in a const table, it is only parsed to name the bad column id.
+/// The infallible `ChainStore::get` implementations check it under `debug_assert!` only.
+pub(crate) fn check_column(col: Col) -> Result<(), Error> {
+    if REGISTERED_COLUMNS.contains(&col) {
+        return Ok(());
+    }
+    match col.parse::<u32>() {
+        Ok(id) => Err(InternalErrorKind::System
+            .reason(format!("unknown column {}: only {} columns", id, COLUMNS))
+            .into()),
+        Err(_) => Err(InternalErrorKind::System
+            .reason(format!("unknown column {:?}: not a column id", col))
+            .into()),
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{ChainDB, ChainStore};
+use ckb_error::{Error, InternalErrorKind};
+use ckb_types::core::{BlockNumber, BlockView};
+use ckb_util::{Condvar, Mutex};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Read the main chain blocks of a range on several threads and feed them to a consumer in
+/// number order, e.g. to rebuild an index or the cell set
+///
+/// The threads read at most `window` blocks ahead of the next one to be consumed, so the
+/// memory held by the loaded but not yet consumed blocks is bounded however slow the
+/// consumer is.
+pub struct BlockLoader {
+    store: Arc<ChainDB>,
+    window: usize,
+    threads: usize,
+}
+
+struct Progress {
+    // the number of the next block to be consumed
+    next: BlockNumber,
+    stopped: bool,
+}
+
+impl BlockLoader {
+    pub fn new(store: Arc<ChainDB>, window: usize, threads: usize) -> Self {
+        BlockLoader {
+            store,
+            window: window.max(1),
+            threads: threads.max(1),
+        }
+    }
+
+    /// Consume the main chain blocks from `from` to `to` inclusively, return the count of
+    /// consumed blocks
+    ///
+    /// Stop at the first error of the consumer, or with a `DataCorrupted` error at the
+    /// first missing block, the blocks after it are not consumed.
+    pub fn load<F>(&self, from: BlockNumber, to: BlockNumber, mut consumer: F) -> Result<u64, Error>
+    where
+        F: FnMut(BlockView) -> Result<(), Error>,
+    {
+        if from > to {
+            return Ok(0);
+        }
+        let cursor = Arc::new(AtomicU64::new(from));
+        let progress = Arc::new((
+            Mutex::new(Progress {
+                next: from,
+                stopped: false,
+            }),
+            Condvar::new(),
+        ));
+        let (sender, receiver) = mpsc::channel();
+        let window = self.window as u64;
+        let workers: Vec<_> = (0..self.threads)
+            .map(|_| {
+                let store = Arc::clone(&self.store);
+                let cursor = Arc::clone(&cursor);
+                let progress = Arc::clone(&progress);
+                let sender = sender.clone();
+                thread::spawn(move || loop {
+                    let number = cursor.fetch_add(1, Ordering::SeqCst);
+                    if number > to {
+                        break;
+                    }
+                    {
+                        let (ref lock, ref cvar) = *progress;
+                        let mut progress = lock.lock();
+                        // the block of `progress.next` is always within the window, so
+                        // the thread reading it never waits here
+                        while !progress.stopped && number >= progress.next + window {
+                            cvar.wait(&mut progress);
+                        }
+                        if progress.stopped {
+                            break;
+                        }
+                    }
+                    let block = store
+                        .get_block_hash(number)
+                        .and_then(|hash| store.get_block(&hash));
+                    if sender.send((number, block)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+
+        let mut consumed = 0;
+        let result = consume(from, to, &receiver, &progress, |block| {
+            consumer(block)?;
+            consumed += 1;
+            Ok(())
+        });
+
+        let (ref lock, ref cvar) = *progress;
+        lock.lock().stopped = true;
+        cvar.notify_all();
+        for worker in workers {
+            if worker.join().is_err() && result.is_ok() {
+                Err(InternalErrorKind::System.reason("a block loader thread panicked"))?;
+            }
+        }
+        result.map(|_| consumed)
+    }
+}
+
+fn consume<F>(
+    from: BlockNumber,
+    to: BlockNumber,
+    receiver: &mpsc::Receiver<(BlockNumber, Option<BlockView>)>,
+    progress: &(Mutex<Progress>, Condvar),
+    mut consumer: F,
+) -> Result<(), Error>
+where
+    F: FnMut(BlockView) -> Result<(), Error>,
+{
+    let mut pending = BTreeMap::new();
+    let mut next = from;
+    loop {
+        let (number, block) = match receiver.recv() {
+            Ok(loaded) => loaded,
+            Err(_) => Err(InternalErrorKind::System.reason(format!(
+                "the block loader threads exited before block {}",
+                next
+            )))?,
+        };
+        pending.insert(number, block);
+        while let Some(block) = pending.remove(&next) {
+            match block {
+                Some(block) => consumer(block)?,
+                None => Err(InternalErrorKind::DataCorrupted
+                    .reason(format!("main chain block {} is missing", next)))?,
+            }
+            if next == to {
+                return Ok(());
+            }
+            next += 1;
+            let (ref lock, ref cvar) = *progress;
+            lock.lock().next = next;
+            cvar.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockImporter, COLUMNS};
+    use ckb_chain_spec::consensus::ConsensusBuilder;
+    use ckb_db::RocksDB;
+    use ckb_error::InternalError;
+    use ckb_types::{core::BlockBuilder, prelude::*};
+
+    #[test]
+    fn load_in_order() {
+        let store = Arc::new(ChainDB::new(RocksDB::open_tmp(COLUMNS), Default::default()));
+        let consensus = ConsensusBuilder::default().build();
+        store.init(&consensus).unwrap();
+        let mut parent = consensus.genesis_block().header();
+        let blocks: Vec<_> = (0..20)
+            .map(|_| {
+                let block = BlockBuilder::default()
+                    .parent_hash(parent.hash())
+                    .number((parent.number() + 1).pack())
+                    .build();
+                parent = block.header();
+                block
+            })
+            .collect();
+        BlockImporter::new(&store, &consensus, 5, usize::max_value())
+            .import(blocks)
+            .unwrap();
+
+        let loader = BlockLoader::new(Arc::clone(&store), 3, 4);
+        let mut numbers = Vec::new();
+        let loaded = loader
+            .load(1, 20, |block| {
+                numbers.push(block.number());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(loaded, 20);
+        assert_eq!(numbers, (1..=20).collect::<Vec<_>>());
+
+        let err = loader
+            .load(18, 25, |block| {
+                assert!(block.number() <= 20);
+                Ok(())
+            })
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::DataCorrupted)
+        );
+
+        let err = loader
+            .load(1, 20, |block| {
+                if block.number() == 10 {
+                    Err(InternalErrorKind::System.reason("stop"))?;
+                }
+                Ok(())
+            })
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>().map(InternalError::kind),
+            Some(&InternalErrorKind::System)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::cache::StoreCache;
+use crate::store::ChainStore;
+use ckb_db::{iter::DBIteratorItem, Col, Direction};
+use ckb_error::Error;
+use ckb_types::{
+    core::{
+        cell::{CellProvider, CellStatus, HeaderChecker},
+        error::OutPointError,
+    },
+    packed::{Byte32, OutPoint},
+    prelude::*,
+};
+use ckb_util::RwLock;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+type Columns = HashMap<Col, BTreeMap<Vec<u8>, Vec<u8>>>;
+
+/// A `ChainStore` keeping everything in memory, it is intended for tests only
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    columns: Arc<RwLock<Columns>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    pub fn begin_transaction(&self) -> MemoryStoreTransaction {
+        MemoryStoreTransaction {
+            columns: Arc::clone(&self.columns),
+            pending: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl<'a> ChainStore<'a> for MemoryStore {
+    type Vector = Vec<u8>;
+
+    fn cache(&'a self) -> Option<&'a StoreCache> {
+        None
+    }
+
+    fn get(&'a self, col: Col, key: &[u8]) -> Option<Self::Vector> {
+        self.columns
+            .read()
+            .get(col)
+            .and_then(|column| column.get(key).cloned())
+    }
+
+    fn get_iter<'i>(
+        &'i self,
+        col: Col,
+        from_key: &'i [u8],
+        direction: Direction,
+    ) -> Box<Iterator<Item = DBIteratorItem> + 'i> {
+        let columns = self.columns.read();
+        let items: Vec<DBIteratorItem> = match columns.get(col) {
+            Some(column) => {
+                let into_item = |(key, value): (&Vec<u8>, &Vec<u8>)| {
+                    (
+                        key.clone().into_boxed_slice(),
+                        value.clone().into_boxed_slice(),
+                    )
+                };
+                match direction {
+                    Direction::Forward => {
+                        column.range(from_key.to_vec()..).map(into_item).collect()
+                    }
+                    Direction::Reverse => column
+                        .range(..=from_key.to_vec())
+                        .rev()
+                        .map(into_item)
+                        .collect(),
+                }
+            }
+            None => Vec::new(),
+        };
+        Box::new(items.into_iter())
+    }
+}
+
+impl CellProvider for MemoryStore {
+    fn cell(&self, out_point: &OutPoint, with_data: bool) -> CellStatus {
+        let tx_hash = out_point.tx_hash();
+        let index = out_point.index().unpack();
+        match self.get_tx_meta(&tx_hash) {
+            Some(tx_meta) => match tx_meta.is_dead(index as usize) {
+                Some(false) => {
+                    let mut cell_meta = self
+                        .get_cell_meta(&tx_hash, index)
+                        .expect("store should be consistent with cell_set");
+                    if with_data {
+                        cell_meta.mem_cell_data = self.get_cell_data(&tx_hash, index);
+                    }
+                    CellStatus::live_cell(cell_meta)
+                }
+                Some(true) => CellStatus::Dead,
+                None => CellStatus::Unknown,
+            },
+            None => CellStatus::Unknown,
+        }
+    }
+}
+
+impl HeaderChecker for MemoryStore {
+    fn check_valid(&self, block_hash: &Byte32) -> Result<(), Error> {
+        if self.get_block_number(block_hash).is_some() {
+            Ok(())
+        } else {
+            Err(OutPointError::InvalidHeader(block_hash.clone()).into())
+        }
+    }
+}
+
+/// Buffers writes until `commit`, a `None` value marks a deleted key
+pub struct MemoryStoreTransaction {
+    columns: Arc<RwLock<Columns>>,
+    pending: RwLock<HashMap<Col, BTreeMap<Vec<u8>, Option<Vec<u8>>>>>,
+}
+
+impl MemoryStoreTransaction {
+    pub fn insert_raw(&self, col: Col, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.pending
+            .write()
+            .entry(col)
+            .or_insert_with(BTreeMap::new)
+            .insert(key.to_vec(), Some(value.to_vec()));
+        Ok(())
+    }
+
+    pub fn delete(&self, col: Col, key: &[u8]) -> Result<(), Error> {
+        self.pending
+            .write()
+            .entry(col)
+            .or_insert_with(BTreeMap::new)
+            .insert(key.to_vec(), None);
+        Ok(())
+    }
+
+    pub fn commit(&self) -> Result<(), Error> {
+        let mut columns = self.columns.write();
+        for (col, writes) in self.pending.write().drain() {
+            let column = columns.entry(col).or_insert_with(BTreeMap::new);
+            for (key, value) in writes {
+                match value {
+                    Some(value) => column.insert(key, value),
+                    None => column.remove(&key),
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{COLUMN_BLOCK_HEADER, COLUMN_META};
+    use ckb_chain_spec::consensus::ConsensusBuilder;
+
+    #[test]
+    fn commit_and_iterate() {
+        let store = MemoryStore::new();
+        let txn = store.begin_transaction();
+        txn.insert_raw(COLUMN_META, &[1], &[1]).unwrap();
+        txn.insert_raw(COLUMN_META, &[2], &[2]).unwrap();
+        txn.insert_raw(COLUMN_META, &[3], &[3]).unwrap();
+        assert!(store.get(COLUMN_META, &[1]).is_none());
+        txn.commit().unwrap();
+        assert_eq!(store.get(COLUMN_META, &[1]), Some(vec![1]));
+
+        let txn = store.begin_transaction();
+        txn.delete(COLUMN_META, &[1]).unwrap();
+        txn.commit().unwrap();
+        assert!(store.get(COLUMN_META, &[1]).is_none());
+
+        let forward: Vec<_> = store
+            .get_iter(COLUMN_META, &[2], Direction::Forward)
+            .map(|(key, _)| key.to_vec())
+            .collect();
+        assert_eq!(forward, vec![vec![2], vec![3]]);
+        let reverse: Vec<_> = store
+            .get_iter(COLUMN_META, &[2], Direction::Reverse)
+            .map(|(key, _)| key.to_vec())
+            .collect();
+        assert_eq!(reverse, vec![vec![2]]);
+    }
+
+    #[test]
+    fn save_and_get_header() {
+        let store = MemoryStore::new();
+        let consensus = ConsensusBuilder::default().build();
+        let header = consensus.genesis_block().header().to_owned();
+        let hash = header.hash();
+
+        let txn = store.begin_transaction();
+        txn.insert_raw(
+            COLUMN_BLOCK_HEADER,
+            hash.as_slice(),
+            header.pack().as_slice(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+        assert_eq!(store.get_block_header(&hash), Some(header));
+    }
+}
@@ -0,0 +1,91 @@
+//! Counters of the reads and writes issued to the database, for profiling
+//!
+//! The counters are kept per store, shared by its snapshots and transactions, see
+//! `ChainDB::metrics`. They are only maintained when the `metrics` feature is enabled,
+//! otherwise the metrics are always zeros and the recording compiles to nothing.
+
+#[cfg(feature = "metrics")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StoreMetrics {
+    pub gets: u64,
+    pub inserts: u64,
+    pub deletes: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct Counters {
+    #[cfg(feature = "metrics")]
+    gets: AtomicU64,
+    #[cfg(feature = "metrics")]
+    inserts: AtomicU64,
+    #[cfg(feature = "metrics")]
+    deletes: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn load(&self) -> StoreMetrics {
+        #[cfg(feature = "metrics")]
+        {
+            StoreMetrics {
+                gets: self.gets.load(Ordering::Relaxed),
+                inserts: self.inserts.load(Ordering::Relaxed),
+                deletes: self.deletes.load(Ordering::Relaxed),
+            }
+        }
+        #[cfg(not(feature = "metrics"))]
+        {
+            StoreMetrics::default()
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_get(&self) {
+        #[cfg(feature = "metrics")]
+        self.gets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn record_insert(&self) {
+        #[cfg(feature = "metrics")]
+        self.inserts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn record_delete(&self) {
+        #[cfg(feature = "metrics")]
+        self.deletes.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChainDB, ChainStore, COLUMNS, COLUMN_META};
+    use ckb_db::RocksDB;
+
+    #[test]
+    fn count_reads_and_writes() {
+        let store = ChainDB::new(RocksDB::open_tmp(COLUMNS), Default::default());
+        let other = ChainDB::new(RocksDB::open_tmp(COLUMNS), Default::default());
+        let txn = store.begin_transaction();
+        txn.insert_raw(COLUMN_META, b"key", b"value").unwrap();
+        txn.delete(COLUMN_META, b"key").unwrap();
+        txn.commit().unwrap();
+        store.get(COLUMN_META, b"key");
+        store.get_snapshot().get(COLUMN_META, b"key");
+        other.get(COLUMN_META, b"key");
+
+        let expected = if cfg!(feature = "metrics") {
+            StoreMetrics {
+                gets: 2,
+                inserts: 1,
+                deletes: 1,
+            }
+        } else {
+            StoreMetrics::default()
+        };
+        assert_eq!(store.metrics(), expected);
+    }
+}
@@ -0,0 +1,64 @@
+use ckb_types::{core::BlockView, packed};
+use ckb_util::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// What a committed transaction changed, in the order of the writes
+#[derive(Clone, Debug, Default)]
+pub struct CommittedChanges {
+    pub attached: Vec<BlockView>,
+    pub detached: Vec<BlockView>,
+    pub cell_set: Vec<CellSetChange>,
+}
+
+impl CommittedChanges {
+    pub fn is_empty(&self) -> bool {
+        self.attached.is_empty() && self.detached.is_empty() && self.cell_set.is_empty()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum CellSetChange {
+    Updated(packed::Byte32, packed::TransactionMeta),
+    Deleted(packed::Byte32),
+}
+
+/// A callback invoked after every commit which attached or detached blocks or changed the
+/// cell set
+///
+/// It runs on the committing thread while the observers are locked, so it should be quick
+/// and must not subscribe or unsubscribe.
+pub type Observer = Box<Fn(&CommittedChanges) + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+#[derive(Default)]
+pub(crate) struct Observers {
+    next_id: AtomicU64,
+    observers: RwLock<Vec<(ObserverId, Observer)>>,
+}
+
+impl Observers {
+    pub(crate) fn subscribe(&self, observer: Observer) -> ObserverId {
+        let id = ObserverId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.observers.write().push((id, observer));
+        id
+    }
+
+    pub(crate) fn unsubscribe(&self, id: ObserverId) -> bool {
+        let mut observers = self.observers.write();
+        let len = observers.len();
+        observers.retain(|(observer_id, _)| *observer_id != id);
+        observers.len() != len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.observers.read().is_empty()
+    }
+
+    pub(crate) fn notify(&self, changes: &CommittedChanges) {
+        for (_, observer) in self.observers.read().iter() {
+            observer(changes);
+        }
+    }
+}
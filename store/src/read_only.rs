@@ -0,0 +1,94 @@
+use crate::cache::StoreCache;
+use crate::check_column;
+use crate::config::StoreConfig;
+use crate::metrics::{Counters, StoreMetrics};
+use crate::store::ChainStore;
+use ckb_db::{
+    iter::{DBIterator, DBIteratorItem, Direction},
+    Col, DBConfig, DBPinnableSlice, ReadOnlyDB,
+};
+use ckb_error::Error;
+use std::sync::Arc;
+
+/// A store over a database opened read-only, e.g. a backup opened alongside a live node
+///
+/// All the `ChainStore` reads work as usual, and since there's no transaction to begin,
+/// the store can't be written at all.
+pub struct ReadOnlyStore {
+    db: ReadOnlyDB,
+    cache: Arc<StoreCache>,
+    metrics: Counters,
+}
+
+impl ReadOnlyStore {
+    pub fn open(db_config: &DBConfig, columns: u32, config: StoreConfig) -> Result<Self, Error> {
+        Ok(ReadOnlyStore {
+            db: ReadOnlyDB::open(db_config, columns)?,
+            cache: Arc::new(StoreCache::from_config(config)),
+            metrics: Default::default(),
+        })
+    }
+
+    /// The counts of the reads issued by the store, see `ChainDB::metrics`
+    pub fn metrics(&self) -> StoreMetrics {
+        self.metrics.load()
+    }
+}
+
+impl<'a> ChainStore<'a> for ReadOnlyStore {
+    type Vector = DBPinnableSlice<'a>;
+
+    fn cache(&'a self) -> Option<&'a StoreCache> {
+        Some(&self.cache)
+    }
+
+    fn get(&'a self, col: Col, key: &[u8]) -> Option<Self::Vector> {
+        debug_assert!(check_column(col).is_ok(), "unknown column {:?}", col);
+        self.metrics.record_get();
+        self.db
+            .get_pinned(col, key)
+            .expect("db operation should be ok")
+    }
+
+    fn get_iter<'i>(
+        &'i self,
+        col: Col,
+        from_key: &'i [u8],
+        direction: Direction,
+    ) -> Box<Iterator<Item = DBIteratorItem> + 'i> {
+        self.db
+            .iter(col, from_key, direction)
+            .expect("db operation should be ok")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChainDB, COLUMNS};
+    use ckb_chain_spec::consensus::ConsensusBuilder;
+    use ckb_db::RocksDB;
+
+    #[test]
+    fn read_a_closed_store() {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("read_a_closed_store")
+            .tempdir()
+            .unwrap();
+        let db_config = DBConfig {
+            path: tmp_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        assert!(ReadOnlyStore::open(&db_config, COLUMNS, Default::default()).is_err());
+
+        let consensus = ConsensusBuilder::default().build();
+        {
+            let store = ChainDB::new(RocksDB::open(&db_config, COLUMNS), Default::default());
+            store.init(&consensus).unwrap();
+        }
+        let store = ReadOnlyStore::open(&db_config, COLUMNS, Default::default()).unwrap();
+        let genesis = consensus.genesis_block();
+        assert_eq!(store.get_tip_header(), Some(genesis.header()));
+        assert_eq!(store.get_block(&genesis.hash()).as_ref(), Some(genesis));
+    }
+}
@@ -1,14 +1,41 @@
-use crate::cache::StoreCache;
+use crate::cache::{StoreCache, TxMetaCache};
+use crate::check_column;
+use crate::metrics::Counters;
 use crate::store::ChainStore;
 use ckb_db::{
     iter::{DBIterator, DBIteratorItem, Direction},
     Col, DBPinnableSlice, RocksDBSnapshot,
 };
+use ckb_types::packed;
 use std::sync::Arc;
 
 pub struct StoreSnapshot {
     pub(crate) inner: RocksDBSnapshot,
     pub(crate) cache: Arc<StoreCache>,
+    pub(crate) metrics: Arc<Counters>,
+    // the generation of the cell set entry cache the snapshot is taken at
+    pub(crate) tx_meta_generation: Option<u64>,
+}
+
+impl StoreSnapshot {
+    pub(crate) fn new<F>(cache: Arc<StoreCache>, metrics: Arc<Counters>, take_snapshot: F) -> Self
+    where
+        F: FnOnce() -> RocksDBSnapshot,
+    {
+        let (tx_meta_generation, inner) = match cache.tx_meta.as_ref() {
+            Some(tx_meta) => {
+                let (generation, inner) = tx_meta.pin(take_snapshot);
+                (Some(generation), inner)
+            }
+            None => (None, take_snapshot()),
+        };
+        StoreSnapshot {
+            inner,
+            cache,
+            metrics,
+            tx_meta_generation,
+        }
+    }
 }
 
 impl<'a> ChainStore<'a> for StoreSnapshot {
@@ -18,7 +45,19 @@ impl<'a> ChainStore<'a> for StoreSnapshot {
         Some(&self.cache)
     }
 
+    fn tx_meta_cache(
+        &'a self,
+        _tx_hash: &packed::Byte32,
+    ) -> Option<(&'a TxMetaCache, Option<u64>)> {
+        self.cache
+            .tx_meta
+            .as_ref()
+            .map(|cache| (cache, self.tx_meta_generation))
+    }
+
     fn get(&'a self, col: Col, key: &[u8]) -> Option<Self::Vector> {
+        debug_assert!(check_column(col).is_ok(), "unknown column {:?}", col);
+        self.metrics.record_get();
         self.inner
             .get_pinned(col, key)
             .expect("db operation should be ok")
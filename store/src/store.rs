@@ -1,25 +1,41 @@
-use crate::cache::StoreCache;
+use crate::cache::{StoreCache, TxMetaCache};
 use crate::{
-    COLUMN_BLOCK_BODY, COLUMN_BLOCK_EPOCH, COLUMN_BLOCK_EXT, COLUMN_BLOCK_HEADER,
-    COLUMN_BLOCK_PROPOSAL_IDS, COLUMN_BLOCK_UNCLE, COLUMN_CELL_SET, COLUMN_EPOCH, COLUMN_INDEX,
-    COLUMN_META, COLUMN_TRANSACTION_INFO, COLUMN_UNCLES, META_CURRENT_EPOCH_KEY,
+    cell_set_snapshot_marker, COLUMN_BLOCK_BODY, COLUMN_BLOCK_BODY_CHECKSUM, COLUMN_BLOCK_CHILDREN,
+    COLUMN_BLOCK_EPOCH, COLUMN_BLOCK_EXT, COLUMN_BLOCK_HEADER, COLUMN_BLOCK_PROPOSAL_IDS,
+    COLUMN_BLOCK_TOTAL_DIFFICULTY, COLUMN_BLOCK_UNCLE, COLUMN_CELL_SET, COLUMN_CELL_SET_SNAPSHOT,
+    COLUMN_CELL_SPENDER, COLUMN_EPOCH, COLUMN_HEADER_INDEX, COLUMN_INDEX, COLUMN_META,
+    COLUMN_TRANSACTION_INFO, COLUMN_UNCLES, META_CURRENT_EPOCH_KEY, META_FINALIZED_KEY,
     META_TIP_HEADER_KEY,
 };
 use ckb_chain_spec::consensus::Consensus;
 use ckb_db::{iter::DBIteratorItem, Col, Direction};
+use ckb_error::{Error, InternalErrorKind};
+use ckb_hash::new_blake2b;
+use ckb_logger::warn;
 use ckb_types::{
     bytes::Bytes,
     core::{
-        cell::CellMeta, BlockExt, BlockNumber, BlockView, EpochExt, EpochNumber, HeaderView,
-        TransactionInfo, TransactionMeta, TransactionView, UncleBlockVecView,
+        cell::CellMeta, BlockExt, BlockNumber, BlockView, Capacity, EpochExt, EpochNumber,
+        HeaderView, TransactionInfo, TransactionMeta, TransactionView, UncleBlockVecView,
     },
     packed,
     prelude::*,
+    U256,
 };
+use std::collections::HashSet;
 
 pub trait ChainStore<'a>: Send + Sync {
     type Vector: AsRef<[u8]>;
     fn cache(&'a self) -> Option<&'a StoreCache>;
+    /// The cache of the committed cell set entries and the generation this store is pinned
+    /// at, see `TxMetaCache::get`, only if the store reads the committed entry of the
+    /// transaction
+    fn tx_meta_cache(
+        &'a self,
+        _tx_hash: &packed::Byte32,
+    ) -> Option<(&'a TxMetaCache, Option<u64>)> {
+        None
+    }
     fn get(&'a self, col: Col, key: &[u8]) -> Option<Self::Vector>;
     fn get_iter<'i>(
         &'i self,
@@ -29,17 +45,20 @@ pub trait ChainStore<'a>: Send + Sync {
     ) -> Box<Iterator<Item = DBIteratorItem> + 'i>;
 
     /// Get block by block header hash
+    ///
+    /// Return None if only the header is stored, e.g. a header-first sync has not received
+    /// the body yet, or the body is pruned
     fn get_block(&'a self, h: &packed::Byte32) -> Option<BlockView> {
-        self.get_block_header(h).map(|header| {
-            let body = self.get_block_body(h);
-            let uncles = self
-                .get_block_uncles(h)
-                .expect("block uncles must be stored");
-            let proposals = self
-                .get_block_proposal_txs_ids(h)
-                .expect("block proposal_ids must be stored");
-            BlockView::new_unchecked(header, uncles, body, proposals)
-        })
+        let header = self.get_block_header(h)?;
+        let uncles = self.get_block_uncles(h)?;
+        let proposals = self
+            .get_block_proposal_txs_ids(h)
+            .expect("block proposal_ids must be stored");
+        let body = self.get_block_body(h);
+        if body.is_empty() && self.is_block_pruned(h) {
+            return None;
+        }
+        Some(BlockView::new_unchecked(header, uncles, body, proposals))
     }
 
     /// Get header by block header hash
@@ -64,6 +83,20 @@ pub trait ChainStore<'a>: Send + Sync {
         }
     }
 
+    /// Get the timestamp of a block, only the timestamp field of the stored header is
+    /// decoded unless the header is cached
+    fn get_block_timestamp(&'a self, hash: &packed::Byte32) -> Option<u64> {
+        if let Some(cache) = self.cache() {
+            if let Some(header) = cache.headers.lock().get_refresh(hash) {
+                return Some(header.timestamp());
+            }
+        };
+        self.get(COLUMN_BLOCK_HEADER, hash.as_slice()).map(|slice| {
+            let reader = packed::HeaderViewReader::from_slice_should_be_ok(&slice.as_ref());
+            reader.data().raw().timestamp().unpack()
+        })
+    }
+
     /// Get block body by block header hash
     fn get_block_body(&'a self, hash: &packed::Byte32) -> Vec<TransactionView> {
         let prefix = hash.as_slice();
@@ -77,6 +110,181 @@ pub trait ChainStore<'a>: Send + Sync {
             .collect()
     }
 
+    /// Decode the transactions of block body one at a time and pass them to `sink` along
+    /// with their indices, so the whole body is never held in memory
+    ///
+    /// Return the count of the transactions passed, 0 if the block is not stored. Stop at
+    /// the first transaction which fails to decode with a DataCorrupted error.
+    fn stream_block_transactions<F>(
+        &'a self,
+        hash: &packed::Byte32,
+        mut sink: F,
+    ) -> Result<usize, Error>
+    where
+        F: FnMut(usize, TransactionView),
+    {
+        let prefix = hash.as_slice();
+        let mut count = 0;
+        for (_key, value) in self
+            .get_iter(COLUMN_BLOCK_BODY, prefix, Direction::Forward)
+            .take_while(|(key, _)| key.starts_with(prefix))
+        {
+            let reader = match packed::TransactionViewReader::from_slice(&value.as_ref()) {
+                Ok(reader) => reader,
+                Err(_) => Err(InternalErrorKind::DataCorrupted.reason(format!(
+                    "transaction {} of block {} is malformed",
+                    count, hash
+                )))?,
+            };
+            sink(count, Unpack::<TransactionView>::unpack(&reader));
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Get the total length of the stored transactions of block body without decoding them
+    ///
+    /// Each transaction is stored with its hashes, so it's a bit larger than the serialized
+    /// block body. The size is stored along with the body checksum, so it's one point read.
+    /// For a block stored before that, the body is iterated instead, which reads every
+    /// transaction. Return None if the block is not stored or its body is pruned
+    fn get_block_body_size(&'a self, hash: &packed::Byte32) -> Option<usize> {
+        if !self.block_exists(hash) {
+            return None;
+        }
+        if let Some(checksum) = self.get(COLUMN_BLOCK_BODY_CHECKSUM, hash.as_slice()) {
+            let checksum = checksum.as_ref();
+            if checksum.is_empty() {
+                return None;
+            }
+            if checksum.len() == 32 + packed::Uint64::TOTAL_SIZE {
+                let size: u64 =
+                    packed::Uint64Reader::from_slice_should_be_ok(&checksum[32..]).unpack();
+                return Some(size as usize);
+            }
+        }
+        let prefix = hash.as_slice();
+        let size = self
+            .get_iter(COLUMN_BLOCK_BODY, prefix, Direction::Forward)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(_key, value)| value.len())
+            .sum();
+        Some(size)
+    }
+
+    /// Get the sum of the output capacities of all transactions of block body
+    ///
+    /// The cell set doesn't record capacities, so the outputs are read from the stored
+    /// transactions, only the capacity fields are decoded. Return None if the block is not
+    /// stored and `CapacityOverflow` if the sum overflows.
+    fn get_block_total_output_capacity(
+        &'a self,
+        hash: &packed::Byte32,
+    ) -> Result<Option<Capacity>, Error> {
+        if !self.block_exists(hash) {
+            return Ok(None);
+        }
+        let prefix = hash.as_slice();
+        let mut total = Capacity::zero();
+        for (_key, value) in self
+            .get_iter(COLUMN_BLOCK_BODY, prefix, Direction::Forward)
+            .take_while(|(key, _)| key.starts_with(prefix))
+        {
+            let reader = packed::TransactionViewReader::from_slice_should_be_ok(&value.as_ref());
+            for output in reader.data().raw().outputs().iter() {
+                let capacity: Capacity = output.capacity().unpack();
+                total = total.safe_add(capacity)?;
+            }
+        }
+        Ok(Some(total))
+    }
+
+    /// Get the reward the block pays to its miner, the sum of the output capacities of its
+    /// cellbase
+    ///
+    /// No reward breakdown is stored, the block ext only keeps the fees of the transactions.
+    /// Return None if the block is not stored and `CapacityOverflow` if the sum overflows.
+    fn get_block_reward(&'a self, hash: &packed::Byte32) -> Result<Option<Capacity>, Error> {
+        let cellbase = match self.get_cellbase(hash) {
+            Some(cellbase) => cellbase,
+            None => return Ok(None),
+        };
+        let mut total = Capacity::zero();
+        for output in cellbase.outputs().into_iter() {
+            let capacity: Capacity = output.capacity().unpack();
+            total = total.safe_add(capacity)?;
+        }
+        Ok(Some(total))
+    }
+
+    /// Get at most `len` transactions of block body starting from `start`, only the
+    /// requested transactions are read since each of them is stored under its own key
+    ///
+    /// Return None if the block is not stored
+    fn get_block_body_range(
+        &'a self,
+        hash: &packed::Byte32,
+        start: usize,
+        len: usize,
+    ) -> Option<Vec<TransactionView>> {
+        if !self.block_exists(hash) {
+            return None;
+        }
+        let txs = (start..start.saturating_add(len))
+            .map(|index| {
+                let key = packed::TransactionKey::new_builder()
+                    .block_hash(hash.clone())
+                    .index(index.pack())
+                    .build();
+                self.get(COLUMN_BLOCK_BODY, key.as_slice()).map(|slice| {
+                    let reader =
+                        packed::TransactionViewReader::from_slice_should_be_ok(&slice.as_ref());
+                    Unpack::<TransactionView>::unpack(&reader)
+                })
+            })
+            .take_while(Option::is_some)
+            .filter_map(|tx| tx)
+            .collect();
+        Some(txs)
+    }
+
+    /// Whether the body of the block is pruned by `ChainDB::prune_bodies`, which leaves an
+    /// empty checksum in place of the body
+    fn is_block_pruned(&'a self, hash: &packed::Byte32) -> bool {
+        self.get(COLUMN_BLOCK_BODY_CHECKSUM, hash.as_slice())
+            .map(|slice| slice.as_ref().is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Recompute the checksum of block body and compare it with the stored one
+    ///
+    /// Return false if there is no stored checksum, e.g. the block was inserted before the
+    /// checksum column was added, `DataCorrupted` error if the checksum mismatches and
+    /// `Database` error if the body is pruned
+    fn verify_block_body(&'a self, hash: &packed::Byte32) -> Result<bool, Error> {
+        let expected = match self.get(COLUMN_BLOCK_BODY_CHECKSUM, hash.as_slice()) {
+            Some(slice) => slice.as_ref().to_vec(),
+            None => return Ok(false),
+        };
+        if expected.is_empty() {
+            Err(
+                InternalErrorKind::Database.reason(format!("the body of block {} is pruned", hash))
+            )?;
+        }
+        let prefix = hash.as_slice();
+        let actual = block_body_checksum(
+            self.get_iter(COLUMN_BLOCK_BODY, prefix, Direction::Forward)
+                .take_while(|(key, _)| key.starts_with(prefix))
+                .map(|(_key, value)| value),
+        );
+        // the stored size may follow the checksum
+        if expected.len() < actual.len() || expected[..actual.len()] != actual[..] {
+            Err(InternalErrorKind::DataCorrupted
+                .reason(format!("block {} body checksum mismatch", hash)))?;
+        }
+        Ok(true)
+    }
+
     /// Get all transaction-hashes in block body by block header hash
     fn get_block_txs_hashes(&'a self, hash: &packed::Byte32) -> Vec<packed::Byte32> {
         if let Some(cache) = self.cache() {
@@ -106,6 +314,17 @@ pub trait ChainStore<'a>: Send + Sync {
         ret
     }
 
+    /// Get proposal short ids of block as a vector by block header hash
+    ///
+    /// Return None if the block is not stored
+    fn get_block_proposal_ids(
+        &'a self,
+        hash: &packed::Byte32,
+    ) -> Option<Vec<packed::ProposalShortId>> {
+        self.get_block_proposal_txs_ids(hash)
+            .map(|ids| ids.into_iter().collect())
+    }
+
     /// Get proposal short id by block header hash
     fn get_block_proposal_txs_ids(
         &'a self,
@@ -163,6 +382,50 @@ pub trait ChainStore<'a>: Send + Sync {
         }
     }
 
+    /// Get the hashes of the stored blocks whose parent is `parent`, in key order
+    ///
+    /// The children are indexed by `insert_block`, whether they are on the main chain or not
+    fn get_block_children(&'a self, parent: &packed::Byte32) -> Vec<packed::Byte32> {
+        let prefix = parent.as_slice();
+        self.get_iter(COLUMN_BLOCK_CHILDREN, prefix, Direction::Forward)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, _)| {
+                packed::Byte32Reader::from_slice_should_be_ok(&key[prefix.len()..]).to_entity()
+            })
+            .collect()
+    }
+
+    /// Get the hashes of the stored headers at `number`, in key order
+    ///
+    /// Unlike `get_block_hash`, it covers the headers written by `insert_headers` and the
+    /// blocks off the main chain
+    fn get_header_hashes(&'a self, number: BlockNumber) -> Vec<packed::Byte32> {
+        let number: packed::Uint64 = number.pack();
+        let prefix = number.as_slice();
+        self.get_iter(COLUMN_HEADER_INDEX, prefix, Direction::Forward)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, _)| {
+                packed::Byte32Reader::from_slice_should_be_ok(&key[prefix.len()..]).to_entity()
+            })
+            .collect()
+    }
+
+    /// Get the stored blocks which have no recorded children, i.e. the tips of the main
+    /// chain and of every fork
+    ///
+    /// Both the headers and the children index are scanned in full, it's meant for
+    /// monitoring and debugging rather than the hot path
+    fn find_tips(&'a self) -> Vec<packed::Byte32> {
+        let parents: HashSet<Vec<u8>> = self
+            .get_iter(COLUMN_BLOCK_CHILDREN, &[], Direction::Forward)
+            .map(|(key, _)| key[..32].to_vec())
+            .collect();
+        self.get_iter(COLUMN_BLOCK_HEADER, &[], Direction::Forward)
+            .filter(|(key, _)| !parents.contains(&key[..]))
+            .map(|(key, _)| packed::Byte32Reader::from_slice_should_be_ok(&key).to_entity())
+            .collect()
+    }
+
     /// Get block ext by block header hash
     fn get_block_ext(&'a self, block_hash: &packed::Byte32) -> Option<BlockExt> {
         self.get(COLUMN_BLOCK_EXT, block_hash.as_slice())
@@ -171,6 +434,33 @@ pub trait ChainStore<'a>: Send + Sync {
             })
     }
 
+    /// Get the verified flag of the block ext, only the flag field is decoded
+    ///
+    /// Return None if the ext is not stored or the block is not verified yet.
+    fn is_block_verified(&'a self, block_hash: &packed::Byte32) -> Option<bool> {
+        self.get(COLUMN_BLOCK_EXT, block_hash.as_slice())
+            .and_then(|slice| {
+                packed::BlockExtReader::from_slice_should_be_ok(&slice.as_ref()[..])
+                    .verified()
+                    .unpack()
+            })
+    }
+
+    /// Get the total difficulty of the chain ending at the block, without decoding the whole
+    /// block ext, it is written along with the block ext
+    ///
+    /// Fall back to the block ext for the blocks stored before the column was added.
+    fn get_total_difficulty(&'a self, block_hash: &packed::Byte32) -> Option<U256> {
+        self.get(COLUMN_BLOCK_TOTAL_DIFFICULTY, block_hash.as_slice())
+            .map(|slice| {
+                packed::Uint256Reader::from_slice_should_be_ok(&slice.as_ref()[..]).unpack()
+            })
+            .or_else(|| {
+                self.get_block_ext(block_hash)
+                    .map(|ext| ext.total_difficulty)
+            })
+    }
+
     /// Get block header hash by block number
     fn get_block_hash(&'a self, number: BlockNumber) -> Option<packed::Byte32> {
         let block_number: packed::Uint64 = number.pack();
@@ -178,12 +468,195 @@ pub trait ChainStore<'a>: Send + Sync {
             .map(|raw| packed::Byte32Reader::from_slice_should_be_ok(&raw.as_ref()[..]).to_entity())
     }
 
+    /// Same as `get_block_hash`, but return a DataCorrupted error instead of panicking if
+    /// the stored hash is not 32 bytes
+    fn get_block_hash_checked(
+        &'a self,
+        number: BlockNumber,
+    ) -> Result<Option<packed::Byte32>, Error> {
+        let block_number: packed::Uint64 = number.pack();
+        match self.get(COLUMN_INDEX, block_number.as_slice()) {
+            Some(raw) => match packed::Byte32Reader::from_slice(raw.as_ref()) {
+                Ok(reader) => Ok(Some(reader.to_entity())),
+                Err(_) => Err(InternalErrorKind::DataCorrupted.reason(format!(
+                    "the hash of block {} is {} bytes",
+                    number,
+                    raw.as_ref().len()
+                )))?,
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Check whether the genesis block is indexed, i.e. the store has been initialized
+    fn has_genesis(&'a self) -> bool {
+        self.get_block_hash(0).is_some()
+    }
+
     /// Get block number by block header hash
     fn get_block_number(&'a self, hash: &packed::Byte32) -> Option<BlockNumber> {
         self.get(COLUMN_INDEX, hash.as_slice())
             .map(|raw| packed::Uint64Reader::from_slice_should_be_ok(&raw.as_ref()[..]).unpack())
     }
 
+    /// Same as `get_block_number`, but return a DataCorrupted error instead of panicking if
+    /// the stored number is not 8 bytes
+    fn get_block_number_checked(
+        &'a self,
+        hash: &packed::Byte32,
+    ) -> Result<Option<BlockNumber>, Error> {
+        match self.get(COLUMN_INDEX, hash.as_slice()) {
+            Some(raw) => match packed::Uint64Reader::from_slice(raw.as_ref()) {
+                Ok(reader) => Ok(Some(reader.unpack())),
+                Err(_) => Err(InternalErrorKind::DataCorrupted.reason(format!(
+                    "the number of block {} is {} bytes",
+                    hash,
+                    raw.as_ref().len()
+                )))?,
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Check whether the block is on the main chain, by the reverse index from hash to number
+    /// and then the forward index from number to hash
+    fn is_main_chain(&'a self, hash: &packed::Byte32) -> bool {
+        self.get_block_number(hash)
+            .and_then(|number| self.get_block_hash(number))
+            .map(|main_hash| &main_hash == hash)
+            .unwrap_or(false)
+    }
+
+    /// Iterate main chain blocks with number in `[from, to]`, each block is loaded lazily
+    ///
+    /// The iteration terminates at the first number which is not indexed, e.g. above the tip,
+    /// or whose block can't be loaded, e.g. its body is pruned, so the blocks are always
+    /// consecutive from `from`
+    fn block_range(
+        &'a self,
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> Box<Iterator<Item = BlockView> + 'a> {
+        Box::new(
+            (from..=to)
+                .map(move |number| {
+                    self.get_block_hash(number)
+                        .and_then(|hash| self.get_block(&hash))
+                })
+                .take_while(Option::is_some)
+                .filter_map(|block| block),
+        )
+    }
+
+    /// Check the main chain blocks with number in `[from, to]`, every number should be
+    /// indexed to a stored header which agrees on the number and whose parent is the block
+    /// indexed at the previous number
+    ///
+    /// Nothing is written, run it against a snapshot to get a consistent view.
+    fn check_chain_links(&'a self, from: BlockNumber, to: BlockNumber) -> Vec<LinkError> {
+        let mut errors = Vec::new();
+        for number in from..=to {
+            let hash = match self.get_block_hash(number) {
+                Some(hash) => hash,
+                None => {
+                    errors.push(LinkError::Gap { number });
+                    continue;
+                }
+            };
+            let header = match self.get_block_header(&hash) {
+                Some(header) => header,
+                None => {
+                    errors.push(LinkError::MissingHeader { number, hash });
+                    continue;
+                }
+            };
+            if header.number() != number || self.get_block_number(&hash) != Some(number) {
+                errors.push(LinkError::IndexMismatch {
+                    number,
+                    hash: hash.clone(),
+                });
+            }
+            if number == 0 {
+                continue;
+            }
+            let parent = header.parent_hash();
+            if self.get_block_header(&parent).is_none() {
+                errors.push(LinkError::MissingParent { hash, parent });
+            } else if self.get_block_hash(number - 1).as_ref() != Some(&parent) {
+                errors.push(LinkError::ParentMismatch {
+                    number,
+                    hash,
+                    parent,
+                });
+            }
+        }
+        errors
+    }
+
+    /// Get the latest block which is an ancestor of both `a` and `b`, a block is an
+    /// ancestor of itself
+    ///
+    /// The higher one is walked back to the height of the other first, then both are walked
+    /// back together. Return None if either is unknown or the walk reaches a missing parent
+    fn get_common_ancestor(
+        &'a self,
+        a: &packed::Byte32,
+        b: &packed::Byte32,
+    ) -> Option<packed::Byte32> {
+        let mut a = self.get_block_header(a)?;
+        let mut b = self.get_block_header(b)?;
+        while a.number() > b.number() {
+            a = self.get_block_header(&a.parent_hash())?;
+        }
+        while b.number() > a.number() {
+            b = self.get_block_header(&b.parent_hash())?;
+        }
+        while a.hash() != b.hash() {
+            if a.number() == 0 {
+                return None;
+            }
+            a = self.get_block_header(&a.parent_hash())?;
+            b = self.get_block_header(&b.parent_hash())?;
+        }
+        Some(a.hash())
+    }
+
+    /// Get the median timestamp of the block and its `window - 1` ancestors, the greater
+    /// one of the middle two if the count is even
+    ///
+    /// Near genesis fewer than `window` blocks exist and all of them are used. Return None
+    /// if `window` is 0, the block is unknown or the walk reaches a missing parent
+    fn get_block_median_time(&'a self, hash: &packed::Byte32, window: usize) -> Option<u64> {
+        if window == 0 {
+            return None;
+        }
+        let mut header = self.get_block_header(hash)?;
+        let mut timestamps = Vec::with_capacity(window);
+        loop {
+            timestamps.push(header.timestamp());
+            if timestamps.len() == window || header.number() == 0 {
+                break;
+            }
+            header = self.get_block_header(&header.parent_hash())?;
+        }
+        timestamps.sort();
+        Some(timestamps[timestamps.len() >> 1])
+    }
+
+    /// Count how many blocks from `old_tip` back to the main chain have been detached
+    ///
+    /// Return 0 if `old_tip` is still on the main chain, and None if the old tip or one of
+    /// its ancestors is no longer in the store
+    fn reorg_depth(&'a self, old_tip: &packed::Byte32) -> Option<u64> {
+        let mut header = self.get_block_header(old_tip)?;
+        let mut depth = 0;
+        while self.get_block_hash(header.number()).as_ref() != Some(&header.hash()) {
+            header = self.get_block_header(&header.parent_hash())?;
+            depth += 1;
+        }
+        Some(depth)
+    }
+
     fn get_tip_header(&'a self) -> Option<HeaderView> {
         self.get(COLUMN_META, META_TIP_HEADER_KEY)
             .and_then(|raw| {
@@ -194,6 +667,16 @@ pub trait ChainStore<'a>: Send + Sync {
             .map(Into::into)
     }
 
+    /// Get the header of the finalized block, the main chain can't be reorganized at or
+    /// below it. Return None if nothing has been finalized yet
+    fn get_finalized_header(&'a self) -> Option<HeaderView> {
+        self.get(COLUMN_META, META_FINALIZED_KEY).and_then(|raw| {
+            self.get_block_header(
+                &packed::Byte32Reader::from_slice_should_be_ok(&raw.as_ref()[..]).to_entity(),
+            )
+        })
+    }
+
     /// Get commit transaction and block hash by it's hash
     fn get_transaction(
         &'a self,
@@ -211,6 +694,14 @@ pub trait ChainStore<'a>: Send + Sync {
         })
     }
 
+    /// Check whether a transaction is on the main chain by its transaction info key
+    ///
+    /// It is still a full pinned get of the value, it only skips decoding the value and
+    /// loading the transaction itself.
+    fn transaction_exists(&'a self, hash: &packed::Byte32) -> bool {
+        self.get(COLUMN_TRANSACTION_INFO, hash.as_slice()).is_some()
+    }
+
     fn get_transaction_info_packed(
         &'a self,
         hash: &packed::Byte32,
@@ -232,12 +723,145 @@ pub trait ChainStore<'a>: Send + Sync {
             })
     }
 
+    /// Get transaction infos of many transactions, the result is in the same order as
+    /// `hashes` and unknown transactions are None
+    fn get_transaction_infos(&'a self, hashes: &[packed::Byte32]) -> Vec<Option<TransactionInfo>> {
+        hashes
+            .iter()
+            .map(|hash| self.get_transaction_info(hash))
+            .collect()
+    }
+
+    /// Get transaction infos of all transactions in block body by block header hash
+    ///
+    /// Stale infos which point to another block are skipped
+    fn get_block_transaction_infos(&'a self, block_hash: &packed::Byte32) -> Vec<TransactionInfo> {
+        self.get_block_txs_hashes(block_hash)
+            .iter()
+            .filter_map(|tx_hash| self.get_transaction_info(tx_hash))
+            .filter(|info| &info.block_hash == block_hash)
+            .collect()
+    }
+
+    /// Same as `get_block_transaction_infos`, and check that the stored index of each info
+    /// is the position of its transaction in block body, so the infos are in index order
+    ///
+    /// Return a DataCorrupted error at the first info with a mismatched index
+    fn transaction_infos_ordered(
+        &'a self,
+        block_hash: &packed::Byte32,
+    ) -> Result<Vec<TransactionInfo>, Error> {
+        let mut infos = Vec::new();
+        for (index, tx_hash) in self.get_block_txs_hashes(block_hash).iter().enumerate() {
+            let info = match self.get_transaction_info(tx_hash) {
+                Some(info) if &info.block_hash == block_hash => info,
+                _ => continue,
+            };
+            if info.index != index {
+                Err(InternalErrorKind::DataCorrupted.reason(format!(
+                    "transaction {} is at {} of block {} but its info says {}",
+                    tx_hash, index, block_hash, info.index
+                )))?;
+            }
+            infos.push(info);
+        }
+        Ok(infos)
+    }
+
     fn get_tx_meta(&'a self, tx_hash: &packed::Byte32) -> Option<TransactionMeta> {
-        self.get(COLUMN_CELL_SET, tx_hash.as_slice()).map(|slice| {
-            packed::TransactionMetaReader::from_slice_should_be_ok(&slice.as_ref()).unpack()
+        let fill = match self.tx_meta_cache(tx_hash) {
+            Some((cache, pinned)) => match cache.get(tx_hash, pinned) {
+                Ok(meta) => return Some(meta),
+                Err(generation) => generation.map(|generation| (cache, generation)),
+            },
+            None => None,
+        };
+        let meta: Option<TransactionMeta> =
+            self.get(COLUMN_CELL_SET, tx_hash.as_slice()).map(|slice| {
+                packed::TransactionMetaReader::from_slice_should_be_ok(&slice.as_ref()).unpack()
+            });
+        if let (Some((cache, generation)), Some(meta)) = (fill, meta.as_ref()) {
+            cache.fill(generation, tx_hash.clone(), meta.clone());
+        }
+        meta
+    }
+
+    /// Get the transaction hash and the input index spending the cell on the main chain
+    ///
+    /// Always None unless the store is opened with `cell_spender_index` enabled
+    fn get_cell_spender(&'a self, out_point: &packed::OutPoint) -> Option<(packed::Byte32, u32)> {
+        self.get(COLUMN_CELL_SPENDER, out_point.as_slice())
+            .map(|slice| {
+                let reader = packed::OutPointReader::from_slice_should_be_ok(&slice.as_ref());
+                (reader.tx_hash().to_entity(), reader.index().unpack())
+            })
+    }
+
+    /// Get the cell set snapshot stored by `StoreTransaction::insert_cell_set_snapshot`
+    /// nearest at or below the main chain block `number`, along with the number it's
+    /// taken at
+    ///
+    /// The snapshots of the blocks no longer on the main chain are skipped.
+    fn get_cell_set_snapshot(
+        &'a self,
+        number: BlockNumber,
+    ) -> Option<(BlockNumber, Vec<(packed::Byte32, TransactionMeta)>)> {
+        let mut upper = number;
+        let anchor = loop {
+            let marker = cell_set_snapshot_marker(upper);
+            let (key, value) = self
+                .get_iter(COLUMN_CELL_SET_SNAPSHOT, &marker, Direction::Reverse)
+                .next()?;
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(&key[..8]);
+            let anchor = BlockNumber::from_be_bytes(buf);
+            // the entries of a snapshot are met if it has no marker
+            if key.len() == marker.len()
+                && self
+                    .get_block_hash(anchor)
+                    .as_ref()
+                    .map(|hash| hash.as_slice())
+                    == Some(&value[..])
+            {
+                break anchor;
+            }
+            if anchor == 0 {
+                return None;
+            }
+            upper = anchor - 1;
+        };
+        let prefix = anchor.to_be_bytes();
+        let entries = self
+            .get_iter(COLUMN_CELL_SET_SNAPSHOT, &prefix, Direction::Forward)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .filter(|(key, _)| key.len() == prefix.len() + 32)
+            .map(|(key, value)| {
+                let tx_hash =
+                    packed::Byte32Reader::from_slice_should_be_ok(&key[prefix.len()..]).to_entity();
+                let meta = packed::TransactionMetaReader::from_slice_should_be_ok(&value).unpack();
+                (tx_hash, meta)
+            })
+            .collect();
+        Some((anchor, entries))
+    }
+
+    /// Get the spent status of every output of a transaction, `true` means the output is dead
+    ///
+    /// Return None if the transaction is not in the cell set, note that a transaction is
+    /// removed from the cell set once all its outputs are dead
+    fn get_tx_output_statuses(&'a self, tx_hash: &packed::Byte32) -> Option<Vec<bool>> {
+        self.get_tx_meta(tx_hash).map(|meta| {
+            (0..meta.len())
+                .map(|index| meta.is_dead(index).unwrap_or(false))
+                .collect()
         })
     }
 
+    /// Get the meta of a cell, decoded from the stored transaction located by its
+    /// transaction info
+    ///
+    /// No cell meta is stored apart from the block bodies, so attaching a block writes
+    /// nothing for it.
     fn get_cell_meta(&'a self, tx_hash: &packed::Byte32, index: u32) -> Option<CellMeta> {
         self.get_transaction_info_packed(&tx_hash)
             .and_then(|tx_info| {
@@ -260,20 +884,75 @@ pub trait ChainStore<'a>: Send + Sync {
                             .expect("inconsistent index")
                             .raw_data()
                             .len() as u64;
-                        let out_point = packed::OutPoint::new_builder()
-                            .tx_hash(tx_hash.to_owned())
-                            .index(index.pack())
-                            .build();
-                        // notice mem_cell_data is set to None, the cell data should be load in need
-                        CellMeta {
-                            cell_output,
-                            out_point,
-                            transaction_info: Some(tx_info.unpack()),
-                            data_bytes,
-                            mem_cell_data: None,
-                        }
+                        build_cell_meta(tx_hash, index, cell_output, data_bytes, &tx_info)
+                    })
+            })
+    }
+
+    /// Get the cell meta along with the header of the block containing the transaction
+    /// which creates the cell, as needed to resolve header deps
+    ///
+    /// Return None if the cell or its transaction info is missing
+    fn get_cell_meta_and_header(
+        &'a self,
+        tx_hash: &packed::Byte32,
+        index: u32,
+    ) -> Option<(CellMeta, HeaderView)> {
+        let cell_meta = self.get_cell_meta(tx_hash, index)?;
+        let header = cell_meta
+            .transaction_info
+            .as_ref()
+            .and_then(|info| self.get_block_header(&info.block_hash))?;
+        Some((cell_meta, header))
+    }
+
+    /// Get the capacity of a cell, only the capacity field of the output is decoded
+    ///
+    /// No cell meta is stored, so it's read from the stored transaction. Return None if the
+    /// transaction is not on the main chain or it has no such output
+    fn get_cell_capacity(&'a self, tx_hash: &packed::Byte32, index: u32) -> Option<Capacity> {
+        self.get_transaction_info_packed(&tx_hash)
+            .and_then(|tx_info| self.get(COLUMN_BLOCK_BODY, tx_info.key().as_slice()))
+            .and_then(|slice| {
+                let reader =
+                    packed::TransactionViewReader::from_slice_should_be_ok(&slice.as_ref());
+                reader
+                    .data()
+                    .raw()
+                    .outputs()
+                    .get(index as usize)
+                    .map(|output| output.capacity().unpack())
+            })
+    }
+
+    /// Get cell metas of all outputs of a transaction, the transaction is decoded only once
+    fn cell_metas_of_tx(&'a self, tx_hash: &packed::Byte32) -> Vec<(u32, CellMeta)> {
+        self.get_transaction_info_packed(&tx_hash)
+            .and_then(|tx_info| {
+                self.get(COLUMN_BLOCK_BODY, tx_info.key().as_slice())
+                    .map(|slice| {
+                        let reader =
+                            packed::TransactionViewReader::from_slice_should_be_ok(&slice.as_ref());
+                        let raw = reader.data().raw();
+                        raw.outputs()
+                            .iter()
+                            .zip(raw.outputs_data().iter())
+                            .enumerate()
+                            .map(|(index, (cell_output, data))| {
+                                let index = index as u32;
+                                let cell_meta = build_cell_meta(
+                                    tx_hash,
+                                    index,
+                                    cell_output.to_entity(),
+                                    data.raw_data().len() as u64,
+                                    &tx_info,
+                                );
+                                (index, cell_meta)
+                            })
+                            .collect()
                     })
             })
+            .unwrap_or_default()
     }
 
     fn get_cell_data(
@@ -348,15 +1027,55 @@ pub trait ChainStore<'a>: Send + Sync {
             .map(|raw| packed::Byte32Reader::from_slice_should_be_ok(&raw.as_ref()).to_entity())
     }
 
+    /// Get the epoch which the block belongs to
+    ///
+    /// Return None if the block has no epoch index, a warning is logged if the index points
+    /// to a missing epoch since that means the data is corrupted
     fn get_block_epoch(&'a self, hash: &packed::Byte32) -> Option<EpochExt> {
-        self.get_block_epoch_index(hash)
-            .and_then(|index| self.get_epoch_ext(&index))
+        self.get_block_epoch_index(hash).and_then(|index| {
+            let epoch = self.get_epoch_ext(&index);
+            if epoch.is_none() {
+                warn!(
+                    "DataCorrupted: block {} refers to missing epoch {}",
+                    hash, index
+                );
+            }
+            epoch
+        })
     }
 
+    /// Get the epoch which the main chain block at `number` belongs to
+    ///
+    /// Return None if the number is not indexed, e.g. above the tip, a warning is logged if
+    /// an indexed block has no epoch index since that means the data is corrupted
+    fn get_block_epoch_by_number(&'a self, number: BlockNumber) -> Option<EpochExt> {
+        self.get_block_hash(number).and_then(|hash| {
+            if self.get_block_epoch_index(&hash).is_none() {
+                warn!(
+                    "DataCorrupted: block {} at {} has no epoch index",
+                    hash, number
+                );
+                return None;
+            }
+            self.get_block_epoch(&hash)
+        })
+    }
+
+    /// Check whether the hash has been embedded as an uncle by a main chain block
+    ///
+    /// The record is removed when the embedding block is detached
     fn is_uncle(&'a self, hash: &packed::Byte32) -> bool {
         self.get(COLUMN_UNCLES, hash.as_slice()).is_some()
     }
 
+    /// Iterate the hashes of all uncles embedded by main chain blocks, in key order
+    fn uncles_iter(&'a self) -> Box<Iterator<Item = packed::Byte32> + 'a> {
+        Box::new(
+            self.get_iter(COLUMN_UNCLES, &[], Direction::Forward)
+                .map(|(key, _)| packed::Byte32Reader::from_slice_should_be_ok(&key).to_entity()),
+        )
+    }
+
     /// Get header by uncle header hash
     fn get_uncle_header(&'a self, hash: &packed::Byte32) -> Option<HeaderView> {
         self.get(COLUMN_UNCLES, hash.as_slice()).map(|slice| {
@@ -365,6 +1084,10 @@ pub trait ChainStore<'a>: Send + Sync {
         })
     }
 
+    /// Check whether the block header is stored, the header is never decoded
+    ///
+    /// The header cache is tried first, then a pinned get. The get still reads the value
+    /// from the database, it only saves copying and decoding it.
     fn block_exists(&'a self, hash: &packed::Byte32) -> bool {
         if let Some(cache) = self.cache() {
             if cache.headers.lock().get_refresh(hash).is_some() {
@@ -374,7 +1097,9 @@ pub trait ChainStore<'a>: Send + Sync {
         self.get(COLUMN_BLOCK_HEADER, hash.as_slice()).is_some()
     }
 
-    // Get cellbase by block hash
+    /// Get cellbase by block hash, only the first transaction of the body is decoded
+    ///
+    /// Return None if the block body is empty
     fn get_cellbase(&'a self, hash: &packed::Byte32) -> Option<TransactionView> {
         if let Some(cache) = self.cache() {
             if let Some(data) = cache.cellbase.lock().get_refresh(hash) {
@@ -412,3 +1137,67 @@ pub trait ChainStore<'a>: Send + Sync {
         )
     }
 }
+
+/// An inconsistency found by `ChainStore::check_chain_links`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkError {
+    /// No block is indexed at the number
+    Gap { number: BlockNumber },
+    /// The block indexed at the number has no stored header
+    MissingHeader {
+        number: BlockNumber,
+        hash: packed::Byte32,
+    },
+    /// The header number or the reverse index of the block disagrees with the number
+    IndexMismatch {
+        number: BlockNumber,
+        hash: packed::Byte32,
+    },
+    /// The parent of the block is not stored
+    MissingParent {
+        hash: packed::Byte32,
+        parent: packed::Byte32,
+    },
+    /// The parent of the block is stored but not indexed at the previous number
+    ParentMismatch {
+        number: BlockNumber,
+        hash: packed::Byte32,
+        parent: packed::Byte32,
+    },
+}
+
+fn build_cell_meta(
+    tx_hash: &packed::Byte32,
+    index: u32,
+    cell_output: packed::CellOutput,
+    data_bytes: u64,
+    tx_info: &packed::TransactionInfo,
+) -> CellMeta {
+    let out_point = packed::OutPoint::new_builder()
+        .tx_hash(tx_hash.to_owned())
+        .index(index.pack())
+        .build();
+    // notice mem_cell_data is set to None, the cell data should be load in need
+    CellMeta {
+        cell_output,
+        out_point,
+        transaction_info: Some(tx_info.unpack()),
+        data_bytes,
+        mem_cell_data: None,
+    }
+}
+
+/// The blake2b hash of all serialized transactions of block body in order
+pub(crate) fn block_body_checksum<T, I>(txs: I) -> [u8; 32]
+where
+    T: AsRef<[u8]>,
+    I: IntoIterator<Item = T>,
+{
+    let mut result = [0u8; 32];
+    let mut blake2b = new_blake2b();
+    for tx in txs {
+        blake2b.update(tx.as_ref());
+    }
+    blake2b.finalize(&mut result);
+    result
+}
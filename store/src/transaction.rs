@@ -1,26 +1,47 @@
-use crate::cache::StoreCache;
-use crate::store::ChainStore;
+use crate::cache::{StoreCache, TxMetaCache};
+use crate::metrics::Counters;
+use crate::observer::{CellSetChange, CommittedChanges, Observers};
+use crate::store::{block_body_checksum, ChainStore};
+use crate::{cell_set_snapshot_marker, check_column, prune_candidate_key, StoreSnapshot};
 use crate::{
-    COLUMN_BLOCK_BODY, COLUMN_BLOCK_EPOCH, COLUMN_BLOCK_EXT, COLUMN_BLOCK_HEADER,
-    COLUMN_BLOCK_PROPOSAL_IDS, COLUMN_BLOCK_UNCLE, COLUMN_CELL_SET, COLUMN_EPOCH, COLUMN_INDEX,
-    COLUMN_META, COLUMN_TRANSACTION_INFO, COLUMN_UNCLES, META_CURRENT_EPOCH_KEY,
-    META_TIP_HEADER_KEY,
+    COLUMN_BLOCK_BODY, COLUMN_BLOCK_BODY_CHECKSUM, COLUMN_BLOCK_CHILDREN, COLUMN_BLOCK_EPOCH,
+    COLUMN_BLOCK_EXT, COLUMN_BLOCK_HEADER, COLUMN_BLOCK_PROPOSAL_IDS,
+    COLUMN_BLOCK_TOTAL_DIFFICULTY, COLUMN_BLOCK_UNCLE, COLUMN_CELL_SET, COLUMN_CELL_SET_SNAPSHOT,
+    COLUMN_CELL_SPENDER, COLUMN_EPOCH, COLUMN_HEADER_INDEX, COLUMN_INDEX, COLUMN_META,
+    COLUMN_PRUNE_CANDIDATE, COLUMN_QUARANTINE, COLUMN_TRANSACTION_INFO, COLUMN_UNCLES,
+    META_CURRENT_EPOCH_KEY, META_FINALIZED_KEY, META_TIP_HEADER_KEY,
 };
 use ckb_db::{
     iter::{DBIterator, DBIteratorItem},
-    Col, DBVector, Direction, RocksDBTransaction, RocksDBTransactionSnapshot,
+    Col, DBVector, Direction, RetryPolicy, RocksDBTransaction, RocksDBTransactionSnapshot,
 };
-use ckb_error::Error;
+use ckb_error::{Error, InternalErrorKind};
 use ckb_types::{
-    core::{BlockExt, BlockView, EpochExt, HeaderView},
+    core::{BlockExt, BlockView, EpochExt, HeaderView, TransactionMeta},
     packed,
     prelude::*,
+    utilities::merkle_root,
 };
+use ckb_util::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::slice;
 use std::sync::Arc;
 
 pub struct StoreTransaction {
     pub(crate) inner: RocksDBTransaction,
     pub(crate) cache: Arc<StoreCache>,
+    pub(crate) cell_spender_index: bool,
+    pub(crate) max_block_body_bytes: Option<usize>,
+    pub(crate) track_prunable_bodies: bool,
+    pub(crate) read_retry: RetryPolicy,
+    // the malformed values met by the checked reads of the store, written on commit
+    pub(crate) quarantined: Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>,
+    // the cell set entries written, to invalidate them in the cache on commit
+    pub(crate) written_tx_metas: Mutex<HashSet<packed::Byte32>>,
+    pub(crate) observers: Arc<Observers>,
+    pub(crate) metrics: Arc<Counters>,
+    // None if no observer was subscribed when the transaction began
+    pub(crate) changes: Option<Mutex<CommittedChanges>>,
 }
 
 impl<'a> ChainStore<'a> for StoreTransaction {
@@ -30,7 +51,18 @@ impl<'a> ChainStore<'a> for StoreTransaction {
         Some(&self.cache)
     }
 
+    // an entry written by the transaction is not the committed one
+    fn tx_meta_cache(&'a self, tx_hash: &packed::Byte32) -> Option<(&'a TxMetaCache, Option<u64>)> {
+        self.cache
+            .tx_meta
+            .as_ref()
+            .filter(|_| !self.written_tx_metas.lock().contains(tx_hash))
+            .map(|cache| (cache, None))
+    }
+
     fn get(&self, col: Col, key: &[u8]) -> Option<Self::Vector> {
+        debug_assert!(check_column(col).is_ok(), "unknown column {:?}", col);
+        self.metrics.record_get();
         self.inner.get(col, key).expect("db operation should be ok")
     }
 
@@ -49,6 +81,10 @@ impl<'a> ChainStore<'a> for StoreTransaction {
 pub struct StoreTransactionSnapshot<'a> {
     pub(crate) inner: RocksDBTransactionSnapshot<'a>,
     pub(crate) cache: Arc<StoreCache>,
+    pub(crate) metrics: Arc<Counters>,
+    pub(crate) read_retry: RetryPolicy,
+    // the keys got through the snapshot, only tracked for `StoreTransaction::commit_if`
+    pub(crate) read_keys: Option<Mutex<Vec<(Col, Vec<u8>)>>>,
 }
 
 impl<'a> ChainStore<'a> for StoreTransactionSnapshot<'a> {
@@ -59,7 +95,14 @@ impl<'a> ChainStore<'a> for StoreTransactionSnapshot<'a> {
     }
 
     fn get(&self, col: Col, key: &[u8]) -> Option<Self::Vector> {
-        self.inner.get(col, key).expect("db operation should be ok")
+        debug_assert!(check_column(col).is_ok(), "unknown column {:?}", col);
+        self.metrics.record_get();
+        if let Some(read_keys) = self.read_keys.as_ref() {
+            read_keys.lock().push((col, key.to_vec()));
+        }
+        self.inner
+            .get_with_retry(col, key, &self.read_retry)
+            .expect("db operation should be ok")
     }
 
     fn get_iter<'i>(
@@ -74,26 +117,124 @@ impl<'a> ChainStore<'a> for StoreTransactionSnapshot<'a> {
     }
 }
 
+impl<'a> StoreTransactionSnapshot<'a> {
+    /// Read the raw value without panicking, the transient rocksdb errors are retried by
+    /// `StoreConfig::read_retry` and any other error is returned as
+    /// `InternalErrorKind::Database`
+    pub fn try_get(&self, col: Col, key: &[u8]) -> Result<Option<DBVector>, Error> {
+        check_column(col)?;
+        self.metrics.record_get();
+        self.inner.get_with_retry(col, key, &self.read_retry)
+    }
+}
+
 impl StoreTransaction {
     pub fn insert_raw(&self, col: Col, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        check_column(col)?;
+        self.metrics.record_insert();
         self.inner.put(col, key, value)
     }
 
     pub fn delete(&self, col: Col, key: &[u8]) -> Result<(), Error> {
+        check_column(col)?;
+        self.metrics.record_delete();
         self.inner.delete(col, key)
     }
 
+    /// Write a packed entity, the encoded value is only verified in debug builds since an
+    /// entity produced by the builders is always well formed
+    pub fn insert_entity<T: Entity>(&self, col: Col, key: &[u8], value: &T) -> Result<(), Error> {
+        debug_assert!(
+            T::from_slice(value.as_slice()).is_ok(),
+            "malformed value for column {}",
+            col
+        );
+        self.insert_raw(col, key, value.as_slice())
+    }
+
     pub fn commit(&self) -> Result<(), Error> {
-        self.inner.commit()
+        // taken out so no concurrent transaction writes them too, put back if not committed
+        let quarantined: Vec<_> = self.quarantined.lock().drain().collect();
+        let committed = quarantined
+            .iter()
+            .map(|(key, value)| self.insert_raw(COLUMN_QUARANTINE, key, value))
+            .collect::<Result<(), Error>>()
+            .and_then(|()| match self.cache.tx_meta.as_ref() {
+                Some(cache) => {
+                    let written = self.written_tx_metas.lock();
+                    cache.commit(&*written, || self.inner.commit())
+                }
+                None => self.inner.commit(),
+            });
+        if committed.is_err() && !quarantined.is_empty() {
+            let mut pending = self.quarantined.lock();
+            for (key, value) in quarantined {
+                pending.entry(key).or_insert(value);
+            }
+        }
+        committed?;
+        if let Some(changes) = self.changes.as_ref() {
+            let changes = changes.lock();
+            if !changes.is_empty() {
+                self.observers.notify(&changes);
+            }
+        }
+        Ok(())
+    }
+
+    /// Commit only if `predicate` accepts the state the transaction would commit, e.g. to
+    /// enforce that the tip only moves forward
+    ///
+    /// The predicate reads a snapshot which sees the pending writes on top of the committed
+    /// data. Return false without committing if it rejects, the pending writes are kept and
+    /// discarded when the transaction is dropped.
+    ///
+    /// The keys the predicate gets are read for update like in `compare_and_set_tip`, so
+    /// the commit fails if another transaction changed any of them since this one began.
+    /// The keys it reads by iterating are not tracked.
+    pub fn commit_if<F>(&self, predicate: F) -> Result<bool, Error>
+    where
+        F: FnOnce(&StoreTransactionSnapshot<'_>) -> bool,
+    {
+        {
+            let snapshot = StoreTransactionSnapshot {
+                read_keys: Some(Mutex::new(Vec::new())),
+                ..self.get_snapshot()
+            };
+            if !predicate(&snapshot) {
+                return Ok(false);
+            }
+            let read_keys = snapshot.read_keys.as_ref().expect("tracked above").lock();
+            for (col, key) in read_keys.iter() {
+                self.inner.get_for_update(col, key, &snapshot.inner)?;
+            }
+        }
+        self.commit()?;
+        Ok(true)
     }
 
     pub fn get_snapshot(&self) -> StoreTransactionSnapshot<'_> {
         StoreTransactionSnapshot {
             inner: self.inner.get_snapshot(),
             cache: Arc::clone(&self.cache),
+            metrics: Arc::clone(&self.metrics),
+            read_retry: self.read_retry,
+            read_keys: None,
         }
     }
 
+    /// Take an owned snapshot of the committed data, it can be shared between threads
+    /// by `Arc` and used after the transaction is gone
+    ///
+    /// The snapshot doesn't see the uncommitted writes of this transaction. Rocksdb keeps
+    /// every version of data visible to a snapshot until it's dropped, so holding one for a
+    /// long time increases memory and disk usage, drop it once the work is done.
+    pub fn get_owned_snapshot(&self) -> StoreSnapshot {
+        StoreSnapshot::new(Arc::clone(&self.cache), Arc::clone(&self.metrics), || {
+            self.inner.get_db_snapshot()
+        })
+    }
+
     pub fn get_update_for_tip_hash(
         &self,
         snapshot: &StoreTransactionSnapshot<'_>,
@@ -110,26 +251,159 @@ impl StoreTransaction {
         self.insert_raw(COLUMN_META, META_TIP_HEADER_KEY, h.hash().as_slice())
     }
 
+    /// Write the new tip header only if the stored tip hash equals `expected`
+    ///
+    /// Return false without writing if the stored tip has been changed by someone else
+    pub fn compare_and_set_tip(
+        &self,
+        expected: Option<&packed::Byte32>,
+        new: &HeaderView,
+    ) -> Result<bool, Error> {
+        let snapshot = self.get_snapshot();
+        let current = self.get_update_for_tip_hash(&snapshot);
+        if current.as_ref() != expected {
+            return Ok(false);
+        }
+        self.insert_tip_header(new)?;
+        Ok(true)
+    }
+
+    /// Write the tip header and the current epoch ext if it's given, both are committed
+    /// with the transaction, so the store never holds one of them without the other
+    pub fn advance_tip(&self, tip: &HeaderView, epoch: Option<&EpochExt>) -> Result<(), Error> {
+        if let Some(epoch) = epoch {
+            self.insert_current_epoch_ext(epoch)?;
+        }
+        self.insert_tip_header(tip)
+    }
+
+    /// Write the block, refuse it with an `ExceededMaximumBlockBytes` error if its
+    /// transactions exceed `StoreConfig::max_block_body_bytes`
     pub fn insert_block(&self, block: &BlockView) -> Result<(), Error> {
+        self.write_block_parts(block)?;
         let hash = block.hash();
         let header = block.header().pack();
+        self.insert_entity(COLUMN_BLOCK_HEADER, hash.as_slice(), &header)?;
+        self.insert_raw(COLUMN_HEADER_INDEX, &header_index_key(&block.header()), &[])?;
+        self.insert_raw(COLUMN_BLOCK_CHILDREN, &child_key(&block.header()), &[])
+    }
+
+    /// Write the headers for header-first sync, `get_block` returns None until the rest of
+    /// the block is written by `insert_block_body`
+    ///
+    /// The headers are indexed by number in the header index, see `get_header_hashes`, the
+    /// main chain index is only written when the block is attached.
+    pub fn insert_headers(&self, headers: &[HeaderView]) -> Result<(), Error> {
+        for header in headers {
+            let hash = header.hash();
+            self.insert_entity(COLUMN_BLOCK_HEADER, hash.as_slice(), &header.pack())?;
+            self.insert_raw(COLUMN_HEADER_INDEX, &header_index_key(header), &[])?;
+            self.insert_raw(COLUMN_BLOCK_CHILDREN, &child_key(header), &[])?;
+        }
+        Ok(())
+    }
+
+    /// Write the uncles, the proposals and the transactions of a block whose header is
+    /// already stored, e.g. by `insert_headers`
+    ///
+    /// Return `DataCorrupted` error if the header is missing or the body doesn't match its
+    /// transactions root, uncles hash or proposals hash, and `ExceededMaximumBlockBytes`
+    /// error like `insert_block` does
+    pub fn insert_block_body(&self, block: &BlockView) -> Result<(), Error> {
+        let hash = block.hash();
+        let header = match self.get_block_header(&hash) {
+            Some(header) => header,
+            None => Err(InternalErrorKind::DataCorrupted.reason(format!(
+                "failed to insert the body of block {}: header not stored",
+                hash
+            )))?,
+        };
+        let mismatch = if merkle_root(block.tx_hashes()) != header.transactions_root() {
+            Some("transactions root")
+        } else if block.data().calc_uncles_hash() != header.uncles_hash() {
+            Some("uncles hash")
+        } else if block.data().calc_proposals_hash() != header.proposals_hash() {
+            Some("proposals hash")
+        } else {
+            None
+        };
+        if let Some(field) = mismatch {
+            Err(InternalErrorKind::DataCorrupted.reason(format!(
+                "failed to insert the body of block {}: {} mismatch",
+                hash, field
+            )))?;
+        }
+        self.write_block_parts(block)
+    }
+
+    // Write everything of the block but the header, the body is written first so that an
+    // oversized block is refused before anything is written
+    fn write_block_parts(&self, block: &BlockView) -> Result<(), Error> {
+        self.write_block_body(block)?;
+        let hash = block.hash();
         let uncles = block.uncles().pack();
         let proposals = block.data().proposals();
-        self.insert_raw(COLUMN_BLOCK_HEADER, hash.as_slice(), header.as_slice())?;
-        self.insert_raw(COLUMN_BLOCK_UNCLE, hash.as_slice(), uncles.as_slice())?;
+        self.insert_entity(COLUMN_BLOCK_UNCLE, hash.as_slice(), &uncles)?;
+        self.insert_entity(COLUMN_BLOCK_PROPOSAL_IDS, hash.as_slice(), &proposals)
+    }
+
+    // Every body write goes through here, so it's where the body size limit is enforced
+    fn write_block_body(&self, block: &BlockView) -> Result<(), Error> {
+        let hash = block.hash();
+        if let Some(max) = self.max_block_body_bytes {
+            let size = block.data().transactions().as_slice().len();
+            if size > max {
+                Err(InternalErrorKind::ExceededMaximumBlockBytes.reason(format!(
+                    "the body of block {} is {} bytes, the maximum is {}",
+                    hash, size, max
+                )))?;
+            }
+        }
+        let txs_data: Vec<_> = block.transactions().iter().map(|tx| tx.pack()).collect();
+        for (index, tx_data) in txs_data.iter().enumerate() {
+            let key = packed::TransactionKey::new_builder()
+                .block_hash(hash.clone())
+                .index(index.pack())
+                .build();
+            self.insert_entity(COLUMN_BLOCK_BODY, key.as_slice(), tx_data)?;
+        }
+        // the checksum is followed by the stored size of the body, see `get_block_body_size`
+        let checksum = block_body_checksum(txs_data.iter().map(|tx_data| tx_data.as_slice()));
+        let size: usize = txs_data
+            .iter()
+            .map(|tx_data| tx_data.as_slice().len())
+            .sum();
+        let size: packed::Uint64 = (size as u64).pack();
         self.insert_raw(
-            COLUMN_BLOCK_PROPOSAL_IDS,
+            COLUMN_BLOCK_BODY_CHECKSUM,
             hash.as_slice(),
-            proposals.as_slice(),
-        )?;
-        for (index, tx) in block.transactions().into_iter().enumerate() {
+            &[&checksum[..], size.as_slice()].concat(),
+        )
+    }
+
+    /// Remove what `insert_block` writes, the block ext and the main chain index are kept,
+    /// detach the block first if it's on the main chain
+    pub fn delete_block(&self, block: &BlockView) -> Result<(), Error> {
+        let hash = block.hash();
+        self.delete(COLUMN_BLOCK_HEADER, hash.as_slice())?;
+        self.delete(COLUMN_BLOCK_UNCLE, hash.as_slice())?;
+        self.delete(COLUMN_BLOCK_PROPOSAL_IDS, hash.as_slice())?;
+        for index in 0..block.transactions().len() {
             let key = packed::TransactionKey::new_builder()
                 .block_hash(hash.clone())
                 .index(index.pack())
                 .build();
-            let tx_data = tx.pack();
-            self.insert_raw(COLUMN_BLOCK_BODY, key.as_slice(), tx_data.as_slice())?;
+            self.delete(COLUMN_BLOCK_BODY, key.as_slice())?;
         }
+        self.delete(COLUMN_BLOCK_BODY_CHECKSUM, hash.as_slice())?;
+        self.delete(COLUMN_BLOCK_CHILDREN, &child_key(&block.header()))?;
+        self.delete(COLUMN_HEADER_INDEX, &header_index_key(&block.header()))?;
+
+        self.cache.headers.lock().remove(&hash);
+        self.cache.block_proposals.lock().remove(&hash);
+        self.cache.block_tx_hashes.lock().remove(&hash);
+        self.cache.block_uncles.lock().remove(&hash);
+        self.cache.cellbase.lock().remove(&hash);
         Ok(())
     }
 
@@ -138,13 +412,50 @@ impl StoreTransaction {
         block_hash: &packed::Byte32,
         ext: &BlockExt,
     ) -> Result<(), Error> {
-        self.insert_raw(
-            COLUMN_BLOCK_EXT,
+        self.insert_entity(COLUMN_BLOCK_EXT, block_hash.as_slice(), &ext.pack())?;
+        self.insert_entity(
+            COLUMN_BLOCK_TOTAL_DIFFICULTY,
             block_hash.as_slice(),
-            ext.pack().as_slice(),
+            &ext.total_difficulty.pack(),
         )
     }
 
+    /// Update the verified flag of a stored block ext, the other fields are kept byte for
+    /// byte
+    ///
+    /// `verified` is the last field of the ext, so a flag already set is the last byte and
+    /// it's overwritten in place, otherwise the ext is rebuilt from its undecoded fields.
+    /// It's a read-modify-write since merge is not available on the transaction yet.
+    pub fn set_block_verified(
+        &self,
+        block_hash: &packed::Byte32,
+        verified: bool,
+    ) -> Result<(), Error> {
+        let mut raw = match self.get(COLUMN_BLOCK_EXT, block_hash.as_slice()) {
+            Some(slice) => slice.as_ref().to_vec(),
+            None => Err(InternalErrorKind::DataCorrupted.reason(format!(
+                "failed to set the verified flag of block {}: its ext is not stored",
+                block_hash
+            )))?,
+        };
+        if packed::BlockExtReader::from_slice_should_be_ok(&raw)
+            .verified()
+            .is_some()
+        {
+            if let Some(flag) = raw.last_mut() {
+                *flag = verified as u8;
+            }
+        } else {
+            raw = packed::BlockExt::new_unchecked(raw.into())
+                .as_builder()
+                .verified(Some(verified).pack())
+                .build()
+                .as_slice()
+                .to_vec();
+        }
+        self.insert_raw(COLUMN_BLOCK_EXT, block_hash.as_slice(), &raw)
+    }
+
     pub fn attach_block(&self, block: &BlockView) -> Result<(), Error> {
         let header = block.data().header();
         let block_hash = block.hash();
@@ -165,14 +476,41 @@ impl StoreTransaction {
         for uncle in block.uncles().into_iter() {
             self.insert_raw(
                 COLUMN_UNCLES,
-                &uncle.hash().as_slice(),
-                &uncle.header().pack().as_slice(),
+                uncle.hash().as_slice(),
+                uncle.header().pack().as_slice(),
             )?;
         }
-        self.insert_raw(COLUMN_INDEX, block_hash.as_slice(), block_number.as_slice())
+        self.insert_raw(COLUMN_INDEX, block_hash.as_slice(), block_number.as_slice())?;
+        self.record(|changes| changes.attached.push(block.clone()));
+        Ok(())
+    }
+
+    /// Mark the block as finalized, refuse a block which is not the main chain block at
+    /// its number, so an orphaned block is never finalized
+    pub fn set_finalized(&self, h: &HeaderView) -> Result<(), Error> {
+        let main_hash = self.get_block_hash(h.number());
+        if main_hash.as_ref() != Some(&h.hash()) {
+            Err(InternalErrorKind::System.reason(format!(
+                "failed to finalize block {}: the main chain block at {} is {}",
+                h.hash(),
+                h.number(),
+                main_hash.map_or_else(|| "missing".to_owned(), |hash| hash.to_string())
+            )))?;
+        }
+        self.insert_raw(COLUMN_META, META_FINALIZED_KEY, h.hash().as_slice())
     }
 
+    /// Detach a main chain block, refuse to detach the finalized block or its ancestors
     pub fn detach_block(&self, block: &BlockView) -> Result<(), Error> {
+        if let Some(finalized) = self.get_finalized_header() {
+            if block.number() <= finalized.number() {
+                Err(InternalErrorKind::System.reason(format!(
+                    "failed to detach block {}: not above the finalized block {}",
+                    block.hash(),
+                    finalized.hash()
+                )))?;
+            }
+        }
         for tx_hash in block.tx_hashes().iter() {
             self.delete(COLUMN_TRANSACTION_INFO, tx_hash.as_slice())?;
         }
@@ -181,7 +519,9 @@ impl StoreTransaction {
         }
         let block_number = block.data().header().raw().number();
         self.delete(COLUMN_INDEX, block_number.as_slice())?;
-        self.delete(COLUMN_INDEX, block.hash().as_slice())
+        self.delete(COLUMN_INDEX, block.hash().as_slice())?;
+        self.record(|changes| changes.detached.push(block.clone()));
+        Ok(())
     }
 
     pub fn insert_block_epoch_index(
@@ -197,13 +537,13 @@ impl StoreTransaction {
     }
 
     pub fn insert_epoch_ext(&self, hash: &packed::Byte32, epoch: &EpochExt) -> Result<(), Error> {
-        self.insert_raw(COLUMN_EPOCH, hash.as_slice(), epoch.pack().as_slice())?;
+        self.insert_entity(COLUMN_EPOCH, hash.as_slice(), &epoch.pack())?;
         let epoch_number: packed::Uint64 = epoch.number().pack();
         self.insert_raw(COLUMN_EPOCH, epoch_number.as_slice(), hash.as_slice())
     }
 
     pub fn insert_current_epoch_ext(&self, epoch: &EpochExt) -> Result<(), Error> {
-        self.insert_raw(COLUMN_META, META_CURRENT_EPOCH_KEY, epoch.pack().as_slice())
+        self.insert_entity(COLUMN_META, META_CURRENT_EPOCH_KEY, &epoch.pack())
     }
 
     pub fn update_cell_set(
@@ -211,10 +551,137 @@ impl StoreTransaction {
         tx_hash: &packed::Byte32,
         meta: &packed::TransactionMeta,
     ) -> Result<(), Error> {
-        self.insert_raw(COLUMN_CELL_SET, tx_hash.as_slice(), meta.as_slice())
+        self.write_tx_meta(tx_hash);
+        self.insert_entity(COLUMN_CELL_SET, tx_hash.as_slice(), meta)?;
+        self.record(|changes| {
+            let change = CellSetChange::Updated(tx_hash.clone(), meta.clone());
+            changes.cell_set.push(change)
+        });
+        Ok(())
     }
 
     pub fn delete_cell_set(&self, tx_hash: &packed::Byte32) -> Result<(), Error> {
-        self.delete(COLUMN_CELL_SET, tx_hash.as_slice())
+        self.write_tx_meta(tx_hash);
+        self.record_prune_candidates(slice::from_ref(tx_hash))?;
+        self.delete(COLUMN_CELL_SET, tx_hash.as_slice())?;
+        self.record(|changes| {
+            let change = CellSetChange::Deleted(tx_hash.clone());
+            changes.cell_set.push(change)
+        });
+        Ok(())
+    }
+
+    /// Store the cell set as of the main chain block, see
+    /// `ChainStore::get_cell_set_snapshot`
+    pub fn insert_cell_set_snapshot<'i, I>(
+        &self,
+        header: &HeaderView,
+        entries: I,
+    ) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (&'i packed::Byte32, &'i TransactionMeta)>,
+    {
+        let prefix = header.number().to_be_bytes();
+        for (tx_hash, meta) in entries {
+            let key = [&prefix[..], tx_hash.as_slice()].concat();
+            self.insert_entity(COLUMN_CELL_SET_SNAPSHOT, &key, &meta.pack())?;
+        }
+        self.insert_raw(
+            COLUMN_CELL_SET_SNAPSHOT,
+            &cell_set_snapshot_marker(header.number()),
+            header.hash().as_slice(),
+        )
+    }
+
+    /// Delete the cell set entries of the transactions together, the store gets the same
+    /// deletes as calling `delete_cell_set` for each of them
+    ///
+    /// The hashes are marked as written once for all, so the cached entries are only
+    /// invalidated by the commit. The in-memory cell set is the caller's, remove the hashes
+    /// from it before.
+    pub fn delete_cell_sets(&self, tx_hashes: &[packed::Byte32]) -> Result<(), Error> {
+        if self.cache.tx_meta.is_some() {
+            self.written_tx_metas
+                .lock()
+                .extend(tx_hashes.iter().cloned());
+        }
+        self.record_prune_candidates(tx_hashes)?;
+        for tx_hash in tx_hashes {
+            self.delete(COLUMN_CELL_SET, tx_hash.as_slice())?;
+        }
+        self.record(|changes| {
+            let deleted = tx_hashes.iter().cloned().map(CellSetChange::Deleted);
+            changes.cell_set.extend(deleted)
+        });
+        Ok(())
     }
+
+    /// Index the input spending each cell of the block if the spender index is enabled
+    pub fn attach_cell_spenders(&self, block: &BlockView) -> Result<(), Error> {
+        if !self.cell_spender_index {
+            return Ok(());
+        }
+        for tx in block.transactions().iter().skip(1) {
+            for (index, out_point) in tx.input_pts_iter().enumerate() {
+                // the spender is encoded as an out point, the index is of the input
+                let spender = packed::OutPoint::new(tx.hash(), index as u32);
+                self.insert_raw(
+                    COLUMN_CELL_SPENDER,
+                    out_point.as_slice(),
+                    spender.as_slice(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn detach_cell_spenders(&self, block: &BlockView) -> Result<(), Error> {
+        if !self.cell_spender_index {
+            return Ok(());
+        }
+        for tx in block.transactions().iter().skip(1) {
+            for out_point in tx.input_pts_iter() {
+                self.delete(COLUMN_CELL_SPENDER, out_point.as_slice())?;
+            }
+        }
+        Ok(())
+    }
+
+    // The bodies kept by `ChainDB::prune_bodies` are examined again once a transaction of
+    // theirs loses its cell set entry
+    fn record_prune_candidates(&self, tx_hashes: &[packed::Byte32]) -> Result<(), Error> {
+        if !self.track_prunable_bodies {
+            return Ok(());
+        }
+        for tx_hash in tx_hashes {
+            if let Some(info) = self.get_transaction_info(tx_hash) {
+                let key = prune_candidate_key(info.block_number, tx_hash);
+                self.insert_raw(COLUMN_PRUNE_CANDIDATE, &key, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn record<F: FnOnce(&mut CommittedChanges)>(&self, f: F) {
+        if let Some(changes) = self.changes.as_ref() {
+            f(&mut changes.lock());
+        }
+    }
+
+    fn write_tx_meta(&self, tx_hash: &packed::Byte32) {
+        if self.cache.tx_meta.is_some() {
+            self.written_tx_metas.lock().insert(tx_hash.clone());
+        }
+    }
+}
+
+// The children of a block are stored under the keys prefixed by its hash
+fn child_key(header: &HeaderView) -> Vec<u8> {
+    [header.parent_hash().as_slice(), header.hash().as_slice()].concat()
+}
+
+// The headers of a number are stored under the keys prefixed by it
+fn header_index_key(header: &HeaderView) -> Vec<u8> {
+    let number: packed::Uint64 = header.number().pack();
+    [number.as_slice(), header.hash().as_slice()].concat()
 }
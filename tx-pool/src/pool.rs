@@ -112,6 +112,28 @@ impl TxPool {
         (self.total_tx_cycles + cycles) > self.config.max_cycles
     }
 
+    /// Return `TransactionPoolFull` with the current and max size if `tx_size` doesn't fit
+    pub fn check_size_limit(&self, tx_size: usize) -> Result<(), Error> {
+        if self.reach_size_limit(tx_size) {
+            Err(InternalErrorKind::TransactionPoolFull.reason(format!(
+                "size limit: total {} + tx {} > max_mem_size {}",
+                self.total_tx_size, tx_size, self.config.max_mem_size
+            )))?;
+        }
+        Ok(())
+    }
+
+    /// Return `TransactionPoolFull` with the current and max cycles if `cycles` doesn't fit
+    pub fn check_cycles_limit(&self, cycles: Cycle) -> Result<(), Error> {
+        if self.reach_cycles_limit(cycles) {
+            Err(InternalErrorKind::TransactionPoolFull.reason(format!(
+                "cycles limit: total {} + tx {} > max_cycles {}",
+                self.total_tx_cycles, cycles, self.config.max_cycles
+            )))?;
+        }
+        Ok(())
+    }
+
     pub fn update_statics_for_add_tx(&mut self, tx_size: usize, cycles: Cycle) {
         self.total_tx_size += tx_size;
         self.total_tx_cycles += cycles;
@@ -307,15 +329,11 @@ impl TxPool {
 
     pub fn add_tx_to_pool(&mut self, tx: TransactionView, cycles: Cycle) -> Result<Cycle, Error> {
         let tx_size = tx.serialized_size();
-        if self.reach_size_limit(tx_size) {
-            Err(InternalErrorKind::TransactionPoolFull)?;
-        }
+        self.check_size_limit(tx_size)?;
         let short_id = tx.proposal_short_id();
         match self.resolve_tx_from_pending_and_proposed(tx.clone()) {
             Ok(rtx) => self.verify_rtx(&rtx, Some(cycles)).and_then(|cycles| {
-                if self.reach_cycles_limit(cycles) {
-                    Err(InternalErrorKind::TransactionPoolFull)?;
-                }
+                self.check_cycles_limit(cycles)?;
                 if self.contains_proposed(&short_id) {
                     if let Err(e) = self.proposed_tx_and_descendants(Some(cycles), tx_size, tx) {
                         debug_target!(
@@ -1,6 +1,6 @@
 use crate::component::entry::TxEntry;
 use crate::pool::TxPool;
-use ckb_error::{Error, InternalErrorKind};
+use ckb_error::Error;
 use ckb_snapshot::Snapshot;
 use ckb_types::{
     core::{
@@ -195,9 +195,7 @@ impl<'a> SubmitTxsExecutor<'a> {
         let cycles_vec = txs.iter().map(|(_, cycles)| *cycles).collect();
 
         for ((rtx, cycles), (tx_size, fee, status)) in txs.into_iter().zip(status.into_iter()) {
-            if self.tx_pool.reach_cycles_limit(cycles) {
-                Err(InternalErrorKind::TransactionPoolFull)?;
-            }
+            self.tx_pool.check_cycles_limit(cycles)?;
 
             let related_dep_out_points = rtx.related_dep_out_points();
             let entry = TxEntry::new(
@@ -227,9 +225,7 @@ fn resolve_tx<'a>(
     tx: TransactionView,
 ) -> Result<(ResolvedTransaction, usize, Capacity, TxStatus), Error> {
     let tx_size = tx.serialized_size();
-    if tx_pool.reach_size_limit(tx_size) {
-        Err(InternalErrorKind::TransactionPoolFull)?;
-    }
+    tx_pool.check_size_limit(tx_size)?;
 
     let short_id = tx.proposal_short_id();
     if snapshot.proposals().contains_proposed(&short_id) {
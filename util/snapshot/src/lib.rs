@@ -7,7 +7,7 @@ use ckb_db::{
 use ckb_error::Error;
 use ckb_proposal_table::ProposalView;
 use ckb_reward_calculator::RewardCalculator;
-use ckb_store::{ChainStore, StoreCache, StoreSnapshot};
+use ckb_store::{ChainStore, StoreCache, StoreSnapshot, TxMetaCache};
 use ckb_traits::BlockMedianTimeContext;
 use ckb_types::core::error::OutPointError;
 use ckb_types::{
@@ -47,7 +47,7 @@ pub struct Snapshot {
     total_difficulty: U256,
     epoch_ext: EpochExt,
     store: StoreSnapshot,
-    cell_set: HamtMap<Byte32, TransactionMeta>,
+    cell_set: Option<HamtMap<Byte32, TransactionMeta>>,
     proposals: ProposalView,
     consensus: Arc<Consensus>,
 }
@@ -58,7 +58,7 @@ impl Snapshot {
         total_difficulty: U256,
         epoch_ext: EpochExt,
         store: StoreSnapshot,
-        cell_set: HamtMap<Byte32, TransactionMeta>,
+        cell_set: Option<HamtMap<Byte32, TransactionMeta>>,
         proposals: ProposalView,
         consensus: Arc<Consensus>,
     ) -> Snapshot {
@@ -93,8 +93,18 @@ impl Snapshot {
         &self.consensus
     }
 
-    pub fn cell_set(&self) -> &HamtMap<Byte32, TransactionMeta> {
-        &self.cell_set
+    /// The in-memory cell set, None if the store is opened in `CellSetMode::Store`
+    pub fn cell_set(&self) -> Option<&HamtMap<Byte32, TransactionMeta>> {
+        self.cell_set.as_ref()
+    }
+
+    /// Get the live cell set entry of the transaction from the in-memory cell set, or from
+    /// the store if there isn't one
+    pub fn get_cell_set_meta(&self, tx_hash: &Byte32) -> Option<TransactionMeta> {
+        match self.cell_set {
+            Some(ref cell_set) => cell_set.get(tx_hash).cloned(),
+            None => self.store.get_tx_meta(tx_hash),
+        }
     }
 
     pub fn proposals(&self) -> &ProposalView {
@@ -120,6 +130,10 @@ impl<'a> ChainStore<'a> for Snapshot {
         self.store.cache()
     }
 
+    fn tx_meta_cache(&'a self, tx_hash: &Byte32) -> Option<(&'a TxMetaCache, Option<u64>)> {
+        self.store.tx_meta_cache(tx_hash)
+    }
+
     fn get(&'a self, col: Col, key: &[u8]) -> Option<Self::Vector> {
         self.store.get(col, key)
     }
@@ -146,7 +160,7 @@ impl CellProvider for Snapshot {
     fn cell(&self, out_point: &OutPoint, with_data: bool) -> CellStatus {
         let tx_hash = out_point.tx_hash();
         let index = out_point.index().unpack();
-        match self.cell_set().get(&tx_hash) {
+        match self.get_cell_set_meta(&tx_hash) {
             Some(tx_meta) => match tx_meta.is_dead(index as usize) {
                 Some(false) => {
                     let mut cell_meta = self
@@ -197,4 +211,10 @@ impl BlockMedianTimeContext for Snapshot {
             header.data().raw().parent_hash(),
         )
     }
+
+    fn block_median_time(&self, block_hash: &Byte32) -> u64 {
+        self.store
+            .get_block_median_time(block_hash, self.median_block_count() as usize)
+            .expect("[ChainState] blocks used for median time exist")
+    }
 }
@@ -60,6 +60,15 @@ impl packed::OutPoint {
     pub fn is_null(&self) -> bool {
         self.tx_hash().is_zero() && Unpack::<u32>::unpack(&self.index()) == u32::max_value()
     }
+
+    /// Parse a cell key, which is the serialized out point, back into `(tx_hash, index)`
+    ///
+    /// Return None if the length or the layout of the key is malformed
+    pub fn parse_cell_key(key: &[u8]) -> Option<(packed::Byte32, u32)> {
+        packed::OutPointReader::from_slice(key)
+            .ok()
+            .map(|reader| (reader.tx_hash().to_entity(), reader.index().unpack()))
+    }
 }
 
 impl packed::CellInput {
@@ -248,3 +257,20 @@ impl packed::CompactBlock {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{packed, prelude::*};
+
+    #[test]
+    fn parse_cell_key() {
+        let tx_hash = packed::Byte32::new([1u8; 32]);
+        let out_point = packed::OutPoint::new(tx_hash.clone(), 3);
+        assert_eq!(
+            Some((tx_hash, 3)),
+            packed::OutPoint::parse_cell_key(out_point.as_slice())
+        );
+        assert!(packed::OutPoint::parse_cell_key(&out_point.as_slice()[1..]).is_none());
+        assert!(packed::OutPoint::parse_cell_key(&[]).is_none());
+    }
+}